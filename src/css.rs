@@ -1,9 +1,137 @@
 // NOTE: 参考: https://limpet.net/mbrubeck/2014/08/13/toy-layout-engine-3-css.html
 // https://github.com/mbrubeck/robinson/blob/master/src/css.rs
 
-#[derive(Debug, PartialEq)]
+mod tokenizer;
+mod color;
+mod import;
+mod calc;
+mod serialize;
+mod cssom;
+pub mod properties;
+pub mod media;
+pub mod supports;
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use self::tokenizer::{tokenize_with_positions, HashType, SourcePosition};
+pub use self::tokenizer::Token;
+use self::color::{parse_hex_color, is_color_function, parse_color_function};
+use self::media::{MediaList, MediaCondition, parse_media_query_list, parse_container_condition};
+use self::supports::{SupportsCondition, parse_supports_condition};
+use self::import::parse_import_prelude;
+use self::calc::{is_math_function, parse_math_function};
+use self::properties::{is_supported_declaration, property_definition};
+pub use self::calc::CalcNode;
+pub use self::color::named_color;
+pub use self::import::load_stylesheet;
+pub use self::serialize::{serialize, minify};
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct Stylesheet {
-    pub rules: Vec<Rule>
+    pub rules: Vec<CssRule>
+}
+
+// NOTE: スタイルシートに並ぶルール。スタイルルールか、@mediaのようなアットルール
+#[derive(Clone, Debug, PartialEq)]
+pub enum CssRule {
+    Style(Rule),
+    Media(MediaRule),
+    Import(ImportRule),
+    Keyframes(KeyframesRule),
+    Supports(SupportsRule),
+    Layer(LayerRule),
+    LayerStatement(LayerStatementRule),
+    Container(ContainerRule)
+}
+
+// NOTE: @media screen and (max-width: 600px) { ... }
+#[derive(Clone, Debug, PartialEq)]
+pub struct MediaRule {
+    pub media: MediaList,
+    pub rules: Vec<CssRule>
+}
+
+// NOTE: @supports (display: grid) and (not (gap: 1px)) { ... }
+#[derive(Clone, Debug, PartialEq)]
+pub struct SupportsRule {
+    pub condition: SupportsCondition,
+    pub rules: Vec<CssRule>
+}
+
+// NOTE: @layer base { ... }。nameはa.bのように.で区切られた名前で、空の場合は無名のレイヤー
+#[derive(Clone, Debug, PartialEq)]
+pub struct LayerRule {
+    pub name: Vec<String>,
+    pub rules: Vec<CssRule>
+}
+
+// NOTE: @layer reset, base; のようにレイヤーの順番だけを宣言する文
+#[derive(Clone, Debug, PartialEq)]
+pub struct LayerStatementRule {
+    pub names: Vec<Vec<String>>
+}
+
+// NOTE: @container card (min-width: 400px) { ... }。名前を省略すると一番近いコンテナに対して評価する
+#[derive(Clone, Debug, PartialEq)]
+pub struct ContainerRule {
+    pub name: Option<String>,
+    pub condition: MediaCondition,
+    pub rules: Vec<CssRule>
+}
+
+// NOTE: @import url("base.css") layer(base) screen;
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImportRule {
+    pub url: String,
+    pub media: MediaList,
+    // NOTE: Some(vec![])は無名のレイヤー
+    pub layer: Option<Vec<String>>,
+    // NOTE: 読み込んだスタイルシート。まだ読み込んでいないか、読み込みに失敗した場合はNone
+    pub stylesheet: Option<Box<Stylesheet>>
+}
+
+// NOTE: @keyframes slide { from { ... } 50% { ... } to { ... } }
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeyframesRule {
+    pub name: String,
+    pub keyframes: Vec<Keyframe>
+}
+
+// NOTE: offsetsはキーフレームセレクタを0~100のパーセンテージにしたもの。fromは0、toは100
+#[derive(Clone, Debug, PartialEq)]
+pub struct Keyframe {
+    pub offsets: Vec<f32>,
+    pub declarations: Vec<Declaration>
+}
+
+// NOTE: パニックさせずに利用者に知らせたい問題。ファイルが読めない、@importが循環している、宣言が不正等
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub path: Option<PathBuf>,
+    pub position: Option<SourcePosition>,
+    pub message: String
+}
+
+impl Diagnostic {
+    fn new(path: &Path, message: String) -> Diagnostic {
+        Diagnostic {path: Some(path.to_path_buf()), position: None, message}
+    }
+}
+
+// NOTE: path:line:column: message の形で表示する
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(ref path) = self.path {
+            write!(f, "{}:", path.display())?;
+        }
+        if let Some(position) = self.position {
+            write!(f, "{}:{}:", position.line, position.column)?;
+        }
+        if self.path.is_some() || self.position.is_some() {
+            write!(f, " ")?;
+        }
+        write!(f, "{}", self.message)
+    }
 }
 
 // 一個のセレクタとdeclaration達の塊
@@ -13,49 +141,170 @@ pub struct Rule {
     pub declarations: Vec<Declaration>
 }
 
-// NOTE: 今はSimpleSelectorだけだけど今後[href="example.com"]とか追加できるようになる
+// NOTE: 今後[href="example.com"]とか追加できるようになる
 #[derive(Clone, Debug, PartialEq)]
 pub enum Selector {
-    Simple(SimpleSelector)
+    Simple(SimpleSelector),
+    // NOTE: div > .a p のように結合子を含むセレクタ。左から順に複合セレクタとその右の結合子を並べ、最後に一番右の複合セレクタを置く
+    Complex(Vec<(SimpleSelector, Combinator)>, SimpleSelector)
 }
 
-// NOTE: #id, .class, bodyみたいな部分
+// NOTE: #id, .class, body, [hidden], :hoverみたいな部分
 #[derive(Clone, Debug, PartialEq)]
 pub struct SimpleSelector {
     pub tag_name: Option<String>,
     pub id: Option<String>,
-    pub class: Vec<String>
+    pub class: Vec<String>,
+    pub attributes: Vec<AttributeSelector>,
+    pub pseudo_classes: Vec<PseudoClass>
+}
+
+// NOTE: [hidden] や [type="text" i] のような属性セレクタ。値を書かない場合はmatcherがNone
+#[derive(Clone, Debug, PartialEq)]
+pub struct AttributeSelector {
+    pub name: String,
+    pub matcher: Option<(AttributeMatcher, String)>,
+    // NOTE: 末尾に i を書くと値の大文字と小文字を区別しない
+    pub case_insensitive: bool
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AttributeMatcher {
+    // NOTE: =
+    Equals,
+    // NOTE: ~= 空白で区切った単語のどれか
+    Includes,
+    // NOTE: |= 値そのものか、値の後ろに - が続くもの
+    DashMatch,
+    // NOTE: ^=
+    Prefix,
+    // NOTE: $=
+    Suffix,
+    // NOTE: *=
+    Substring
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Combinator {
+    // NOTE: 空白
+    Descendant,
+    // NOTE: >
+    Child,
+    // NOTE: +
+    NextSibling,
+    // NOTE: ~
+    SubsequentSibling
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PseudoClass {
+    // NOTE: :is(.a, #b)。入れ子のルールの & もこれに置き換える
+    Is(Vec<Selector>),
+    // NOTE: :is()と同じだが詳細度が0になる
+    Where(Vec<Selector>),
+    Not(Vec<Selector>),
+    // NOTE: :hover のようなユーザーの操作による状態。violetでは操作できないので常に一致しない
+    UserAction(String)
 }
 
 // NOTE: margin: auto;
 #[derive(Clone,Debug, PartialEq)]
 pub struct Declaration {
     pub name: String,
-    pub value: Value
+    pub value: Value,
+    // NOTE: !important が付いているか
+    pub important: bool
 }
 
 // NOTE: margin: auto; のautoの部分
+#[allow(clippy::enum_variant_names)]
 #[derive(Clone,Debug, PartialEq)]
 pub enum Value {
     Keyword(String),
     Length(f32, Unit),
     Percentage(f32),
-    ColorValue(Color)
+    // NOTE: animation-duration: 500ms; のような時間。msは秒に直して持つ
+    Time(f32),
+    // NOTE: rotate(45deg) のような角度。rad, grad, turnは度に直して持つ
+    Angle(f32),
+    // NOTE: line-height: 1.5; のような単位のない数値。単位のない0もここに入り、長さを受け付けるプロパティでは長さの0として扱う
+    Number(f32),
+    ColorValue(Color),
+    // NOTE: content: "→"; のようなクォートされた文字列
+    String(String),
+    // NOTE: url(a.png) と url("a.png") のどちらも中身のURLだけを持つ
+    Url(String),
+    // NOTE: translate(10px, 0) のような色や数式以外の関数。argsはカンマで区切られた引数
+    Function {name: String, args: Vec<Value>},
+    // NOTE: margin: 4px 8px; や font-family: a, b; のように複数の値が並んだもの
+    List(Vec<Value>, Separator),
+    // NOTE: カスタムプロパティの値や、var()を含んでいてまだ解釈できない値
+    Tokens(Vec<Token>),
+    // NOTE: calc(100% - 16px) のような式
+    Calc(CalcNode)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Separator {
+    Space,
+    Comma,
+    // NOTE: font: 12px/1.5 serif; の12px/1.5の部分
+    Slash
 }
 
 impl Value {
+    // NOTE: 相対単位(em, vw等)はstyleのcomputed valueの段階でpxに直されている前提
     pub fn to_px(&self) -> f32 {
+        self.to_px_with_basis(0.0)
+    }
+
+    // NOTE: パーセンテージをpercentage_basis(containing blockの幅等)に対する割合としてpxに直す
+    pub fn to_px_with_basis(&self, percentage_basis: f32) -> f32 {
         match *self {
-            Value::Length(f, Unit::Px) => f,
+            Value::Length(f, unit) => unit.absolute_px().map_or(0.0, |ratio| f * ratio),
+            Value::Percentage(p) => percentage_basis * p / 100.0,
+            Value::Calc(ref node) => node.evaluate(percentage_basis),
             _ => 0.0
         }
     }
 }
 
-// NOTE: 現在pxのみだけど本来はvwとかemとか入る
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Unit {
-    Px
+    // 絶対単位
+    Px,
+    Pt,
+    Pc,
+    In,
+    Cm,
+    Mm,
+    Q,
+    // フォントサイズに対する相対単位
+    Em,
+    Rem,
+    Ex,
+    Ch,
+    // ビューポートに対する相対単位
+    Vw,
+    Vh,
+    Vmin,
+    Vmax
+}
+
+impl Unit {
+    // NOTE: 絶対単位の1単位が何pxかを返す。相対単位は基準となる値がないと決まらないのでNone
+    pub fn absolute_px(&self) -> Option<f32> {
+        match *self {
+            Unit::Px => Some(1.0),
+            Unit::Pt => Some(96.0 / 72.0),
+            Unit::Pc => Some(16.0),
+            Unit::In => Some(96.0),
+            Unit::Cm => Some(96.0 / 2.54),
+            Unit::Mm => Some(96.0 / 25.4),
+            Unit::Q => Some(96.0 / 101.6),
+            _ => None
+        }
+    }
 }
 
 // NOTE: 色の構造体
@@ -70,222 +319,1009 @@ pub struct Color {
 pub type Specificity = (usize, usize, usize);
 
 pub fn parse(source: String) -> Stylesheet {
-    let mut parser = Parser {pos: 0, input: source};
-    Stylesheet {rules: parser.parse_rules()}
+    parse_with_diagnostics(source).0
+}
+
+// NOTE: 不正なルールや宣言はCSS Syntaxのエラー回復の規則に沿って読み飛ばし、その位置と理由を警告として返す
+pub fn parse_with_diagnostics(source: String) -> (Stylesheet, Vec<Diagnostic>) {
+    let (tokens, positions) = tokenize_with_positions(&source);
+    let mut parser = Parser {pos: 0, tokens, positions, diagnostics: Vec::new()};
+    let rules = parser.parse_rules(false);
+    (Stylesheet {rules}, parser.diagnostics)
+}
+
+// NOTE: insert_ruleのように、ルール一つだけを文字列から読む。不正なルールや、ルールが一つでない場合はエラー
+pub fn parse_rule(source: &str) -> Result<CssRule, String> {
+    let (stylesheet, diagnostics) = parse_with_diagnostics(source.to_string());
+    if let Some(diagnostic) = diagnostics.into_iter().next() {
+        return Err(diagnostic.to_string());
+    }
+    let mut rules = stylesheet.rules;
+    match rules.len() {
+        1 => Ok(rules.remove(0)),
+        0 => Err("expected a rule but found nothing".to_string()),
+        _ => Err("expected a single rule but found more".to_string())
+    }
 }
 
+// NOTE: style属性の中身のような、{ } で囲まれていない宣言の並びを読む
+pub fn parse_declaration_list(source: &str) -> (Vec<Declaration>, Vec<Diagnostic>) {
+    let (tokens, positions) = tokenize_with_positions(source);
+    let mut parser = Parser {pos: 0, tokens, positions, diagnostics: Vec::new()};
+    let declarations = parser.parse_declarations(false);
+    (declarations, parser.diagnostics)
+}
 
 impl Selector {
     pub fn specificity(&self) -> Specificity {
-        let Selector::Simple(ref simple) = *self;
-        let a = simple.id.iter().count();
-        let b = simple.class.len();
-        let c = simple.tag_name.iter().count();
+        match *self {
+            Selector::Simple(ref simple) => simple.specificity(),
+            Selector::Complex(ref compounds, ref subject) => compounds.iter()
+                .map(|(compound, _)| compound.specificity())
+                .fold(subject.specificity(), |(a1, b1, c1), (a2, b2, c2)| (a1 + a2, b1 + b2, c1 + c2))
+        }
+    }
+}
+
+impl SimpleSelector {
+    // NOTE: :is()と:not()は引数の中で一番高い詳細度、:where()は0になる
+    pub fn specificity(&self) -> Specificity {
+        let mut a = self.id.iter().count();
+        let mut b = self.class.len() + self.attributes.len();
+        let mut c = self.tag_name.iter().count();
+        for pseudo_class in &self.pseudo_classes {
+            let (a2, b2, c2) = match pseudo_class {
+                PseudoClass::Is(selectors) | PseudoClass::Not(selectors) => selectors.iter().map(Selector::specificity).max().unwrap_or((0, 0, 0)),
+                PseudoClass::Where(_) => (0, 0, 0),
+                PseudoClass::UserAction(_) => (0, 1, 0)
+            };
+            a += a2;
+            b += b2;
+            c += c2;
+        }
         (a,b,c)
     }
 }
 
 
 
+// NOTE: スタイルルールのブロックの中身。宣言か、入れ子のルールを平らにしたもの
+enum BlockItem {
+    Declaration(Declaration),
+    Rules(Vec<Rule>)
+}
+
+// NOTE: tokenizerで作ったトークン列を読み進めるパーサ
 struct Parser {
     pos: usize,
-    input: String
+    tokens: Vec<Token>,
+    // NOTE: tokensの各トークンのソース中の位置
+    positions: Vec<SourcePosition>,
+    diagnostics: Vec<Diagnostic>
 }
 
 
 
 impl Parser {
 
-    fn parse_rules(&mut self) -> Vec<Rule> {
+    // NOTE: nestedは@mediaや@supportsのブロックの中を読んでいるかどうか。その場合は } で終わる
+    fn parse_rules(&mut self, nested: bool) -> Vec<CssRule> {
         let mut rules = Vec::new();
         loop {
-            self.consume_whitespace();
+            // NOTE: トップレベルの <!-- と --> は読み飛ばす
+            while let Some(Token::Whitespace) | Some(Token::Cdo) | Some(Token::Cdc) = self.next_token() {
+                self.consume_token();
+            }
             if self.eof() {break};
-            rules.push(self.parse_rule());
+            if nested && self.next_token() == Some(&Token::CloseCurly) {
+                self.consume_token();
+                break;
+            }
+            match self.next_token() {
+                Some(Token::AtKeyword(_)) => {
+                    // NOTE: @importは@layerの文を除く他のルールより前に書かれたものだけが有効
+                    let allow_import = !nested && rules.iter().all(|rule| matches!(rule, CssRule::Import(_) | CssRule::LayerStatement(_)));
+                    rules.extend(self.parse_at_rule(allow_import))
+                },
+                _ => rules.extend(self.parse_rule(nested, None).into_iter().map(CssRule::Style))
+            }
         }
         rules
     }
 
-    fn parse_rule(&mut self) -> Rule {
-        Rule {
-            selectors: self.parse_selectors(),
-            declarations: self.parse_declarations()
+    // NOTE: 知らないアットルールはブロックごと読み飛ばす
+    fn parse_at_rule(&mut self, allow_import: bool) -> Option<CssRule> {
+        let start = self.pos;
+        let name = match self.consume_token() {
+            Some(Token::AtKeyword(name)) => name,
+            t => panic!("Expected at-keyword but found {:?}", t)
+        };
+        let prelude = self.consume_at_rule_prelude();
+        let has_block = match self.next_token() {
+            Some(Token::OpenCurly) => {self.consume_token(); true},
+            Some(Token::Semicolon) => {self.consume_token(); false},
+            _ => false
+        };
+        match &*name.to_ascii_lowercase() {
+            "media" if has_block => Some(CssRule::Media(MediaRule {
+                media: parse_media_query_list(&prelude),
+                rules: self.parse_rules(true)
+            })),
+            "supports" if has_block => match parse_supports_condition(&prelude) {
+                Some(condition) => Some(CssRule::Supports(SupportsRule {condition, rules: self.parse_rules(true)})),
+                None => {
+                    self.warn(start, "invalid @supports condition".to_string());
+                    self.skip_block();
+                    None
+                }
+            },
+            "layer" => {
+                // NOTE: ブロックの形なら名前は一つまでで、省略すると無名のレイヤー。文の形なら一つ以上の名前をカンマで区切る
+                let prelude = trim_whitespace(&prelude);
+                let names: Option<Vec<Vec<String>>> = if prelude.is_empty() {
+                    Some(vec![])
+                } else {
+                    prelude.split(|token| *token == Token::Comma).map(parse_layer_name).collect()
+                };
+                match (names, has_block) {
+                    (Some(mut names), true) if names.len() <= 1 => Some(CssRule::Layer(LayerRule {
+                        name: names.pop().unwrap_or_default(),
+                        rules: self.parse_rules(true)
+                    })),
+                    (Some(names), false) if !names.is_empty() => Some(CssRule::LayerStatement(LayerStatementRule {names})),
+                    _ => {
+                        self.warn(start, "invalid @layer".to_string());
+                        if has_block {
+                            self.skip_block();
+                        }
+                        None
+                    }
+                }
+            },
+            "container" if has_block => match parse_container_prelude(&prelude) {
+                Some((name, condition)) => Some(CssRule::Container(ContainerRule {name, condition, rules: self.parse_rules(true)})),
+                None => {
+                    self.warn(start, "invalid @container".to_string());
+                    self.skip_block();
+                    None
+                }
+            },
+            "keyframes" if has_block => {
+                let keyframes_rule = self.parse_keyframes(&prelude);
+                if keyframes_rule.is_none() {
+                    self.warn(start, "invalid @keyframes name".to_string());
+                }
+                keyframes_rule.map(CssRule::Keyframes)
+            },
+            "import" if !has_block => {
+                if !allow_import {
+                    self.warn(start, "@import must come before all other rules".to_string());
+                    return None;
+                }
+                let import_rule = parse_import_prelude(&prelude);
+                if import_rule.is_none() {
+                    self.warn(start, "invalid @import".to_string());
+                }
+                import_rule.map(CssRule::Import)
+            },
+            _ => {
+                if has_block {
+                    self.skip_block();
+                }
+                None
+            }
         }
     }
 
-    fn parse_selectors(&mut self) -> Vec<Selector> {
-        let mut selectors = Vec::new();
+    // NOTE: { の直後から読み始める。名前が不正な場合はブロックごと読み飛ばす
+    fn parse_keyframes(&mut self, prelude: &[Token]) -> Option<KeyframesRule> {
+        let name = match trim_whitespace(prelude) {
+            [Token::Ident(name)] if !is_reserved_keyframes_name(name) => name.clone(),
+            [Token::String(name)] => name.clone(),
+            _ => {
+                self.skip_block();
+                return None;
+            }
+        };
+        let mut keyframes = Vec::new();
         loop {
-            selectors.push(Selector::Simple(self.parse_simple_selector()));
             self.consume_whitespace();
-            match self.next_char() {
-                ',' => {self.consume_char(); self.consume_whitespace();}
-                '{' => break,
-                c => panic!("Unexpected character {} in selector list", c)
+            let start = self.pos;
+            match self.next_token() {
+                Some(Token::CloseCurly) => {
+                    self.consume_token();
+                    break;
+                },
+                None => {
+                    self.warn(start, "unexpected end of input in @keyframes".to_string());
+                    break;
+                },
+                _ => {}
+            }
+            self.consume_rule_prelude(true);
+            if self.next_token() != Some(&Token::OpenCurly) {
+                self.warn(start, "expected '{' after keyframe selector".to_string());
+                continue;
+            }
+            let offsets = self.sub_parser(start, self.pos).parse_keyframe_selectors();
+            self.consume_token();
+            match offsets {
+                Ok(offsets) => {
+                    // NOTE: キーフレームの中の!importantの宣言は無視される
+                    let (important, declarations): (Vec<Declaration>, Vec<Declaration>) = self.parse_declarations(true)
+                        .into_iter().partition(|declaration| declaration.important);
+                    if !important.is_empty() {
+                        self.warn(start, "!important is ignored in keyframes".to_string());
+                    }
+                    keyframes.push(Keyframe {offsets, declarations});
+                },
+                Err(message) => {
+                    self.warn(start, message);
+                    self.skip_block();
+                }
             }
         }
-        selectors.sort_by(|a,b| b.specificity().cmp(&a.specificity()));
-        selectors
+        Some(KeyframesRule {name, keyframes})
     }
 
-    fn parse_declarations(&mut self) -> Vec<Declaration> {
-        assert_eq!(self.consume_char(), '{');
-        let mut declarations = Vec::new();
+    fn parse_keyframe_selectors(&mut self) -> Result<Vec<f32>, String> {
+        let mut offsets = Vec::new();
         loop {
             self.consume_whitespace();
-            if self.next_char() == '}' {
-                self.consume_char();
-                break;
+            let offset = match self.consume_token() {
+                Some(Token::Ident(ref name)) if name.eq_ignore_ascii_case("from") => 0.0,
+                Some(Token::Ident(ref name)) if name.eq_ignore_ascii_case("to") => 100.0,
+                Some(Token::Percentage(p)) if (0.0..=100.0).contains(&p) => p,
+                t => return Err(format!("invalid keyframe selector {:?}", t))
+            };
+            offsets.push(offset);
+            self.consume_whitespace();
+            match self.consume_token() {
+                Some(Token::Comma) => {},
+                None => break,
+                Some(t) => return Err(format!("unexpected {:?} in keyframe selector", t))
+            }
+        }
+        Ok(offsets)
+    }
+
+    // NOTE: アットルールの名前の後ろから { か ; の手前までのトークンを返す
+    fn consume_at_rule_prelude(&mut self) -> Vec<Token> {
+        let mut prelude = Vec::new();
+        let mut depth = 0;
+        while let Some(token) = self.next_token() {
+            match token {
+                Token::OpenCurly | Token::Semicolon | Token::CloseCurly if depth == 0 => break,
+                Token::Function(_) | Token::OpenParen | Token::OpenSquare => depth += 1,
+                Token::CloseParen | Token::CloseSquare => depth -= 1,
+                _ => {}
+            }
+            prelude.push(self.consume_token().unwrap());
+        }
+        prelude
+    }
+
+    // NOTE: { の直後から対応する } までを読み飛ばす
+    fn skip_block(&mut self) {
+        let mut depth = 0;
+        while let Some(token) = self.consume_token() {
+            match token {
+                Token::OpenCurly => depth += 1,
+                Token::CloseCurly if depth == 0 => break,
+                Token::CloseCurly => depth -= 1,
+                _ => {}
             }
-            declarations.push(self.parse_declaration());
         }
-        declarations
     }
 
-    fn parse_simple_selector(&mut self) -> SimpleSelector {
-        let mut selector = SimpleSelector {tag_name: None, id: None, class: Vec::new()};
-        while !self.eof() {
-            match self.next_char() {
-                '#' => {
-                    self.consume_char();
-                    selector.id = Some(self.parse_identifier());
+    // NOTE: セレクタが不正な場合はルールをブロックごと読み飛ばす
+    // NOTE: parentは入れ子のルールを読んでいる時の親のルールのセレクタ。入れ子のルールは平らにして、親のルールの後ろに書かれた順に並べる
+    fn parse_rule(&mut self, nested: bool, parent: Option<&[Selector]>) -> Vec<Rule> {
+        let start = self.pos;
+        self.consume_rule_prelude(nested);
+        if self.next_token() != Some(&Token::OpenCurly) {
+            self.warn(start, "expected '{' after selector".to_string());
+            return vec![];
+        }
+        let selectors = self.sub_parser(start, self.pos).parse_selectors(parent, parent.is_some());
+        self.consume_token();
+        match selectors {
+            Ok(selectors) => self.parse_style_block(selectors),
+            Err(message) => {
+                self.warn(start, message);
+                self.skip_block();
+                vec![]
+            }
+        }
+    }
+
+    // NOTE: 入れ子のルールより後ろに書かれた宣言は、入れ子のルールの後ろに & を セレクタとするルールとして並べる
+    fn parse_style_block(&mut self, selectors: Vec<Selector>) -> Vec<Rule> {
+        let items = self.parse_block_contents(true, Some(&selectors));
+        let mut rules = vec![Rule {selectors: selectors.clone(), declarations: Vec::new()}];
+        let mut after_nested_rules = false;
+        for item in items {
+            match item {
+                BlockItem::Declaration(declaration) => {
+                    if after_nested_rules {
+                        rules.push(Rule {selectors: vec![nesting_selector(&selectors)], declarations: Vec::new()});
+                        after_nested_rules = false;
+                    }
+                    rules.last_mut().unwrap().declarations.push(declaration);
+                },
+                BlockItem::Rules(nested_rules) => {
+                    after_nested_rules |= !nested_rules.is_empty();
+                    rules.extend(nested_rules);
                 }
-                '.' => {
-                    self.consume_char();
-                    selector.class.push(self.parse_identifier());
+            }
+        }
+        rules
+    }
+
+    // NOTE: { の手前まで読み進める。トップレベルの } はセレクタの一部として扱われる
+    fn consume_rule_prelude(&mut self, nested: bool) {
+        let mut depth = 0;
+        while let Some(token) = self.next_token() {
+            match token {
+                Token::OpenCurly if depth == 0 => break,
+                Token::CloseCurly if depth == 0 && nested => break,
+                Token::Function(_) | Token::OpenParen | Token::OpenSquare => depth += 1,
+                Token::CloseParen | Token::CloseSquare => depth -= 1,
+                _ => {}
+            }
+            self.consume_token();
+        }
+    }
+
+    // NOTE: parentがある時は入れ子のルールの中のセレクタとして読み、& を親のセレクタに置き換える
+    // relativeなら入れ子のルールのセレクタで、& を含まないセレクタは先頭に「& 」があるものとして扱う。> .a のように結合子から書き始めることもできる
+    fn parse_selectors(&mut self, parent: Option<&[Selector]>, relative: bool) -> Result<Vec<Selector>, String> {
+        let mut selectors = Vec::new();
+        self.consume_whitespace();
+        loop {
+            selectors.push(self.parse_complex_selector(parent, relative)?);
+            match self.consume_token() {
+                Some(Token::Comma) => self.consume_whitespace(),
+                None => break,
+                Some(t) => return Err(format!("unexpected {:?} in selector", t))
+            }
+        }
+        Ok(selectors)
+    }
+
+    // NOTE: , か最後の手前まで読む
+    fn parse_complex_selector(&mut self, parent: Option<&[Selector]>, relative: bool) -> Result<Selector, String> {
+        let mut compounds = Vec::new();
+        let mut contains_nesting = false;
+        let leading_combinator = self.parse_combinator();
+        self.consume_whitespace();
+        loop {
+            let (compound, nesting) = self.parse_simple_selector(parent)?;
+            contains_nesting |= nesting;
+            let had_whitespace = self.next_token() == Some(&Token::Whitespace);
+            self.consume_whitespace();
+            let combinator = match self.parse_combinator() {
+                Some(combinator) => combinator,
+                None if had_whitespace && !matches!(self.next_token(), None | Some(Token::Comma)) => Combinator::Descendant,
+                None => {
+                    compounds.push((compound, Combinator::Descendant));
+                    break;
                 }
-                '*' => {
-                    self.consume_char();
+            };
+            self.consume_whitespace();
+            compounds.push((compound, combinator));
+        }
+        match parent {
+            Some(parent) if relative && !contains_nesting => {
+                compounds.insert(0, (nesting_compound(parent), leading_combinator.unwrap_or(Combinator::Descendant)));
+            },
+            _ if leading_combinator.is_some() => return Err("selector cannot start with a combinator".to_string()),
+            _ => {}
+        }
+        let (subject, _) = compounds.pop().unwrap();
+        Ok(if compounds.is_empty() {Selector::Simple(subject)} else {Selector::Complex(compounds, subject)})
+    }
+
+    fn parse_combinator(&mut self) -> Option<Combinator> {
+        let combinator = match self.next_token()? {
+            Token::Delim('>') => Combinator::Child,
+            Token::Delim('+') => Combinator::NextSibling,
+            Token::Delim('~') => Combinator::SubsequentSibling,
+            _ => return None
+        };
+        self.consume_token();
+        Some(combinator)
+    }
+
+    // NOTE: { の直後から読み始める。不正な宣言は ; か } まで読み飛ばし、最後の宣言の ; は省略できる
+    // NOTE: in_blockがfalseの時は { } で囲まれていない宣言の並びとして最後まで読む
+    fn parse_declarations(&mut self, in_block: bool) -> Vec<Declaration> {
+        self.parse_block_contents(in_block, None).into_iter()
+            .filter_map(|item| match item {
+                BlockItem::Declaration(declaration) => Some(declaration),
+                BlockItem::Rules(_) => None
+            })
+            .collect()
+    }
+
+    // NOTE: parentはスタイルルールのブロックを読んでいる時のそのセレクタ。その場合は入れ子のルールも読む
+    fn parse_block_contents(&mut self, in_block: bool, parent: Option<&[Selector]>) -> Vec<BlockItem> {
+        let mut items = Vec::new();
+        loop {
+            let start = self.pos;
+            match self.next_token() {
+                Some(Token::Whitespace) | Some(Token::Semicolon) => {self.consume_token();},
+                Some(Token::CloseCurly) if in_block => {
+                    self.consume_token();
+                    break;
+                },
+                Some(Token::CloseCurly) => {
+                    self.consume_token();
+                    self.warn(start, "unexpected '}' in declaration list".to_string());
+                },
+                None => {
+                    if in_block {
+                        self.warn(start, "unexpected end of input in declaration block".to_string());
+                    }
+                    break;
+                },
+                Some(Token::AtKeyword(name)) => {
+                    let message = format!("unexpected @{} in declaration block", name);
+                    self.consume_token();
+                    self.consume_at_rule_prelude();
+                    if self.next_token() == Some(&Token::OpenCurly) {
+                        self.consume_token();
+                        self.skip_block();
+                    }
+                    self.warn(start, message);
+                },
+                _ => match parent {
+                    Some(parent) if self.starts_nested_rule() => items.push(BlockItem::Rules(self.parse_rule(true, Some(parent)))),
+                    _ => {
+                        self.consume_declaration_tokens();
+                        match self.sub_parser(start, self.pos).parse_declaration() {
+                            Ok(declaration) => items.push(BlockItem::Declaration(declaration)),
+                            Err(message) => self.warn(start, message)
+                        }
+                    }
+                }
+            }
+        }
+        items
+    }
+
+    // NOTE: ; や } より先に { が来るものは入れ子のルール。カスタムプロパティの値に書かれた { } は除く
+    fn starts_nested_rule(&self) -> bool {
+        if matches!(self.next_token(), Some(Token::Ident(name)) if name.starts_with("--")) {
+            return false;
+        }
+        let mut depth = 0;
+        for token in &self.tokens[self.pos..] {
+            match token {
+                Token::OpenCurly if depth == 0 => return true,
+                Token::Semicolon | Token::CloseCurly if depth == 0 => return false,
+                Token::Function(_) | Token::OpenParen | Token::OpenSquare => depth += 1,
+                Token::CloseParen | Token::CloseSquare => depth -= 1,
+                _ => {}
+            }
+        }
+        false
+    }
+
+    // NOTE: 複合セレクタと、その中に & が含まれていたかを返す
+    fn parse_simple_selector(&mut self, parent: Option<&[Selector]>) -> Result<(SimpleSelector, bool), String> {
+        let mut selector = SimpleSelector {tag_name: None, id: None, class: Vec::new(), attributes: Vec::new(), pseudo_classes: Vec::new()};
+        let mut contains_nesting = false;
+        let start = self.pos;
+        loop {
+            match self.next_token() {
+                Some(Token::Hash(_, HashType::Id)) => {
+                    if let Some(Token::Hash(id, _)) = self.consume_token() {
+                        selector.id = Some(id);
+                    }
                 }
-                c if valid_identifier_char(c) => {
-                    selector.tag_name = Some(self.parse_identifier());
+                Some(Token::Delim('.')) => {
+                    self.consume_token();
+                    match self.consume_token() {
+                        Some(Token::Ident(class)) => selector.class.push(class),
+                        t => return Err(format!("expected class name after '.' but found {:?}", t))
+                    }
+                }
+                Some(Token::Delim('*')) => {
+                    self.consume_token();
+                }
+                Some(Token::Ident(_)) => {
+                    if let Some(Token::Ident(tag_name)) = self.consume_token() {
+                        selector.tag_name = Some(tag_name);
+                    }
+                }
+                Some(Token::OpenSquare) => {
+                    self.consume_token();
+                    let mut tokens = Vec::new();
+                    loop {
+                        match self.consume_token() {
+                            Some(Token::CloseSquare) => break,
+                            Some(token) => tokens.push(token),
+                            None => return Err("unclosed attribute selector".to_string())
+                        }
+                    }
+                    match parse_attribute_selector(&tokens) {
+                        Some(attribute) => selector.attributes.push(attribute),
+                        None => return Err(format!("invalid attribute selector {:?}", tokens))
+                    }
+                }
+                Some(Token::Colon) => {
+                    self.consume_token();
+                    selector.pseudo_classes.push(self.parse_pseudo_class(parent)?);
+                }
+                Some(Token::Delim('&')) => {
+                    self.consume_token();
+                    match parent {
+                        Some(parent) => selector = merge_compounds(selector, nesting_compound(parent)),
+                        None => return Err("'&' is only allowed in nested rules".to_string())
+                    }
+                    contains_nesting = true;
                 }
                 _ => break
             }
         }
-        selector
+        if self.pos == start {
+            return Err(format!("unexpected {:?} in selector", self.next_token()));
+        }
+        Ok((selector, contains_nesting))
     }
 
+    // NOTE: : の直後から読む。:is()等の引数の中でも & を使える
+    fn parse_pseudo_class(&mut self, parent: Option<&[Selector]>) -> Result<PseudoClass, String> {
+        match self.consume_token() {
+            Some(Token::Ident(name)) => match &*name.to_ascii_lowercase() {
+                "hover" | "active" | "focus" | "focus-visible" | "focus-within" | "visited" | "target" => Ok(PseudoClass::UserAction(name)),
+                _ => Err(format!("unknown pseudo-class :{}", name))
+            },
+            Some(Token::Function(name)) => {
+                let args = self.consume_function_arguments();
+                let mut parser = Parser {pos: 0, tokens: args, positions: Vec::new(), diagnostics: Vec::new()};
+                let selectors = match &*name.to_ascii_lowercase() {
+                    "is" | "where" | "not" => parser.parse_selectors(parent, false).map_err(|_| format!("invalid selector in :{}()", name))?,
+                    _ => return Err(format!("unknown pseudo-class :{}()", name))
+                };
+                Ok(match &*name.to_ascii_lowercase() {
+                    "is" => PseudoClass::Is(selectors),
+                    "where" => PseudoClass::Where(selectors),
+                    _ => PseudoClass::Not(selectors)
+                })
+            },
+            t => Err(format!("expected pseudo-class name after ':' but found {:?}", t))
+        }
+    }
 
-
-    fn parse_declaration(&mut self) -> Declaration {
-        let property_name = self.parse_identifier();
+    // NOTE: 宣言一つ分のトークンだけを持ったパーサで呼ぶ
+    fn parse_declaration(&mut self) -> Result<Declaration, String> {
+        let property_name = match self.consume_token() {
+            Some(Token::Ident(name)) => name,
+            t => return Err(format!("expected property name but found {:?}", t))
+        };
         self.consume_whitespace();
-        assert_eq!(self.consume_char(), ':');
-        self.consume_whitespace();
-        let value = self.parse_value();
+        if self.consume_token() != Some(Token::Colon) {
+            return Err(format!("expected ':' after {}", property_name));
+        }
         self.consume_whitespace();
-        assert_eq!(self.consume_char(), ';');
+        // NOTE: カスタムプロパティとvar()を含む値はcomputed valueの段階まで解釈できないのでトークン列のまま持つ
+        let (value, important) = if property_name.starts_with("--") || self.value_contains_var() {
+            let mut tokens = self.tokens[self.pos..].to_vec();
+            let important = strip_important(&mut tokens);
+            let mut tokens = trim_whitespace(&tokens).to_vec();
+            tokens.extend(unclosed_block_ends(&tokens));
+            (Value::Tokens(tokens), important)
+        } else {
+            let value = self.parse_value().ok_or_else(|| format!("invalid value for {}", property_name))?;
+            self.consume_whitespace();
+            let important = self.parse_important()?;
+            if !self.eof() {
+                return Err(format!("invalid value for {}", property_name));
+            }
+            (value, important)
+        };
+        // NOTE: 知らないプロパティと、プロパティが受け付けない値の宣言は読み飛ばす
+        if !is_supported_declaration(&property_name, &value) {
+            return Err(match property_definition(&property_name) {
+                Some(_) => format!("invalid value for {}", property_name),
+                None => format!("unknown property {}", property_name)
+            });
+        }
 
-        Declaration {
-            name: property_name,
+        // NOTE: プロパティ名は小文字にそろえて持つ。カスタムプロパティは大文字と小文字を区別するので書かれたまま
+        let name = if property_name.starts_with("--") {property_name} else {property_name.to_ascii_lowercase()};
+        Ok(Declaration {
+            name,
             value,
+            important
+        })
+    }
+
+    fn value_contains_var(&self) -> bool {
+        self.tokens[self.pos.min(self.tokens.len())..].iter()
+            .any(|token| matches!(token, Token::Function(name) if name.eq_ignore_ascii_case("var")))
+    }
+
+    // NOTE: 宣言を ; か } の手前まで読み進める。括弧の中の ; や } は値の一部
+    fn consume_declaration_tokens(&mut self) {
+        let mut depth = 0;
+        while let Some(token) = self.next_token() {
+            match token {
+                Token::Semicolon | Token::CloseCurly if depth == 0 => break,
+                Token::Function(_) | Token::OpenParen | Token::OpenSquare | Token::OpenCurly => depth += 1,
+                Token::CloseParen | Token::CloseSquare | Token::CloseCurly => depth -= 1,
+                _ => {}
+            }
+            self.consume_token();
         }
     }
 
-    fn parse_value(&mut self) -> Value {
-        match self.next_char() {
-            '0'..='9' => self.parse_start_with_num_value(),
-            '#' => self.parse_color(),
-            _ => Value::Keyword(self.parse_identifier())
+    // NOTE: start番目からend番目の手前までのトークンだけを読むパーサを作る
+    fn sub_parser(&self, start: usize, end: usize) -> Parser {
+        Parser {
+            pos: 0,
+            tokens: self.tokens[start..end].to_vec(),
+            positions: self.positions.get(start..end).map_or_else(Vec::new, |positions| positions.to_vec()),
+            diagnostics: Vec::new()
         }
     }
 
-    fn parse_start_with_num_value(&mut self) -> Value {
-        let num_value = self.parse_float();
-        match self.next_char() {
-            '%' => {
-                self.consume_char();
-                Value::Percentage(num_value)
+    fn warn(&mut self, start: usize, message: String) {
+        let position = self.positions.get(start).or_else(|| self.positions.last()).copied();
+        self.diagnostics.push(Diagnostic {path: None, position, message});
+    }
+
+    // NOTE: ! と important の間には空白やコメントがあってもよい
+    fn parse_important(&mut self) -> Result<bool, String> {
+        if self.next_token() != Some(&Token::Delim('!')) {
+            return Ok(false);
+        }
+        self.consume_token();
+        self.consume_whitespace();
+        match self.consume_token() {
+            Some(Token::Ident(ref name)) if name.eq_ignore_ascii_case("important") => {
+                self.consume_whitespace();
+                Ok(true)
             },
-            _ => Value::Length(num_value, self.parse_unit())
+            t => Err(format!("expected important after ! but found {:?}", t))
         }
     }
 
-    fn parse_float(&mut self) -> f32 {
-        let s = self.consume_while(|c| match c {
-            '0'..='9' | '.' => true,
-            _ => false
-        });
-        s.parse().unwrap()
+    // NOTE: カンマ区切りのリストの中にスペース区切りのリストが入る。値が一つだけならリストにしない
+    // NOTE: 解釈できない値の場合はNone
+    fn parse_value(&mut self) -> Option<Value> {
+        let mut values = vec![self.parse_space_separated_values()?];
+        while self.peek_past_whitespace() == Some(&Token::Comma) {
+            self.consume_whitespace();
+            self.consume_token();
+            values.push(self.parse_space_separated_values()?);
+        }
+        Some(into_list(values, Separator::Comma))
     }
 
-    fn parse_unit(&mut self) -> Unit {
-        match &*self.parse_identifier().to_ascii_lowercase() {
-            "px" => Unit::Px,
-            _ => panic!("unrecognized unit")
+    fn parse_space_separated_values(&mut self) -> Option<Value> {
+        let mut values = Vec::new();
+        loop {
+            self.consume_whitespace();
+            match self.next_token() {
+                None | Some(Token::Semicolon) | Some(Token::CloseCurly) | Some(Token::Comma) | Some(Token::Delim('!')) => break,
+                _ => values.push(self.parse_slash_separated_values()?)
+            }
+        }
+        if values.is_empty() {
+            return None;
         }
+        Some(into_list(values, Separator::Space))
     }
 
-    fn parse_color(&mut self) -> Value {
-        assert_eq!(self.consume_char(), '#');
-        Value::ColorValue(Color {
-            r: self.parse_hex_pair(),
-            g: self.parse_hex_pair(),
-            b: self.parse_hex_pair(),
-            a: 255
-        })
+    fn parse_slash_separated_values(&mut self) -> Option<Value> {
+        let mut values = vec![self.parse_component_value()?];
+        while self.peek_past_whitespace() == Some(&Token::Delim('/')) {
+            self.consume_whitespace();
+            self.consume_token();
+            self.consume_whitespace();
+            values.push(self.parse_component_value()?);
+        }
+        Some(into_list(values, Separator::Slash))
     }
 
-    fn parse_hex_pair(&mut self) -> u8 {
-        let s = &self.input[self.pos..self.pos + 2];
-        self.pos += 2;
-        u8::from_str_radix(s, 16).unwrap()
+    fn parse_component_value(&mut self) -> Option<Value> {
+        let value = match self.consume_token()? {
+            Token::Dimension(num_value, unit) => match &*unit.to_ascii_lowercase() {
+                "s" => Value::Time(num_value),
+                "ms" => Value::Time(num_value / 1000.0),
+                unit_name => match angle_in_degrees(num_value, unit_name) {
+                    Some(degrees) => Value::Angle(degrees),
+                    None => Value::Length(num_value, parse_unit(&unit)?)
+                }
+            },
+            Token::Percentage(num_value) => Value::Percentage(num_value),
+            Token::Number(num_value) => Value::Number(num_value),
+            Token::String(string) => Value::String(string),
+            Token::Url(url) => Value::Url(url),
+            Token::Hash(hex, _) => Value::ColorValue(parse_hex_color(&hex)?),
+            // NOTE: redのような色の名前もキーワードのまま持ち、色を受け付けるプロパティで色として扱う
+            Token::Ident(keyword) => Value::Keyword(keyword),
+            Token::Function(name) => {
+                let args = self.consume_function_arguments();
+                if is_math_function(&name) {
+                    Value::Calc(parse_math_function(&name, &args)?)
+                } else if is_color_function(&name) {
+                    Value::ColorValue(parse_color_function(&name, &args)?)
+                } else if name.eq_ignore_ascii_case("url") {
+                    match trim_whitespace(&args) {
+                        [Token::String(url)] => Value::Url(url.clone()),
+                        _ => return None
+                    }
+                } else {
+                    Value::Function {name, args: parse_function_arguments(args)?}
+                }
+            },
+            _ => return None
+        };
+        Some(value)
+    }
+
+    // NOTE: 関数トークンの直後から対応する ) までのトークンを返す。) 自体は返り値に含めない
+    fn consume_function_arguments(&mut self) -> Vec<Token> {
+        let mut args = Vec::new();
+        let mut depth = 0;
+        while let Some(token) = self.consume_token() {
+            match token {
+                Token::Function(_) | Token::OpenParen => depth += 1,
+                Token::CloseParen if depth == 0 => break,
+                Token::CloseParen => depth -= 1,
+                _ => {}
+            }
+            args.push(token);
+        }
+        args
     }
 
-    fn parse_identifier(&mut self) -> String {
-        self.consume_while(valid_identifier_char)
+    fn next_token(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
     }
 
-    fn next_char(&self) -> char {
-        self.input[self.pos..].chars().next().unwrap()
+    fn peek_past_whitespace(&self) -> Option<&Token> {
+        self.tokens[self.pos.min(self.tokens.len())..].iter().find(|token| **token != Token::Whitespace)
     }
 
     fn eof(&self)->bool {
-        self.pos >= self.input.len()
+        self.pos >= self.tokens.len()
     }
 
-    fn consume_char(&mut self) -> char {
-        let mut iter = self.input[self.pos..].char_indices();
-        let (_, cur_char) = iter.next().unwrap();
-        let (next_pos, _) = iter.next().unwrap_or((1, ' '));
-        // NOTE: += next_posがなぞ
-        self.pos += next_pos;
-        return cur_char;
+    fn consume_token(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
     }
 
-    fn consume_while<F>(&mut self, test: F) -> String where F: Fn(char) -> bool {
-        let mut result = String::new();
-        while !self.eof() && test(self.next_char()) {
-            result.push(self.consume_char());
+    fn consume_whitespace(&mut self) {
+        while self.next_token() == Some(&Token::Whitespace) {
+            self.consume_token();
         }
-        result
     }
+}
 
-    fn consume_whitespace(&mut self) {
-        self.consume_while(char::is_whitespace);
+// NOTE: var()を置換した後のトークン列を値として解釈する。解釈できなければNone
+pub fn parse_value_tokens(tokens: Vec<Token>) -> Option<Value> {
+    let mut parser = Parser {pos: 0, tokens, positions: Vec::new(), diagnostics: Vec::new()};
+    parser.consume_whitespace();
+    let value = parser.parse_value()?;
+    parser.consume_whitespace();
+    if parser.eof() {Some(value)} else {None}
+}
+
+// NOTE: 入れ子のルールの & を置き換える複合セレクタ。親のセレクタが複合セレクタ一つだけならそのまま使い、そうでなければ:is()で囲む
+fn nesting_compound(parent: &[Selector]) -> SimpleSelector {
+    match parent {
+        [Selector::Simple(compound)] => compound.clone(),
+        parent => SimpleSelector {tag_name: None, id: None, class: Vec::new(), attributes: Vec::new(), pseudo_classes: vec![PseudoClass::Is(parent.to_vec())]}
     }
 }
 
-fn valid_identifier_char(c: char) -> bool {
-    match c {
-        'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => true,
-        _ => false,
+fn nesting_selector(parent: &[Selector]) -> Selector {
+    Selector::Simple(nesting_compound(parent))
+}
+
+// NOTE: &.a のように複合セレクタの中に書かれた & を展開する。要素名やidが両方にある場合はまとめられないので:is()で囲む
+fn merge_compounds(mut selector: SimpleSelector, other: SimpleSelector) -> SimpleSelector {
+    if selector.tag_name.is_some() && other.tag_name.is_some() || selector.id.is_some() && other.id.is_some() {
+        selector.pseudo_classes.push(PseudoClass::Is(vec![Selector::Simple(other)]));
+        return selector;
     }
+    selector.tag_name = selector.tag_name.or(other.tag_name);
+    selector.id = selector.id.or(other.id);
+    selector.class.extend(other.class);
+    selector.attributes.extend(other.attributes);
+    selector.pseudo_classes.extend(other.pseudo_classes);
+    selector
+}
+
+// NOTE: [ と ] の間のトークン。name、name 演算子 値、name 演算子 値 i の3通り
+fn parse_attribute_selector(tokens: &[Token]) -> Option<AttributeSelector> {
+    let tokens: Vec<&Token> = tokens.iter().filter(|token| **token != Token::Whitespace).collect();
+    let (name, rest) = match tokens.split_first() {
+        Some((Token::Ident(name), rest)) => (name.clone(), rest),
+        _ => return None
+    };
+    let (matcher, rest) = match rest {
+        [] => return Some(AttributeSelector {name, matcher: None, case_insensitive: false}),
+        [Token::Delim('='), rest @ ..] => (AttributeMatcher::Equals, rest),
+        [Token::Delim(c), Token::Delim('='), rest @ ..] => (match c {
+            '~' => AttributeMatcher::Includes,
+            '|' => AttributeMatcher::DashMatch,
+            '^' => AttributeMatcher::Prefix,
+            '$' => AttributeMatcher::Suffix,
+            '*' => AttributeMatcher::Substring,
+            _ => return None
+        }, rest),
+        _ => return None
+    };
+    let (value, rest) = match rest.split_first() {
+        Some((Token::Ident(value), rest)) | Some((Token::String(value), rest)) => (value.clone(), rest),
+        _ => return None
+    };
+    let case_insensitive = match rest {
+        [] => false,
+        [Token::Ident(flag)] if flag.eq_ignore_ascii_case("i") => true,
+        [Token::Ident(flag)] if flag.eq_ignore_ascii_case("s") => false,
+        _ => return None
+    };
+    Some(AttributeSelector {name, matcher: Some((matcher, value)), case_insensitive})
+}
+
+// NOTE: a.b のように.で区切った識別子。間に空白は書けず、CSS全体で使えるキーワードは名前にできない
+fn parse_layer_name(tokens: &[Token]) -> Option<Vec<String>> {
+    let tokens = trim_whitespace(tokens);
+    let mut names = Vec::new();
+    for (i, token) in tokens.iter().enumerate() {
+        match token {
+            Token::Ident(name) if i % 2 == 0 && !is_css_wide_keyword(name) => names.push(name.clone()),
+            Token::Delim('.') if i % 2 == 1 => {},
+            _ => return None
+        }
+    }
+    if tokens.len() % 2 == 1 {Some(names)} else {None}
+}
+
+// NOTE: [<container-name>]? <container-condition>
+fn parse_container_prelude(tokens: &[Token]) -> Option<(Option<String>, MediaCondition)> {
+    let tokens = trim_whitespace(tokens);
+    match tokens.first() {
+        Some(Token::Ident(name)) if !name.eq_ignore_ascii_case("not") => {
+            if is_css_wide_keyword(name) || ["none", "and", "or"].iter().any(|reserved| name.eq_ignore_ascii_case(reserved)) {
+                return None;
+            }
+            Some((Some(name.clone()), parse_container_condition(&tokens[1..])?))
+        },
+        _ => Some((None, parse_container_condition(tokens)?))
+    }
+}
+
+// NOTE: noneとCSS全体で使えるキーワードは@keyframesの名前にできない。使いたい場合は文字列で書く
+fn is_reserved_keyframes_name(name: &str) -> bool {
+    is_css_wide_keyword(name) || ["none", "default"].iter().any(|reserved| name.eq_ignore_ascii_case(reserved))
+}
+
+fn is_css_wide_keyword(name: &str) -> bool {
+    ["initial", "inherit", "unset", "revert", "revert-layer"].iter().any(|keyword| name.eq_ignore_ascii_case(keyword))
+}
+
+// NOTE: 関数の引数をカンマで区切ってそれぞれ値として解釈する。引数がなければ空
+fn parse_function_arguments(args: Vec<Token>) -> Option<Vec<Value>> {
+    if trim_whitespace(&args).is_empty() {
+        return Some(vec![]);
+    }
+    match parse_value_tokens(args)? {
+        Value::List(values, Separator::Comma) => Some(values),
+        value => Some(vec![value])
+    }
+}
+
+// NOTE: 末尾の !important を取り除き、付いていたかどうかを返す
+fn strip_important(tokens: &mut Vec<Token>) -> bool {
+    let mut rest = tokens.iter().enumerate().rev().filter(|(_, t)| **t != Token::Whitespace);
+    let bang = match (rest.next(), rest.next()) {
+        (Some((_, Token::Ident(name))), Some((bang, Token::Delim('!')))) if name.eq_ignore_ascii_case("important") => bang,
+        _ => return false
+    };
+    tokens.truncate(bang);
+    true
+}
+
+pub fn trim_whitespace(tokens: &[Token]) -> &[Token] {
+    let start = tokens.iter().position(|t| *t != Token::Whitespace).unwrap_or(tokens.len());
+    let end = tokens.iter().rposition(|t| *t != Token::Whitespace).map_or(start, |i| i + 1);
+    &tokens[start..end]
+}
+
+// NOTE: 閉じられないまま終わった関数や括弧を閉じるトークン。内側のものから順に並ぶ
+// ファイルの終わりで閉じていないブロックは閉じたものとして扱われるので、書き戻した後に続く宣言を飲み込まないように補う
+// https://www.w3.org/TR/css-syntax-3/#consume-simple-block
+fn unclosed_block_ends(tokens: &[Token]) -> Vec<Token> {
+    let mut ends = Vec::new();
+    for token in tokens {
+        match token {
+            Token::Function(_) | Token::OpenParen => ends.push(Token::CloseParen),
+            Token::OpenSquare => ends.push(Token::CloseSquare),
+            Token::OpenCurly => ends.push(Token::CloseCurly),
+            // NOTE: 対応しない閉じ括弧はブロックの中のただのトークン
+            token if ends.last() == Some(token) => {
+                ends.pop();
+            },
+            _ => {}
+        }
+    }
+    ends.reverse();
+    ends
+}
+
+fn into_list(mut values: Vec<Value>, separator: Separator) -> Value {
+    if values.len() == 1 {
+        values.remove(0)
+    } else {
+        Value::List(values, separator)
+    }
+}
+
+// NOTE: 角度の単位を度に直す。unitは小文字にしたもの
+fn angle_in_degrees(value: f32, unit: &str) -> Option<f32> {
+    match unit {
+        "deg" => Some(value),
+        "rad" => Some(value.to_degrees()),
+        "grad" => Some(value * 0.9),
+        "turn" => Some(value * 360.0),
+        _ => None
+    }
+}
+
+fn parse_unit(unit: &str) -> Option<Unit> {
+    let unit = match &*unit.to_ascii_lowercase() {
+        "px" => Unit::Px,
+        "pt" => Unit::Pt,
+        "pc" => Unit::Pc,
+        "in" => Unit::In,
+        "cm" => Unit::Cm,
+        "mm" => Unit::Mm,
+        "q" => Unit::Q,
+        "em" => Unit::Em,
+        "rem" => Unit::Rem,
+        "ex" => Unit::Ex,
+        "ch" => Unit::Ch,
+        "vw" => Unit::Vw,
+        "vh" => Unit::Vh,
+        "vmin" => Unit::Vmin,
+        "vmax" => Unit::Vmax,
+        _ => return None
+    };
+    Some(unit)
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{parse, Stylesheet, Rule, SimpleSelector, Declaration, Value, Selector, Unit};
+    use super::{parse, parse_with_diagnostics, Stylesheet, CssRule, AttributeSelector, AttributeMatcher, Combinator, PseudoClass, Specificity, KeyframesRule, Keyframe, LayerRule, LayerStatementRule, ContainerRule, MediaRule, Rule, SimpleSelector, Declaration, Value, Selector, Unit, Separator};
+    use super::media::{MediaCondition, parse_media_query_list, parse_container_condition};
+    use super::supports::parse_supports_condition;
+    use super::tokenizer::{tokenize, SourcePosition};
     use crate::css::Color;
 
+    fn style_rule(rule: &CssRule) -> &Rule {
+        match rule {
+            CssRule::Style(rule) => rule,
+            rule => panic!("Expected style rule but found {:?}", rule)
+        }
+    }
+
     #[test]
     fn parse_id_selector() {
         let target_str = "#id {margin: auto;}".to_string();
         let parsed_css = parse(target_str);
-        let selector = Selector::Simple(SimpleSelector{tag_name: None, id: Some("id".to_string()), class: vec![]});
-        let declaration = Declaration {name: "margin".to_string(), value: Value::Keyword("auto".to_string())};
-        let expected_css = Stylesheet {rules: vec![Rule {selectors: vec![selector], declarations: vec![declaration]}]};
+        let selector = Selector::Simple(SimpleSelector{tag_name: None, id: Some("id".to_string()), class: vec![], attributes: vec![], pseudo_classes: vec![]});
+        let declaration = Declaration {name: "margin".to_string(), value: Value::Keyword("auto".to_string()), important: false};
+        let expected_css = Stylesheet {rules: vec![CssRule::Style(Rule {selectors: vec![selector], declarations: vec![declaration]})]};
         assert_eq!(parsed_css, expected_css);
     }
 
@@ -293,9 +1329,9 @@ mod tests {
     fn parse_class_selector() {
         let target_str = ".class {margin: auto;}".to_string();
         let parsed_css = parse(target_str);
-        let selector = Selector::Simple(SimpleSelector{tag_name: None, id: None, class: vec!["class".to_string()]});
-        let declaration = Declaration {name: "margin".to_string(), value: Value::Keyword("auto".to_string())};
-        let expected_css = Stylesheet {rules: vec![Rule {selectors: vec![selector], declarations: vec![declaration]}]};
+        let selector = Selector::Simple(SimpleSelector{tag_name: None, id: None, class: vec!["class".to_string()], attributes: vec![], pseudo_classes: vec![]});
+        let declaration = Declaration {name: "margin".to_string(), value: Value::Keyword("auto".to_string()), important: false};
+        let expected_css = Stylesheet {rules: vec![CssRule::Style(Rule {selectors: vec![selector], declarations: vec![declaration]})]};
         assert_eq!(parsed_css, expected_css);
     }
 
@@ -303,9 +1339,9 @@ mod tests {
     fn parse_asterisk_selector() {
         let target_str = "* {margin: auto;}".to_string();
         let parsed_css = parse(target_str);
-        let selector = Selector::Simple(SimpleSelector{tag_name: None, id: None, class: vec![]});
-        let declaration = Declaration {name: "margin".to_string(), value: Value::Keyword("auto".to_string())};
-        let expected_css = Stylesheet {rules: vec![Rule {selectors: vec![selector], declarations: vec![declaration]}]};
+        let selector = Selector::Simple(SimpleSelector{tag_name: None, id: None, class: vec![], attributes: vec![], pseudo_classes: vec![]});
+        let declaration = Declaration {name: "margin".to_string(), value: Value::Keyword("auto".to_string()), important: false};
+        let expected_css = Stylesheet {rules: vec![CssRule::Style(Rule {selectors: vec![selector], declarations: vec![declaration]})]};
         assert_eq!(parsed_css, expected_css);
     }
 
@@ -313,19 +1349,121 @@ mod tests {
     fn parse_tag_name_selector() {
         let target_str = "input {margin: auto;}".to_string();
         let parsed_css = parse(target_str);
-        let selector = Selector::Simple(SimpleSelector{tag_name: Some("input".to_string()), id: None, class: vec![]});
-        let declaration = Declaration {name: "margin".to_string(), value: Value::Keyword("auto".to_string())};
-        let expected_css = Stylesheet {rules: vec![Rule {selectors: vec![selector], declarations: vec![declaration]}]};
+        let selector = Selector::Simple(SimpleSelector{tag_name: Some("input".to_string()), id: None, class: vec![], attributes: vec![], pseudo_classes: vec![]});
+        let declaration = Declaration {name: "margin".to_string(), value: Value::Keyword("auto".to_string()), important: false};
+        let expected_css = Stylesheet {rules: vec![CssRule::Style(Rule {selectors: vec![selector], declarations: vec![declaration]})]};
         assert_eq!(parsed_css, expected_css);
     }
 
+    #[test]
+    fn parse_complex_selector() {
+        let target_str = "div > .a + p ~ #b  c , a b {margin: auto;}".to_string();
+        let parsed_css = parse(target_str);
+        let compound = |tag_name: Option<&str>, id: Option<&str>, class: Vec<&str>| SimpleSelector {
+            tag_name: tag_name.map(|x| x.to_string()), id: id.map(|x| x.to_string()), class: class.iter().map(|x| x.to_string()).collect(), attributes: vec![], pseudo_classes: vec![]
+        };
+        let selectors = vec![
+            Selector::Complex(vec![
+                (compound(Some("div"), None, vec![]), Combinator::Child),
+                (compound(None, None, vec!["a"]), Combinator::NextSibling),
+                (compound(Some("p"), None, vec![]), Combinator::SubsequentSibling),
+                (compound(None, Some("b"), vec![]), Combinator::Descendant)
+            ], compound(Some("c"), None, vec![])),
+            Selector::Complex(vec![(compound(Some("a"), None, vec![]), Combinator::Descendant)], compound(Some("b"), None, vec![]))
+        ];
+        assert_eq!(style_rule(&parsed_css.rules[0]).selectors, selectors);
+        assert_eq!(selectors[0].specificity(), (1, 1, 3));
+    }
+
+    #[test]
+    fn parse_pseudo_class_selector() {
+        let parsed_css = parse("a:HOVER:is(.a, #b > c):where(#c):not(p, .d.e) {margin: auto;}".to_string());
+        let selector = &style_rule(&parsed_css.rules[0]).selectors[0];
+        match selector {
+            Selector::Simple(simple) => {
+                assert_eq!(simple.pseudo_classes[0], PseudoClass::UserAction("HOVER".to_string()));
+                assert!(matches!(simple.pseudo_classes[1], PseudoClass::Is(ref selectors) if selectors.len() == 2));
+                assert!(matches!(simple.pseudo_classes[2], PseudoClass::Where(_)));
+                assert!(matches!(simple.pseudo_classes[3], PseudoClass::Not(_)));
+            },
+            selector => panic!("Expected simple selector but found {:?}", selector)
+        }
+        // NOTE: :is()と:not()は引数の中で一番高い詳細度、:where()は0
+        assert_eq!(selector.specificity(), (1, 3, 2));
+
+        let (parsed_css, diagnostics) = parse_with_diagnostics("a:unknown {} a:is() {} a::before {} & a {} > a {}".to_string());
+        assert_eq!(parsed_css.rules.len(), 0);
+        assert_eq!(diagnostics.len(), 5);
+    }
+
+    #[test]
+    fn parse_attribute_selector() {
+        let parsed_css = parse("input[type='text' i][disabled], [lang|=en] {margin: auto;}".to_string());
+        let selectors = &style_rule(&parsed_css.rules[0]).selectors;
+        match &selectors[0] {
+            Selector::Simple(simple) => assert_eq!(simple.attributes, vec![
+                AttributeSelector {name: "type".to_string(), matcher: Some((AttributeMatcher::Equals, "text".to_string())), case_insensitive: true},
+                AttributeSelector {name: "disabled".to_string(), matcher: None, case_insensitive: false}
+            ]),
+            selector => panic!("Expected simple selector but found {:?}", selector)
+        }
+        // NOTE: 属性セレクタはクラスと同じ詳細度
+        assert_eq!(selectors[0].specificity(), (0, 2, 1));
+        assert_eq!(selectors[1].specificity(), (0, 1, 0));
+
+        let (parsed_css, diagnostics) = parse_with_diagnostics("[a=] {} [a b] {} [a=b x] {} [a%=b] {} [a {}".to_string());
+        assert_eq!(parsed_css.rules.len(), 0);
+        assert_eq!(diagnostics.len(), 5);
+    }
+
+    #[test]
+    fn parse_nested_rules() {
+        let nested = parse("
+            .card {
+                color: red;
+                & .title {margin: auto;}
+                &:hover {color: blue;}
+                > p, + div {margin: 0;}
+                .icon {
+                    margin: 1px;
+                    .b & {margin: 2px;}
+                }
+                width: 1px;
+            }
+            div, #main {
+                & + & {margin: auto;}
+                p& {margin: 0;}
+                @media print {}
+                --x: { a };
+            }
+        ".to_string());
+        // NOTE: 親のセレクタが複合セレクタ一つならそのまま展開し、そうでなければ:is()で囲む
+        // 入れ子のルールより後ろにある宣言は & をセレクタとするルールになる
+        let flat = parse("
+            .card {color: red;}
+            .card .title {margin: auto;}
+            .card:hover {color: blue;}
+            .card > p, .card + div {margin: 0;}
+            .card .icon {margin: 1px;}
+            .b :is(.card .icon) {margin: 2px;}
+            .card {width: 1px;}
+            div, #main {}
+            :is(div, #main) + :is(div, #main) {margin: auto;}
+            p:is(div, #main) {margin: 0;}
+            :is(div, #main) {--x: { a };}
+        ".to_string());
+        assert_eq!(nested, flat);
+        let specificities: Vec<Specificity> = nested.rules.iter().map(|rule| style_rule(rule).selectors[0].specificity()).collect();
+        assert_eq!(specificities, vec![(0, 1, 0), (0, 2, 0), (0, 2, 0), (0, 1, 1), (0, 2, 0), (0, 3, 0), (0, 1, 0), (0, 0, 1), (2, 0, 0), (1, 0, 1), (1, 0, 0)]);
+    }
+
     #[test]
     fn parse_keyword_declaration() {
         let target_str = "#id {display: flex;}".to_string();
         let parsed_css = parse(target_str);
-        let selector = Selector::Simple(SimpleSelector{tag_name: None, id: Some("id".to_string()), class: vec![]});
-        let declaration = Declaration {name: "display".to_string(), value: Value::Keyword("flex".to_string())};
-        let expected_css = Stylesheet {rules: vec![Rule {selectors: vec![selector], declarations: vec![declaration]}]};
+        let selector = Selector::Simple(SimpleSelector{tag_name: None, id: Some("id".to_string()), class: vec![], attributes: vec![], pseudo_classes: vec![]});
+        let declaration = Declaration {name: "display".to_string(), value: Value::Keyword("flex".to_string()), important: false};
+        let expected_css = Stylesheet {rules: vec![CssRule::Style(Rule {selectors: vec![selector], declarations: vec![declaration]})]};
         assert_eq!(parsed_css, expected_css);
     }
 
@@ -333,9 +1471,9 @@ mod tests {
     fn parse_length_declaration() {
         let target_str = "#id {font-size: 16px;}".to_string();
         let parsed_css = parse(target_str);
-        let selector = Selector::Simple(SimpleSelector{tag_name: None, id: Some("id".to_string()), class: vec![]});
-        let declaration = Declaration {name: "font-size".to_string(), value: Value::Length(16.0, Unit::Px)};
-        let expected_css = Stylesheet {rules: vec![Rule {selectors: vec![selector], declarations: vec![declaration]}]};
+        let selector = Selector::Simple(SimpleSelector{tag_name: None, id: Some("id".to_string()), class: vec![], attributes: vec![], pseudo_classes: vec![]});
+        let declaration = Declaration {name: "font-size".to_string(), value: Value::Length(16.0, Unit::Px), important: false};
+        let expected_css = Stylesheet {rules: vec![CssRule::Style(Rule {selectors: vec![selector], declarations: vec![declaration]})]};
         assert_eq!(parsed_css, expected_css);
     }
 
@@ -343,19 +1481,121 @@ mod tests {
     fn parse_color_declaration() {
         let target_str = "#id {color: #FFFF00;}".to_string();
         let parsed_css = parse(target_str);
-        let selector = Selector::Simple(SimpleSelector{tag_name: None, id: Some("id".to_string()), class: vec![]});
-        let declaration = Declaration {name: "color".to_string(), value: Value::ColorValue(Color {r: 255, g: 255, b: 0, a: 255})};
-        let expected_css = Stylesheet {rules: vec![Rule {selectors: vec![selector], declarations: vec![declaration]}]};
+        let selector = Selector::Simple(SimpleSelector{tag_name: None, id: Some("id".to_string()), class: vec![], attributes: vec![], pseudo_classes: vec![]});
+        let declaration = Declaration {name: "color".to_string(), value: Value::ColorValue(Color {r: 255, g: 255, b: 0, a: 255}), important: false};
+        let expected_css = Stylesheet {rules: vec![CssRule::Style(Rule {selectors: vec![selector], declarations: vec![declaration]})]};
         assert_eq!(parsed_css, expected_css);
     }
 
+    #[test]
+    fn parse_color_syntax_declarations() {
+        let target_str = "#id {color: #f80; background: rgb(255 136 0 / 50%); border-color: hsl(32deg, 100%, 50%); outline-color: Orange; fill: transparent; stroke: currentColor;}".to_string();
+        let parsed_css = parse(target_str);
+        let values: Vec<Value> = style_rule(&parsed_css.rules[0]).declarations.iter().map(|d| d.value.clone()).collect();
+        assert_eq!(values, vec![
+            Value::ColorValue(Color {r: 255, g: 136, b: 0, a: 255}),
+            Value::ColorValue(Color {r: 255, g: 136, b: 0, a: 128}),
+            Value::ColorValue(Color {r: 255, g: 136, b: 0, a: 255}),
+            Value::Keyword("Orange".to_string()),
+            Value::Keyword("transparent".to_string()),
+            Value::Keyword("currentColor".to_string())
+        ]);
+    }
+
+    #[test]
+    fn parse_important_declarations() {
+        let target_str = "#id {margin: 4px 8px !important; color: red ! IMPORTANT ; padding: 0/* x */!/* y */important; width: auto;}".to_string();
+        let parsed_css = parse(target_str);
+        let important_flags: Vec<bool> = style_rule(&parsed_css.rules[0]).declarations.iter().map(|d| d.important).collect();
+        assert_eq!(important_flags, vec![true, true, true, false]);
+        assert_eq!(style_rule(&parsed_css.rules[0]).declarations[0].value,
+            Value::List(vec![Value::Length(4.0, Unit::Px), Value::Length(8.0, Unit::Px)], Separator::Space));
+    }
+
+    #[test]
+    fn parse_multi_value_declarations() {
+        let target_str = "#id {margin: 4px 8px; font: bold 12px / 30px Georgia, serif; background: red}".to_string();
+        let parsed_css = parse(target_str.replace("}", ";}"));
+        let values: Vec<Value> = style_rule(&parsed_css.rules[0]).declarations.iter().map(|d| d.value.clone()).collect();
+        let keyword = |k: &str| Value::Keyword(k.to_string());
+        assert_eq!(values, vec![
+            Value::List(vec![Value::Length(4.0, Unit::Px), Value::Length(8.0, Unit::Px)], Separator::Space),
+            Value::List(vec![
+                Value::List(vec![
+                    keyword("bold"),
+                    Value::List(vec![Value::Length(12.0, Unit::Px), Value::Length(30.0, Unit::Px)], Separator::Slash),
+                    keyword("Georgia")
+                ], Separator::Space),
+                keyword("serif")
+            ], Separator::Comma),
+            keyword("red")
+        ]);
+    }
+
+    #[test]
+    fn parse_string_url_number_and_function_declarations() {
+        let target_str = "#id {font-family: \"Noto Sans JP\", sans-serif; background-image: url(a.png); list-style-image: url( 'b.png' ); content: \"\\2192\"; line-height: 1.5; transform: translate(10px, 0) scale(1.5); filter: none(); width: translate(1px,);}".to_string();
+        let (parsed_css, diagnostics) = parse_with_diagnostics(target_str);
+        let values: Vec<Value> = style_rule(&parsed_css.rules[0]).declarations.iter().map(|d| d.value.clone()).collect();
+        let function = |name: &str, args: Vec<Value>| Value::Function {name: name.to_string(), args};
+        assert_eq!(values, vec![
+            Value::List(vec![Value::String("Noto Sans JP".to_string()), Value::Keyword("sans-serif".to_string())], Separator::Comma),
+            Value::Url("a.png".to_string()),
+            Value::Url("b.png".to_string()),
+            Value::String("\u{2192}".to_string()),
+            Value::Number(1.5),
+            Value::List(vec![
+                function("translate", vec![Value::Length(10.0, Unit::Px), Value::Number(0.0)]),
+                function("scale", vec![Value::Number(1.5)])
+            ], Separator::Space),
+            function("none", vec![])
+        ]);
+        // NOTE: 引数が解釈できない関数は宣言ごと無効になる
+        assert_eq!(diagnostics.len(), 1);
+    }
+
+    #[test]
+    fn parse_keyframes_rule() {
+        let (parsed_css, diagnostics) = parse_with_diagnostics("
+            @keyframes slide {
+                from {width: 0; animation-timing-function: linear;}
+                50%, 75% {width: 10px; color: red !important;}
+                120% {width: 1px;}
+                to {width: 20px}
+            }
+            @keyframes 'none' {}
+            @keyframes none {to {width: 1px;}}
+            div {animation-duration: 1.5s, 200ms;}
+        ".to_string());
+        let width = |px: f32| Declaration {name: "width".to_string(), value: Value::Length(px, Unit::Px), important: false};
+        assert_eq!(parsed_css.rules[0], CssRule::Keyframes(KeyframesRule {
+            name: "slide".to_string(),
+            keyframes: vec![
+                Keyframe {offsets: vec![0.0], declarations: vec![
+                    Declaration {name: "width".to_string(), value: Value::Number(0.0), important: false},
+                    Declaration {name: "animation-timing-function".to_string(), value: Value::Keyword("linear".to_string()), important: false}
+                ]},
+                Keyframe {offsets: vec![50.0, 75.0], declarations: vec![width(10.0)]},
+                Keyframe {offsets: vec![100.0], declarations: vec![width(20.0)]}
+            ]
+        }));
+        assert_eq!(parsed_css.rules[1], CssRule::Keyframes(KeyframesRule {name: "none".to_string(), keyframes: vec![]}));
+        assert_eq!(style_rule(&parsed_css.rules[2]).declarations[0].value, Value::List(vec![Value::Time(1.5), Value::Time(0.2)], Separator::Comma));
+        // NOTE: !importantの宣言、範囲外のキーフレームセレクタ、予約された名前をそれぞれ報告する
+        let messages: Vec<&str> = diagnostics.iter().map(|diagnostic| diagnostic.message.as_str()).collect();
+        assert_eq!(messages.len(), 3);
+        assert!(messages[0].contains("!important"));
+        assert!(messages[1].contains("keyframe selector"));
+        assert!(messages[2].contains("@keyframes"));
+    }
+
     #[test]
     fn parse_percentage_declaration() {
         let target_str = "#id {width: 100%;}".to_string();
         let parsed_css = parse(target_str);
-        let selector = Selector::Simple(SimpleSelector{tag_name: None, id: Some("id".to_string()), class: vec![]});
-        let declaration = Declaration {name: "width".to_string(), value: Value::Percentage(100.0)};
-        let expected_css = Stylesheet {rules: vec![Rule {selectors: vec![selector], declarations: vec![declaration]}]};
+        let selector = Selector::Simple(SimpleSelector{tag_name: None, id: Some("id".to_string()), class: vec![], attributes: vec![], pseudo_classes: vec![]});
+        let declaration = Declaration {name: "width".to_string(), value: Value::Percentage(100.0), important: false};
+        let expected_css = Stylesheet {rules: vec![CssRule::Style(Rule {selectors: vec![selector], declarations: vec![declaration]})]};
         assert_eq!(parsed_css, expected_css);
     }
 
@@ -363,13 +1603,276 @@ mod tests {
     fn parse_multi_rules() {
         let target_str = "#id {margin: auto;} .class {margin: auto;}".to_string();
         let parsed_css = parse(target_str);
-        let id_selector = Selector::Simple(SimpleSelector{tag_name: None, id: Some("id".to_string()), class: vec![]});
-        let class_selector = Selector::Simple(SimpleSelector{tag_name: None, id: None, class: vec!["class".to_string()]});
-        let declaration = Declaration {name: "margin".to_string(), value: Value::Keyword("auto".to_string())};
-        let id_rule = Rule {selectors: vec![id_selector], declarations: vec![declaration.clone()]};
-        let class_rule = Rule {selectors: vec![class_selector], declarations: vec![declaration]};
+        let id_selector = Selector::Simple(SimpleSelector{tag_name: None, id: Some("id".to_string()), class: vec![], attributes: vec![], pseudo_classes: vec![]});
+        let class_selector = Selector::Simple(SimpleSelector{tag_name: None, id: None, class: vec!["class".to_string()], attributes: vec![], pseudo_classes: vec![]});
+        let declaration = Declaration {name: "margin".to_string(), value: Value::Keyword("auto".to_string()), important: false};
+        let id_rule = CssRule::Style(Rule {selectors: vec![id_selector], declarations: vec![declaration.clone()]});
+        let class_rule = CssRule::Style(Rule {selectors: vec![class_selector], declarations: vec![declaration]});
         let expected_css = Stylesheet {rules: vec![id_rule, class_rule]};
         assert_eq!(parsed_css, expected_css);
     }
 
+    #[test]
+    fn parse_relative_and_absolute_units() {
+        let target_str = "#id {margin: 1.5em; padding: 2rem; width: 50vw; height: 1in; font-size: 12pt; border-width: 10Q;}".to_string();
+        let parsed_css = parse(target_str);
+        let values: Vec<Value> = style_rule(&parsed_css.rules[0]).declarations.iter().map(|d| d.value.clone()).collect();
+        assert_eq!(values, vec![
+            Value::Length(1.5, Unit::Em),
+            Value::Length(2.0, Unit::Rem),
+            Value::Length(50.0, Unit::Vw),
+            Value::Length(1.0, Unit::In),
+            Value::Length(12.0, Unit::Pt),
+            Value::Length(10.0, Unit::Q)
+        ]);
+    }
+
+    #[test]
+    fn absolute_units_to_px() {
+        assert_eq!(Value::Length(1.0, Unit::In).to_px(), 96.0);
+        assert_eq!(Value::Length(12.0, Unit::Pt).to_px(), 16.0);
+        assert_eq!(Value::Length(2.0, Unit::Pc).to_px(), 32.0);
+        assert!((Value::Length(2.54, Unit::Cm).to_px() - 96.0).abs() < 0.001);
+        assert!((Value::Length(40.0, Unit::Q).to_px() - Value::Length(1.0, Unit::Cm).to_px()).abs() < 0.001);
+        assert_eq!(Value::Length(2.0, Unit::Em).to_px(), 0.0);
+    }
+
+    #[test]
+    fn parse_rules_with_comments() {
+        let target_str = "/* header */ #id /* id */ {/* before */margin: /* value */ auto; /* after */}".to_string();
+        let parsed_css = parse(target_str);
+        let selector = Selector::Simple(SimpleSelector{tag_name: None, id: Some("id".to_string()), class: vec![], attributes: vec![], pseudo_classes: vec![]});
+        let declaration = Declaration {name: "margin".to_string(), value: Value::Keyword("auto".to_string()), important: false};
+        let expected_css = Stylesheet {rules: vec![CssRule::Style(Rule {selectors: vec![selector], declarations: vec![declaration]})]};
+        assert_eq!(parsed_css, expected_css);
+    }
+
+    #[test]
+    fn parse_escaped_class_selector() {
+        let target_str = ".sm\\:p-4 {padding: 0;}".to_string();
+        let parsed_css = parse(target_str);
+        let selector = Selector::Simple(SimpleSelector{tag_name: None, id: None, class: vec!["sm:p-4".to_string()], attributes: vec![], pseudo_classes: vec![]});
+        let declaration = Declaration {name: "padding".to_string(), value: Value::Number(0.0), important: false};
+        let expected_css = Stylesheet {rules: vec![CssRule::Style(Rule {selectors: vec![selector], declarations: vec![declaration]})]};
+        assert_eq!(parsed_css, expected_css);
+    }
+
+    #[test]
+    fn parse_media_rule() {
+        let target_str = "@media screen and (max-width: 600px) { #id {margin: auto;} } .class {margin: auto;}".to_string();
+        let parsed_css = parse(target_str);
+        let id_selector = Selector::Simple(SimpleSelector{tag_name: None, id: Some("id".to_string()), class: vec![], attributes: vec![], pseudo_classes: vec![]});
+        let class_selector = Selector::Simple(SimpleSelector{tag_name: None, id: None, class: vec!["class".to_string()], attributes: vec![], pseudo_classes: vec![]});
+        let declaration = Declaration {name: "margin".to_string(), value: Value::Keyword("auto".to_string()), important: false};
+        let media_rule = CssRule::Media(MediaRule {
+            media: parse_media_query_list(&tokenize("screen and (max-width: 600px)")),
+            rules: vec![CssRule::Style(Rule {selectors: vec![id_selector], declarations: vec![declaration.clone()]})]
+        });
+        let class_rule = CssRule::Style(Rule {selectors: vec![class_selector], declarations: vec![declaration]});
+        let expected_css = Stylesheet {rules: vec![media_rule, class_rule]};
+        assert_eq!(parsed_css, expected_css);
+    }
+
+    #[test]
+    fn parse_supports_rule() {
+        let target_str = "@supports (display: grid) and (not (gap: 1px)) {#id {margin: auto;} @media print {}} @supports display: grid {#id {margin: auto;}} .class {margin: auto;}".to_string();
+        let (parsed_css, diagnostics) = parse_with_diagnostics(target_str);
+        assert_eq!(parsed_css.rules.len(), 2);
+        match &parsed_css.rules[0] {
+            CssRule::Supports(supports_rule) => {
+                assert_eq!(supports_rule.condition, parse_supports_condition(&tokenize("(display: grid) and (not (gap: 1px))")).unwrap());
+                assert_eq!(supports_rule.rules.len(), 2);
+            },
+            rule => panic!("Expected supports rule but found {:?}", rule)
+        }
+        assert_eq!(style_rule(&parsed_css.rules[1]).selectors.len(), 1);
+        // NOTE: 括弧で囲まれていない条件は不正なので、ブロックごと読み飛ばす
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "invalid @supports condition");
+    }
+
+    #[test]
+    fn parse_layer_rules() {
+        let target_str = "
+            @layer reset, theme.dark;
+            @import 'a.css';
+            @layer base {@layer {#id {margin: auto;}}}
+            @layer a b {#id {margin: auto;}}
+            @layer a, b {#id {margin: auto;}}
+            @layer initial;
+            @layer a. b;
+            @layer;
+        ".to_string();
+        let (parsed_css, diagnostics) = parse_with_diagnostics(target_str);
+        let name = |names: &[&str]| names.iter().map(|name| name.to_string()).collect::<Vec<String>>();
+        assert_eq!(parsed_css.rules.len(), 3);
+        assert_eq!(parsed_css.rules[0], CssRule::LayerStatement(LayerStatementRule {names: vec![name(&["reset"]), name(&["theme", "dark"])]}));
+        assert!(matches!(parsed_css.rules[1], CssRule::Import(_)));
+        match &parsed_css.rules[2] {
+            CssRule::Layer(layer_rule) => {
+                assert_eq!(layer_rule.name, name(&["base"]));
+                assert!(matches!(layer_rule.rules[0], CssRule::Layer(LayerRule {ref name, ..}) if name.is_empty()));
+            },
+            rule => panic!("Expected layer rule but found {:?}", rule)
+        }
+        assert_eq!(diagnostics.len(), 5);
+        assert!(diagnostics.iter().all(|diagnostic| diagnostic.message == "invalid @layer"));
+    }
+
+    #[test]
+    fn parse_container_rules() {
+        let target_str = "
+            @container card (min-width: 400px) {#id {margin: auto;}}
+            @container not (width < 10em) {}
+            @container none (width > 0) {}
+            @container card {}
+        ".to_string();
+        let (parsed_css, diagnostics) = parse_with_diagnostics(target_str);
+        assert_eq!(parsed_css.rules.len(), 2);
+        match &parsed_css.rules[0] {
+            CssRule::Container(container_rule) => {
+                assert_eq!(container_rule.name, Some("card".to_string()));
+                assert_eq!(container_rule.condition, parse_container_condition(&tokenize("(min-width: 400px)")).unwrap());
+                assert_eq!(style_rule(&container_rule.rules[0]).declarations[0].name, "margin");
+            },
+            rule => panic!("Expected container rule but found {:?}", rule)
+        }
+        assert!(matches!(parsed_css.rules[1], CssRule::Container(ContainerRule {name: None, condition: MediaCondition::Not(_), ..})));
+        assert_eq!(diagnostics.len(), 2);
+        assert!(diagnostics.iter().all(|diagnostic| diagnostic.message == "invalid @container"));
+    }
+
+    #[test]
+    fn skip_unknown_at_rules() {
+        let target_str = "@charset \"utf-8\"; @font-face { font-family: a; src: url(a.woff) } #id {margin: auto;}".to_string();
+        let parsed_css = parse(target_str);
+        assert_eq!(parsed_css.rules.len(), 1);
+        assert_eq!(style_rule(&parsed_css.rules[0]).declarations[0].name, "margin");
+    }
+
+    #[test]
+    fn ignore_import_after_other_rules() {
+        let target_str = "@import 'a.css'; @media print {} @import 'b.css'; #id {margin: auto;} @import 'c.css';".to_string();
+        let parsed_css = parse(target_str);
+        assert_eq!(parsed_css.rules.len(), 3);
+        match &parsed_css.rules[0] {
+            CssRule::Import(import_rule) => assert_eq!(import_rule.url, "a.css"),
+            rule => panic!("Expected import rule but found {:?}", rule)
+        }
+        assert!(matches!(parsed_css.rules[1], CssRule::Media(_)));
+    }
+
+    #[test]
+    fn parse_custom_properties_and_var_as_tokens() {
+        let target_str = "#id {--Brand: { a: b } !important; color: var(--Brand, rgb(0 0 0)); margin: 0 var(--x);}".to_string();
+        let parsed_css = parse(target_str);
+        let declarations = &style_rule(&parsed_css.rules[0]).declarations;
+        assert_eq!(declarations[0].name, "--Brand");
+        assert!(declarations[0].important);
+        assert_eq!(declarations[0].value, Value::Tokens(tokenize("{ a: b }")));
+        assert_eq!(declarations[1].value, Value::Tokens(tokenize("var(--Brand, rgb(0 0 0))")));
+        assert_eq!(declarations[2].value, Value::Tokens(tokenize("0 var(--x)")));
+    }
+
+    #[test]
+    fn parse_angles_and_background_images() {
+        let target_str = "#id {transform: rotate(45deg) rotate(0.5turn) skew(100grad, 0rad); background: url(a.png) no-repeat red;}".to_string();
+        let (parsed_css, diagnostics) = parse_with_diagnostics(target_str);
+        let values: Vec<Value> = style_rule(&parsed_css.rules[0]).declarations.iter().map(|d| d.value.clone()).collect();
+        let function = |name: &str, args: Vec<Value>| Value::Function {name: name.to_string(), args};
+        assert_eq!(values[0], Value::List(vec![
+            function("rotate", vec![Value::Angle(45.0)]),
+            function("rotate", vec![Value::Angle(180.0)]),
+            function("skew", vec![Value::Angle(90.0), Value::Angle(0.0)])
+        ], Separator::Space));
+        assert_eq!(values[1], Value::List(vec![
+            Value::Url("a.png".to_string()),
+            Value::Keyword("no-repeat".to_string()),
+            Value::Keyword("red".to_string())
+        ], Separator::Space));
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn parse_color_names_as_keywords() {
+        let target_str = "#id {animation-name: red; font-family: Gold, serif; container-name: tan; color: Navy;}".to_string();
+        let (parsed_css, diagnostics) = parse_with_diagnostics(target_str);
+        let values: Vec<Value> = style_rule(&parsed_css.rules[0]).declarations.iter().map(|d| d.value.clone()).collect();
+        let keyword = |k: &str| Value::Keyword(k.to_string());
+        assert_eq!(values, vec![
+            keyword("red"),
+            Value::List(vec![keyword("Gold"), keyword("serif")], Separator::Comma),
+            keyword("tan"),
+            keyword("Navy")
+        ]);
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn recover_from_invalid_declarations() {
+        let target_str = "#id {width: 10foo; color: red !imp; margin: auto; 12px: 1px; height: (1px; 2px); padding: 0}".to_string();
+        let (parsed_css, diagnostics) = parse_with_diagnostics(target_str);
+        let selector = Selector::Simple(SimpleSelector{tag_name: None, id: Some("id".to_string()), class: vec![], attributes: vec![], pseudo_classes: vec![]});
+        let declarations = vec![
+            Declaration {name: "margin".to_string(), value: Value::Keyword("auto".to_string()), important: false},
+            Declaration {name: "padding".to_string(), value: Value::Number(0.0), important: false}
+        ];
+        let expected_css = Stylesheet {rules: vec![CssRule::Style(Rule {selectors: vec![selector], declarations})]};
+        assert_eq!(parsed_css, expected_css);
+        assert_eq!(diagnostics.len(), 4);
+        assert_eq!(diagnostics[0].position, Some(SourcePosition {line: 1, column: 6}));
+        assert_eq!(diagnostics[3].position, Some(SourcePosition {line: 1, column: 62}));
+    }
+
+    #[test]
+    fn reject_unknown_properties_and_invalid_values() {
+        let target_str = "#id {colr: red; color: 1px; Width: AUTO; display: table-cell; --x: 1px; margin: var(--x); gaps: var(--x); font-weight: 400; --Brand: red}".to_string();
+        let (parsed_css, diagnostics) = parse_with_diagnostics(target_str);
+        let names: Vec<&str> = style_rule(&parsed_css.rules[0]).declarations.iter().map(|d| d.name.as_str()).collect();
+        assert_eq!(names, vec!["width", "--x", "margin", "font-weight", "--Brand"]);
+        let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(messages, vec!["unknown property colr", "invalid value for color", "invalid value for display", "unknown property gaps"]);
+    }
+
+    #[test]
+    fn recover_from_invalid_rules() {
+        let target_str = "a >> b {margin: auto;}\n.{margin: auto; x: {}}\n#id, {margin: auto;} } .class {margin: auto}\ninput {margin: auto;}\n@media screen { ::hover {} p {margin: auto;} }".to_string();
+        let (parsed_css, diagnostics) = parse_with_diagnostics(target_str);
+        let selectors: Vec<Vec<Selector>> = parsed_css.rules.iter().map(|rule| match rule {
+            CssRule::Style(rule) => rule.selectors.clone(),
+            CssRule::Media(media_rule) => style_rule(&media_rule.rules[0]).selectors.clone(),
+            rule => panic!("Unexpected rule {:?}", rule)
+        }).collect();
+        assert_eq!(selectors, vec![
+            vec![Selector::Simple(SimpleSelector{tag_name: Some("input".to_string()), id: None, class: vec![], attributes: vec![], pseudo_classes: vec![]})],
+            vec![Selector::Simple(SimpleSelector{tag_name: Some("p".to_string()), id: None, class: vec![], attributes: vec![], pseudo_classes: vec![]})]
+        ]);
+        let positions: Vec<Option<SourcePosition>> = diagnostics.iter().map(|diagnostic| diagnostic.position).collect();
+        assert_eq!(positions, vec![
+            Some(SourcePosition {line: 1, column: 1}),
+            Some(SourcePosition {line: 2, column: 1}),
+            Some(SourcePosition {line: 3, column: 1}),
+            Some(SourcePosition {line: 3, column: 22}),
+            Some(SourcePosition {line: 5, column: 17})
+        ]);
+    }
+
+    #[test]
+    fn report_unclosed_blocks_at_end_of_input() {
+        let (parsed_css, diagnostics) = parse_with_diagnostics("#id {margin: auto".to_string());
+        assert_eq!(style_rule(&parsed_css.rules[0]).declarations.len(), 1);
+        assert_eq!(diagnostics.len(), 1);
+
+        let (parsed_css, diagnostics) = parse_with_diagnostics("#id {margin: auto;} .class".to_string());
+        assert_eq!(parsed_css.rules.len(), 1);
+        assert_eq!(diagnostics[0].to_string(), "1:21: expected '{' after selector");
+    }
+
+    #[test]
+    fn recover_from_unterminated_url_at_end_of_input() {
+        let (parsed_css, diagnostics) = parse_with_diagnostics("a {background-image: url(a ".to_string());
+        assert_eq!(style_rule(&parsed_css.rules[0]).declarations[0].value, Value::Url("a".to_string()));
+        assert_eq!(diagnostics[0].to_string(), "1:22: unexpected end of input in declaration block");
+    }
+
 }
@@ -0,0 +1,364 @@
+// NOTE: calc(), min(), max(), clamp() の式の木
+// https://www.w3.org/TR/css-values-4/#math
+
+use super::{Unit, parse_unit, trim_whitespace};
+use super::tokenizer::Token;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum CalcNode {
+    Number(f32),
+    Length(f32, Unit),
+    Percentage(f32),
+    Sum(Box<CalcNode>, Box<CalcNode>),
+    Difference(Box<CalcNode>, Box<CalcNode>),
+    Product(Box<CalcNode>, Box<CalcNode>),
+    Quotient(Box<CalcNode>, Box<CalcNode>),
+    Min(Vec<CalcNode>),
+    Max(Vec<CalcNode>),
+    // NOTE: clamp(最小値, 推奨値, 最大値)
+    Clamp(Box<CalcNode>, Box<CalcNode>, Box<CalcNode>)
+}
+
+pub fn is_math_function(name: &str) -> bool {
+    ["calc", "min", "max", "clamp"].iter().any(|f| name.eq_ignore_ascii_case(f))
+}
+
+// NOTE: 関数名と ( から ) までの間のトークンから式の木を作る。型が合わない式はNone
+pub fn parse_math_function(name: &str, args: &[Token]) -> Option<CalcNode> {
+    let mut parser = CalcParser {tokens: trim_whitespace(args), pos: 0};
+    let node = parser.parse_function_body(&name.to_ascii_lowercase())?;
+    if !parser.eof() {
+        return None;
+    }
+    node.check_type()?;
+    Some(node)
+}
+
+impl CalcNode {
+    // NOTE: 数値の式ならtrue、長さやパーセンテージの式ならfalse
+    pub fn is_number(&self) -> bool {
+        match self {
+            CalcNode::Number(_) => true,
+            CalcNode::Length(..) | CalcNode::Percentage(_) => false,
+            CalcNode::Sum(a, _) | CalcNode::Difference(a, _) | CalcNode::Quotient(a, _) => a.is_number(),
+            CalcNode::Product(a, b) => a.is_number() && b.is_number(),
+            CalcNode::Min(nodes) | CalcNode::Max(nodes) => nodes[0].is_number(),
+            CalcNode::Clamp(_, value, _) => value.is_number()
+        }
+    }
+
+    // NOTE: 長さ同士の掛け算や、数値と長さの足し算のような型の合わない式を弾く。式の型を返す
+    fn check_type(&self) -> Option<bool> {
+        let is_number = match self {
+            CalcNode::Number(_) => true,
+            CalcNode::Length(..) | CalcNode::Percentage(_) => false,
+            CalcNode::Sum(a, b) | CalcNode::Difference(a, b) => {
+                let a = a.check_type()?;
+                if a != b.check_type()? {
+                    return None;
+                }
+                a
+            },
+            CalcNode::Product(a, b) => {
+                let (a, b) = (a.check_type()?, b.check_type()?);
+                if !a && !b {
+                    return None;
+                }
+                a && b
+            },
+            CalcNode::Quotient(a, b) => {
+                if !b.check_type()? {
+                    return None;
+                }
+                a.check_type()?
+            },
+            CalcNode::Min(nodes) | CalcNode::Max(nodes) => {
+                let types = nodes.iter().map(|node| node.check_type()).collect::<Option<Vec<bool>>>()?;
+                if types.iter().any(|t| *t != types[0]) {
+                    return None;
+                }
+                types[0]
+            },
+            CalcNode::Clamp(min, value, max) => {
+                let types = [min.check_type()?, value.check_type()?, max.check_type()?];
+                if types.iter().any(|t| *t != types[0]) {
+                    return None;
+                }
+                types[0]
+            }
+        };
+        Some(is_number)
+    }
+
+    // NOTE: 全ての長さをresolveでpxに直す
+    pub fn resolve_lengths<F: Fn(f32, Unit) -> f32>(&mut self, resolve: &F) {
+        match self {
+            CalcNode::Length(f, unit) => *self = CalcNode::Length(resolve(*f, *unit), Unit::Px),
+            CalcNode::Number(_) | CalcNode::Percentage(_) => {},
+            CalcNode::Sum(a, b) | CalcNode::Difference(a, b) | CalcNode::Product(a, b) | CalcNode::Quotient(a, b) => {
+                a.resolve_lengths(resolve);
+                b.resolve_lengths(resolve);
+            },
+            CalcNode::Min(nodes) | CalcNode::Max(nodes) => nodes.iter_mut().for_each(|node| node.resolve_lengths(resolve)),
+            CalcNode::Clamp(min, value, max) => {
+                min.resolve_lengths(resolve);
+                value.resolve_lengths(resolve);
+                max.resolve_lengths(resolve);
+            }
+        }
+    }
+
+    pub fn contains_percentage(&self) -> bool {
+        match self {
+            CalcNode::Percentage(_) => true,
+            CalcNode::Number(_) | CalcNode::Length(..) => false,
+            CalcNode::Sum(a, b) | CalcNode::Difference(a, b) | CalcNode::Product(a, b) | CalcNode::Quotient(a, b) => a.contains_percentage() || b.contains_percentage(),
+            CalcNode::Min(nodes) | CalcNode::Max(nodes) => nodes.iter().any(|node| node.contains_percentage()),
+            CalcNode::Clamp(min, value, max) => min.contains_percentage() || value.contains_percentage() || max.contains_percentage()
+        }
+    }
+
+    // NOTE: 長さがpxに直されている前提で、パーセンテージをpercentage_basisに対する割合として計算する
+    pub fn evaluate(&self, percentage_basis: f32) -> f32 {
+        match self {
+            CalcNode::Number(n) => *n,
+            CalcNode::Length(f, unit) => f * unit.absolute_px().unwrap_or(0.0),
+            CalcNode::Percentage(p) => percentage_basis * p / 100.0,
+            CalcNode::Sum(a, b) => a.evaluate(percentage_basis) + b.evaluate(percentage_basis),
+            CalcNode::Difference(a, b) => a.evaluate(percentage_basis) - b.evaluate(percentage_basis),
+            CalcNode::Product(a, b) => a.evaluate(percentage_basis) * b.evaluate(percentage_basis),
+            CalcNode::Quotient(a, b) => a.evaluate(percentage_basis) / b.evaluate(percentage_basis),
+            CalcNode::Min(nodes) => nodes.iter().map(|node| node.evaluate(percentage_basis)).fold(f32::INFINITY, f32::min),
+            CalcNode::Max(nodes) => nodes.iter().map(|node| node.evaluate(percentage_basis)).fold(f32::NEG_INFINITY, f32::max),
+            // NOTE: 最小値が最大値より大きい場合は最小値が優先される
+            CalcNode::Clamp(min, value, max) => value.evaluate(percentage_basis).min(max.evaluate(percentage_basis)).max(min.evaluate(percentage_basis))
+        }
+    }
+
+    // NOTE: 長さがpxに直されている前提で、計算できる部分を畳み込む
+    // パーセンテージを含む足し算は「px + %」の形にまとめ、min()等の中のパーセンテージはレイアウトまで残す
+    pub fn simplify(self) -> CalcNode {
+        if let Some((px, percentage)) = self.linear() {
+            return match (self.is_number(), px, percentage) {
+                (true, n, _) => CalcNode::Number(n),
+                (false, px, 0.0) => CalcNode::Length(px, Unit::Px),
+                (false, 0.0, p) => CalcNode::Percentage(p),
+                (false, px, p) => CalcNode::Sum(Box::new(CalcNode::Length(px, Unit::Px)), Box::new(CalcNode::Percentage(p)))
+            };
+        }
+        let simplify_box = |node: Box<CalcNode>| Box::new(node.simplify());
+        match self {
+            CalcNode::Sum(a, b) => CalcNode::Sum(simplify_box(a), simplify_box(b)),
+            CalcNode::Difference(a, b) => CalcNode::Difference(simplify_box(a), simplify_box(b)),
+            CalcNode::Product(a, b) => CalcNode::Product(simplify_box(a), simplify_box(b)),
+            CalcNode::Quotient(a, b) => CalcNode::Quotient(simplify_box(a), simplify_box(b)),
+            CalcNode::Min(nodes) => CalcNode::Min(nodes.into_iter().map(CalcNode::simplify).collect()),
+            CalcNode::Max(nodes) => CalcNode::Max(nodes.into_iter().map(CalcNode::simplify).collect()),
+            CalcNode::Clamp(min, value, max) => CalcNode::Clamp(simplify_box(min), simplify_box(value), simplify_box(max)),
+            node => node
+        }
+    }
+
+    // NOTE: 式を「px + %」(数値の式なら数値だけ)の形で表せる場合はその係数を返す
+    fn linear(&self) -> Option<(f32, f32)> {
+        let scale = |(px, p): (f32, f32), n: f32| (px * n, p * n);
+        match self {
+            CalcNode::Number(n) => Some((*n, 0.0)),
+            CalcNode::Length(f, unit) => Some((f * unit.absolute_px()?, 0.0)),
+            CalcNode::Percentage(p) => Some((0.0, *p)),
+            CalcNode::Sum(a, b) => {
+                let ((a_px, a_p), (b_px, b_p)) = (a.linear()?, b.linear()?);
+                Some((a_px + b_px, a_p + b_p))
+            },
+            CalcNode::Difference(a, b) => {
+                let ((a_px, a_p), (b_px, b_p)) = (a.linear()?, b.linear()?);
+                Some((a_px - b_px, a_p - b_p))
+            },
+            CalcNode::Product(a, b) if a.is_number() => Some(scale(b.linear()?, a.linear()?.0)),
+            CalcNode::Product(a, b) => Some(scale(a.linear()?, b.linear()?.0)),
+            CalcNode::Quotient(a, b) => Some(scale(a.linear()?, 1.0 / b.linear()?.0)),
+            CalcNode::Min(_) | CalcNode::Max(_) | CalcNode::Clamp(..) if !self.contains_percentage() => Some((self.evaluate(0.0), 0.0)),
+            _ => None
+        }
+    }
+}
+
+struct CalcParser<'a> {
+    tokens: &'a [Token],
+    pos: usize
+}
+
+impl<'a> CalcParser<'a> {
+
+    fn parse_function_body(&mut self, name: &str) -> Option<CalcNode> {
+        match name {
+            "calc" => self.parse_sum(),
+            "min" | "max" => {
+                let nodes = self.parse_comma_separated()?;
+                Some(if name == "min" {CalcNode::Min(nodes)} else {CalcNode::Max(nodes)})
+            },
+            "clamp" => {
+                let mut nodes = self.parse_comma_separated()?;
+                if nodes.len() != 3 {
+                    return None;
+                }
+                let max = nodes.pop()?;
+                let value = nodes.pop()?;
+                let min = nodes.pop()?;
+                Some(CalcNode::Clamp(Box::new(min), Box::new(value), Box::new(max)))
+            },
+            _ => None
+        }
+    }
+
+    fn parse_comma_separated(&mut self) -> Option<Vec<CalcNode>> {
+        let mut nodes = vec![self.parse_sum()?];
+        while self.next_token() == Some(&Token::Comma) {
+            self.pos += 1;
+            self.consume_whitespace();
+            nodes.push(self.parse_sum()?);
+        }
+        Some(nodes)
+    }
+
+    // NOTE: + と - の前後には空白が必要
+    fn parse_sum(&mut self) -> Option<CalcNode> {
+        let mut node = self.parse_product()?;
+        loop {
+            let operator = match (self.next_token(), self.tokens.get(self.pos + 1), self.tokens.get(self.pos + 2)) {
+                (Some(Token::Whitespace), Some(Token::Delim(c)), Some(Token::Whitespace)) if *c == '+' || *c == '-' => *c,
+                _ => break
+            };
+            self.pos += 3;
+            self.consume_whitespace();
+            let rhs = Box::new(self.parse_product()?);
+            node = if operator == '+' {CalcNode::Sum(Box::new(node), rhs)} else {CalcNode::Difference(Box::new(node), rhs)};
+        }
+        self.consume_whitespace();
+        Some(node)
+    }
+
+    fn parse_product(&mut self) -> Option<CalcNode> {
+        let mut node = self.parse_value()?;
+        loop {
+            let start = self.pos;
+            self.consume_whitespace();
+            let operator = match self.next_token() {
+                Some(Token::Delim(c)) if *c == '*' || *c == '/' => *c,
+                _ => {
+                    self.pos = start;
+                    break;
+                }
+            };
+            self.pos += 1;
+            self.consume_whitespace();
+            let rhs = Box::new(self.parse_value()?);
+            node = if operator == '*' {CalcNode::Product(Box::new(node), rhs)} else {CalcNode::Quotient(Box::new(node), rhs)};
+        }
+        Some(node)
+    }
+
+    fn parse_value(&mut self) -> Option<CalcNode> {
+        let token = self.tokens.get(self.pos)?;
+        self.pos += 1;
+        match token {
+            Token::Number(n) => Some(CalcNode::Number(*n)),
+            Token::Percentage(p) => Some(CalcNode::Percentage(*p)),
+            Token::Dimension(f, unit) => Some(CalcNode::Length(*f, parse_unit(unit)?)),
+            Token::OpenParen => {
+                self.consume_whitespace();
+                let node = self.parse_sum()?;
+                if self.tokens.get(self.pos) != Some(&Token::CloseParen) {
+                    return None;
+                }
+                self.pos += 1;
+                Some(node)
+            },
+            Token::Function(name) if is_math_function(name) => {
+                self.consume_whitespace();
+                let node = self.parse_function_body(&name.to_ascii_lowercase())?;
+                if self.tokens.get(self.pos) != Some(&Token::CloseParen) {
+                    return None;
+                }
+                self.pos += 1;
+                Some(node)
+            },
+            _ => None
+        }
+    }
+
+    fn next_token(&self) -> Option<&'a Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn consume_whitespace(&mut self) {
+        while self.next_token() == Some(&Token::Whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    fn eof(&self) -> bool {
+        self.pos >= self.tokens.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_math_function, CalcNode};
+    use crate::css::Unit;
+    use crate::css::tokenizer::tokenize;
+
+    fn parse(name: &str, args: &str) -> Option<CalcNode> {
+        parse_math_function(name, &tokenize(args))
+    }
+
+    fn px(f: f32) -> Box<CalcNode> {
+        Box::new(CalcNode::Length(f, Unit::Px))
+    }
+
+    #[test]
+    fn parse_operator_precedence() {
+        assert_eq!(parse("calc", "100% - 2 * 16px"), Some(CalcNode::Difference(
+            Box::new(CalcNode::Percentage(100.0)),
+            Box::new(CalcNode::Product(Box::new(CalcNode::Number(2.0)), px(16.0)))
+        )));
+        assert_eq!(parse("calc", " (1px + 2px) / 2 "), Some(CalcNode::Quotient(
+            Box::new(CalcNode::Sum(px(1.0), px(2.0))),
+            Box::new(CalcNode::Number(2.0))
+        )));
+        assert_eq!(parse("clamp", "14px, 2vw, calc(1em + 4px)"), Some(CalcNode::Clamp(
+            px(14.0),
+            Box::new(CalcNode::Length(2.0, Unit::Vw)),
+            Box::new(CalcNode::Sum(Box::new(CalcNode::Length(1.0, Unit::Em)), px(4.0)))
+        )));
+    }
+
+    #[test]
+    fn reject_invalid_expressions() {
+        assert_eq!(parse("calc", "1px * 2px"), None);
+        assert_eq!(parse("calc", "1px + 2"), None);
+        assert_eq!(parse("calc", "2 / 1px"), None);
+        assert_eq!(parse("calc", "1px -2px"), None);
+        assert_eq!(parse("calc", "1px+2px"), None);
+        assert_eq!(parse("min", "1px, 2"), None);
+        assert_eq!(parse("clamp", "1px, 2px"), None);
+        assert_eq!(parse("calc", ""), None);
+    }
+
+    #[test]
+    fn simplify_and_evaluate() {
+        let node = parse("calc", "100% - 2 * 16px + 1in").unwrap().simplify();
+        assert_eq!(node, CalcNode::Sum(px(64.0), Box::new(CalcNode::Percentage(100.0))));
+        assert_eq!(node.evaluate(200.0), 264.0);
+
+        assert_eq!(parse("max", "10px, 2 * 8px").unwrap().simplify(), CalcNode::Length(16.0, Unit::Px));
+        assert_eq!(parse("calc", "3 * (2 + 1)").unwrap().simplify(), CalcNode::Number(9.0));
+
+        let node = parse("min", "50%, 300px").unwrap().simplify();
+        assert!(node.contains_percentage());
+        assert_eq!(node.evaluate(400.0), 200.0);
+        assert_eq!(node.evaluate(800.0), 300.0);
+
+        assert_eq!(parse("clamp", "20px, 10px, 15px").unwrap().evaluate(0.0), 20.0);
+    }
+}
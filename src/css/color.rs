@@ -0,0 +1,378 @@
+// NOTE: CSS Color Module Level 4 の色の構文
+// https://www.w3.org/TR/css-color-4/
+
+use super::Color;
+use super::tokenizer::Token;
+
+// NOTE: #rgb, #rgba, #rrggbb, #rrggbbaa を受け付ける
+pub fn parse_hex_color(hex: &str) -> Option<Color> {
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let digit = |i: usize| u8::from_str_radix(&hex[i..i + 1], 16).unwrap();
+    let pair = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).unwrap();
+    match hex.len() {
+        3 | 4 => Some(Color {
+            r: digit(0) * 17,
+            g: digit(1) * 17,
+            b: digit(2) * 17,
+            a: if hex.len() == 4 {digit(3) * 17} else {255}
+        }),
+        6 | 8 => Some(Color {
+            r: pair(0),
+            g: pair(2),
+            b: pair(4),
+            a: if hex.len() == 8 {pair(6)} else {255}
+        }),
+        _ => None
+    }
+}
+
+// NOTE: red, rebeccapurple のような名前付きの色とtransparent
+pub fn named_color(name: &str) -> Option<Color> {
+    let name = name.to_ascii_lowercase();
+    if name == "transparent" {
+        return Some(Color {r: 0, g: 0, b: 0, a: 0});
+    }
+    NAMED_COLORS.iter().find(|(color_name, _)| *color_name == name).map(|&(_, rgb)| Color {
+        r: (rgb >> 16) as u8,
+        g: (rgb >> 8) as u8,
+        b: rgb as u8,
+        a: 255
+    })
+}
+
+pub fn is_color_function(name: &str) -> bool {
+    ["rgb", "rgba", "hsl", "hsla", "hwb"].iter().any(|function| name.eq_ignore_ascii_case(function))
+}
+
+// NOTE: rgb(), rgba(), hsl(), hsla(), hwb() の引数のトークンから色を作る
+// カンマ区切りの旧構文と、スペース区切りで / の後にアルファを書く新構文の両方を受け付ける
+pub fn parse_color_function(name: &str, args: &[Token]) -> Option<Color> {
+    let name = name.to_ascii_lowercase();
+    let (components, alpha) = split_components(args, name != "hwb")?;
+    if components.len() != 3 {
+        return None;
+    }
+    let alpha = match alpha {
+        Some(token) => parse_alpha(token)?,
+        None => 1.0
+    };
+    let (r, g, b) = match &*name {
+        "rgb" | "rgba" => {
+            let channel = |token: &Token| match *token {
+                Token::Number(n) => Some(n / 255.0),
+                Token::Percentage(p) => Some(p / 100.0),
+                _ => None
+            };
+            (channel(components[0])?, channel(components[1])?, channel(components[2])?)
+        },
+        "hsl" | "hsla" => {
+            let hue = parse_hue(components[0])?;
+            let saturation = parse_percentage(components[1])?;
+            let lightness = parse_percentage(components[2])?;
+            hsl_to_rgb(hue, saturation, lightness)
+        },
+        "hwb" => {
+            let hue = parse_hue(components[0])?;
+            let whiteness = parse_percentage(components[1])?;
+            let blackness = parse_percentage(components[2])?;
+            hwb_to_rgb(hue, whiteness, blackness)
+        },
+        _ => return None
+    };
+    Some(Color {
+        r: to_channel(r),
+        g: to_channel(g),
+        b: to_channel(b),
+        a: to_channel(alpha)
+    })
+}
+
+// NOTE: 引数を色の3成分とアルファに分ける。legacy_allowedがtrueの時だけカンマ区切りを許す
+fn split_components(args: &[Token], legacy_allowed: bool) -> Option<(Vec<&Token>, Option<&Token>)> {
+    let tokens: Vec<&Token> = args.iter().filter(|t| **t != Token::Whitespace).collect();
+    if tokens.contains(&&Token::Comma) {
+        if !legacy_allowed || tokens.len().is_multiple_of(2) {
+            return None;
+        }
+        let mut components = Vec::new();
+        for (i, token) in tokens.iter().enumerate() {
+            let is_comma = **token == Token::Comma;
+            if is_comma != (i % 2 == 1) {
+                return None;
+            }
+            if !is_comma {
+                components.push(*token);
+            }
+        }
+        let alpha = if components.len() == 4 {components.pop()} else {None};
+        Some((components, alpha))
+    } else {
+        match tokens.iter().position(|t| **t == Token::Delim('/')) {
+            Some(slash) if slash + 2 == tokens.len() => Some((tokens[..slash].to_vec(), Some(tokens[slash + 1]))),
+            Some(_) => None,
+            None => Some((tokens, None))
+        }
+    }
+}
+
+fn parse_alpha(token: &Token) -> Option<f32> {
+    match *token {
+        Token::Number(n) => Some(n),
+        Token::Percentage(p) => Some(p / 100.0),
+        _ => None
+    }
+}
+
+fn parse_percentage(token: &Token) -> Option<f32> {
+    match *token {
+        Token::Percentage(p) => Some((p / 100.0).clamp(0.0, 1.0)),
+        // NOTE: 新構文では%を省略した数値も使える
+        Token::Number(n) => Some((n / 100.0).clamp(0.0, 1.0)),
+        _ => None
+    }
+}
+
+// NOTE: 色相を度数(0..360)で返す
+fn parse_hue(token: &Token) -> Option<f32> {
+    let degrees = match *token {
+        Token::Number(n) => n,
+        Token::Dimension(n, ref unit) => super::angle_in_degrees(n, &unit.to_ascii_lowercase())?,
+        _ => return None
+    };
+    Some(degrees.rem_euclid(360.0))
+}
+
+// NOTE: https://www.w3.org/TR/css-color-4/#hsl-to-rgb
+fn hsl_to_rgb(hue: f32, saturation: f32, lightness: f32) -> (f32, f32, f32) {
+    let f = |n: f32| {
+        let k = (n + hue / 30.0) % 12.0;
+        let a = saturation * lightness.min(1.0 - lightness);
+        lightness - a * (k - 3.0).min(9.0 - k).clamp(-1.0, 1.0)
+    };
+    (f(0.0), f(8.0), f(4.0))
+}
+
+// NOTE: https://www.w3.org/TR/css-color-4/#hwb-to-rgb
+fn hwb_to_rgb(hue: f32, whiteness: f32, blackness: f32) -> (f32, f32, f32) {
+    if whiteness + blackness >= 1.0 {
+        let gray = whiteness / (whiteness + blackness);
+        return (gray, gray, gray);
+    }
+    let (r, g, b) = hsl_to_rgb(hue, 1.0, 0.5);
+    let scale = |c: f32| c * (1.0 - whiteness - blackness) + whiteness;
+    (scale(r), scale(g), scale(b))
+}
+
+fn to_channel(value: f32) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+const NAMED_COLORS: [(&str, u32); 148] = [
+    ("aliceblue", 0xf0f8ff),
+    ("antiquewhite", 0xfaebd7),
+    ("aqua", 0x00ffff),
+    ("aquamarine", 0x7fffd4),
+    ("azure", 0xf0ffff),
+    ("beige", 0xf5f5dc),
+    ("bisque", 0xffe4c4),
+    ("black", 0x000000),
+    ("blanchedalmond", 0xffebcd),
+    ("blue", 0x0000ff),
+    ("blueviolet", 0x8a2be2),
+    ("brown", 0xa52a2a),
+    ("burlywood", 0xdeb887),
+    ("cadetblue", 0x5f9ea0),
+    ("chartreuse", 0x7fff00),
+    ("chocolate", 0xd2691e),
+    ("coral", 0xff7f50),
+    ("cornflowerblue", 0x6495ed),
+    ("cornsilk", 0xfff8dc),
+    ("crimson", 0xdc143c),
+    ("cyan", 0x00ffff),
+    ("darkblue", 0x00008b),
+    ("darkcyan", 0x008b8b),
+    ("darkgoldenrod", 0xb8860b),
+    ("darkgray", 0xa9a9a9),
+    ("darkgreen", 0x006400),
+    ("darkgrey", 0xa9a9a9),
+    ("darkkhaki", 0xbdb76b),
+    ("darkmagenta", 0x8b008b),
+    ("darkolivegreen", 0x556b2f),
+    ("darkorange", 0xff8c00),
+    ("darkorchid", 0x9932cc),
+    ("darkred", 0x8b0000),
+    ("darksalmon", 0xe9967a),
+    ("darkseagreen", 0x8fbc8f),
+    ("darkslateblue", 0x483d8b),
+    ("darkslategray", 0x2f4f4f),
+    ("darkslategrey", 0x2f4f4f),
+    ("darkturquoise", 0x00ced1),
+    ("darkviolet", 0x9400d3),
+    ("deeppink", 0xff1493),
+    ("deepskyblue", 0x00bfff),
+    ("dimgray", 0x696969),
+    ("dimgrey", 0x696969),
+    ("dodgerblue", 0x1e90ff),
+    ("firebrick", 0xb22222),
+    ("floralwhite", 0xfffaf0),
+    ("forestgreen", 0x228b22),
+    ("fuchsia", 0xff00ff),
+    ("gainsboro", 0xdcdcdc),
+    ("ghostwhite", 0xf8f8ff),
+    ("gold", 0xffd700),
+    ("goldenrod", 0xdaa520),
+    ("gray", 0x808080),
+    ("green", 0x008000),
+    ("greenyellow", 0xadff2f),
+    ("grey", 0x808080),
+    ("honeydew", 0xf0fff0),
+    ("hotpink", 0xff69b4),
+    ("indianred", 0xcd5c5c),
+    ("indigo", 0x4b0082),
+    ("ivory", 0xfffff0),
+    ("khaki", 0xf0e68c),
+    ("lavender", 0xe6e6fa),
+    ("lavenderblush", 0xfff0f5),
+    ("lawngreen", 0x7cfc00),
+    ("lemonchiffon", 0xfffacd),
+    ("lightblue", 0xadd8e6),
+    ("lightcoral", 0xf08080),
+    ("lightcyan", 0xe0ffff),
+    ("lightgoldenrodyellow", 0xfafad2),
+    ("lightgray", 0xd3d3d3),
+    ("lightgreen", 0x90ee90),
+    ("lightgrey", 0xd3d3d3),
+    ("lightpink", 0xffb6c1),
+    ("lightsalmon", 0xffa07a),
+    ("lightseagreen", 0x20b2aa),
+    ("lightskyblue", 0x87cefa),
+    ("lightslategray", 0x778899),
+    ("lightslategrey", 0x778899),
+    ("lightsteelblue", 0xb0c4de),
+    ("lightyellow", 0xffffe0),
+    ("lime", 0x00ff00),
+    ("limegreen", 0x32cd32),
+    ("linen", 0xfaf0e6),
+    ("magenta", 0xff00ff),
+    ("maroon", 0x800000),
+    ("mediumaquamarine", 0x66cdaa),
+    ("mediumblue", 0x0000cd),
+    ("mediumorchid", 0xba55d3),
+    ("mediumpurple", 0x9370db),
+    ("mediumseagreen", 0x3cb371),
+    ("mediumslateblue", 0x7b68ee),
+    ("mediumspringgreen", 0x00fa9a),
+    ("mediumturquoise", 0x48d1cc),
+    ("mediumvioletred", 0xc71585),
+    ("midnightblue", 0x191970),
+    ("mintcream", 0xf5fffa),
+    ("mistyrose", 0xffe4e1),
+    ("moccasin", 0xffe4b5),
+    ("navajowhite", 0xffdead),
+    ("navy", 0x000080),
+    ("oldlace", 0xfdf5e6),
+    ("olive", 0x808000),
+    ("olivedrab", 0x6b8e23),
+    ("orange", 0xffa500),
+    ("orangered", 0xff4500),
+    ("orchid", 0xda70d6),
+    ("palegoldenrod", 0xeee8aa),
+    ("palegreen", 0x98fb98),
+    ("paleturquoise", 0xafeeee),
+    ("palevioletred", 0xdb7093),
+    ("papayawhip", 0xffefd5),
+    ("peachpuff", 0xffdab9),
+    ("peru", 0xcd853f),
+    ("pink", 0xffc0cb),
+    ("plum", 0xdda0dd),
+    ("powderblue", 0xb0e0e6),
+    ("purple", 0x800080),
+    ("rebeccapurple", 0x663399),
+    ("red", 0xff0000),
+    ("rosybrown", 0xbc8f8f),
+    ("royalblue", 0x4169e1),
+    ("saddlebrown", 0x8b4513),
+    ("salmon", 0xfa8072),
+    ("sandybrown", 0xf4a460),
+    ("seagreen", 0x2e8b57),
+    ("seashell", 0xfff5ee),
+    ("sienna", 0xa0522d),
+    ("silver", 0xc0c0c0),
+    ("skyblue", 0x87ceeb),
+    ("slateblue", 0x6a5acd),
+    ("slategray", 0x708090),
+    ("slategrey", 0x708090),
+    ("snow", 0xfffafa),
+    ("springgreen", 0x00ff7f),
+    ("steelblue", 0x4682b4),
+    ("tan", 0xd2b48c),
+    ("teal", 0x008080),
+    ("thistle", 0xd8bfd8),
+    ("tomato", 0xff6347),
+    ("turquoise", 0x40e0d0),
+    ("violet", 0xee82ee),
+    ("wheat", 0xf5deb3),
+    ("white", 0xffffff),
+    ("whitesmoke", 0xf5f5f5),
+    ("yellow", 0xffff00),
+    ("yellowgreen", 0x9acd32),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_hex_color, named_color, parse_color_function};
+    use crate::css::Color;
+    use crate::css::tokenizer::{tokenize, Token};
+
+    fn parse_function(source: &str) -> Option<Color> {
+        let mut tokens = tokenize(source);
+        let name = match tokens.remove(0) {
+            Token::Function(name) => name,
+            t => panic!("expected function but found {:?}", t)
+        };
+        assert_eq!(tokens.pop(), Some(Token::CloseParen));
+        parse_color_function(&name, &tokens)
+    }
+
+    #[test]
+    fn parse_hex_colors() {
+        assert_eq!(parse_hex_color("f80"), Some(Color {r: 255, g: 136, b: 0, a: 255}));
+        assert_eq!(parse_hex_color("f808"), Some(Color {r: 255, g: 136, b: 0, a: 136}));
+        assert_eq!(parse_hex_color("FF8800"), Some(Color {r: 255, g: 136, b: 0, a: 255}));
+        assert_eq!(parse_hex_color("ff880080"), Some(Color {r: 255, g: 136, b: 0, a: 128}));
+        assert_eq!(parse_hex_color("ff8800f"), None);
+        assert_eq!(parse_hex_color("gg0000"), None);
+    }
+
+    #[test]
+    fn parse_named_colors() {
+        assert_eq!(named_color("RebeccaPurple"), Some(Color {r: 102, g: 51, b: 153, a: 255}));
+        assert_eq!(named_color("grey"), named_color("gray"));
+        assert_eq!(named_color("transparent"), Some(Color {r: 0, g: 0, b: 0, a: 0}));
+        assert_eq!(named_color("auto"), None);
+    }
+
+    #[test]
+    fn parse_rgb_functions() {
+        let expected = Some(Color {r: 255, g: 0, b: 51, a: 255});
+        assert_eq!(parse_function("rgb(255, 0, 51)"), expected);
+        assert_eq!(parse_function("rgb(255 0 51)"), expected);
+        assert_eq!(parse_function("rgb(100% 0% 20%)"), expected);
+        assert_eq!(parse_function("rgba(255, 0, 51, 0.5)"), Some(Color {r: 255, g: 0, b: 51, a: 128}));
+        assert_eq!(parse_function("rgb(255 0 51 / 50%)"), Some(Color {r: 255, g: 0, b: 51, a: 128}));
+        assert_eq!(parse_function("rgb(255, 0 51)"), None);
+        assert_eq!(parse_function("rgb(255 0)"), None);
+    }
+
+    #[test]
+    fn parse_hsl_and_hwb_functions() {
+        assert_eq!(parse_function("hsl(0, 100%, 50%)"), Some(Color {r: 255, g: 0, b: 0, a: 255}));
+        assert_eq!(parse_function("hsl(120deg 100% 25%)"), Some(Color {r: 0, g: 128, b: 0, a: 255}));
+        assert_eq!(parse_function("hsla(0.5turn, 100%, 50%, 0)"), Some(Color {r: 0, g: 255, b: 255, a: 0}));
+        assert_eq!(parse_function("hwb(240 0% 0%)"), Some(Color {r: 0, g: 0, b: 255, a: 255}));
+        assert_eq!(parse_function("hwb(0 60% 60% / 1)"), Some(Color {r: 128, g: 128, b: 128, a: 255}));
+        assert_eq!(parse_function("hwb(0, 0%, 0%)"), None);
+    }
+}
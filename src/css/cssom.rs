@@ -0,0 +1,163 @@
+// NOTE: CSSOMのようにスタイルシートをコードから編集するためのAPI
+// https://www.w3.org/TR/cssom-1/#the-cssstylesheet-interface
+// NOTE: 今はツールから使うためのAPIで、バイナリの中からはまだ呼ばれていない
+
+use super::{Stylesheet, CssRule, Rule, Declaration, parse_rule, parse_declaration_list};
+use super::serialize::serialize_value;
+
+#[allow(dead_code)]
+impl Stylesheet {
+    // NOTE: ruleを読んでindexの位置に挿入し、挿入した位置を返す
+    pub fn insert_rule(&mut self, rule: &str, index: usize) -> Result<usize, String> {
+        if index > self.rules.len() {
+            return Err(format!("index {} is out of range (rules: {})", index, self.rules.len()));
+        }
+        let rule = parse_rule(rule)?;
+        // NOTE: @importは@layerの文を除く他のルールより前にしか置けない
+        let is_import = |rule: &CssRule| matches!(rule, CssRule::Import(_));
+        let is_layer_statement = |rule: &CssRule| matches!(rule, CssRule::LayerStatement(_));
+        if is_import(&rule) && !self.rules[..index].iter().all(|rule| is_import(rule) || is_layer_statement(rule)) {
+            return Err("@import must come before all other rules".to_string());
+        }
+        if !is_import(&rule) && !is_layer_statement(&rule) && self.rules[index..].iter().any(is_import) {
+            return Err("cannot insert a rule before @import".to_string());
+        }
+        self.rules.insert(index, rule);
+        Ok(index)
+    }
+
+    pub fn delete_rule(&mut self, index: usize) -> Result<(), String> {
+        if index >= self.rules.len() {
+            return Err(format!("index {} is out of range (rules: {})", index, self.rules.len()));
+        }
+        self.rules.remove(index);
+        Ok(())
+    }
+}
+
+#[allow(dead_code)]
+impl Rule {
+    // NOTE: 宣言されていなければ空文字列を返す
+    // ショートハンドはstyleの段階で展開するので、margin-topを聞いてもmarginの宣言からは値を返さない
+    pub fn get_property_value(&self, name: &str) -> String {
+        self.declarations.iter().rev()
+            .find(|declaration| is_same_property(&declaration.name, name))
+            .map_or_else(String::new, |declaration| serialize_value(&declaration.value))
+    }
+
+    // NOTE: priorityは "important" か空文字列。valueが空文字列なら宣言を取り除く
+    // 既に宣言されていればその位置で置き換え、なければ末尾に足す
+    pub fn set_property(&mut self, name: &str, value: &str, priority: &str) -> Result<(), String> {
+        if value.trim().is_empty() {
+            self.remove_property(name);
+            return Ok(());
+        }
+        let important = match priority {
+            "" => false,
+            priority if priority.eq_ignore_ascii_case("important") => true,
+            priority => return Err(format!("invalid priority: {}", priority))
+        };
+        let (mut declarations, diagnostics) = parse_declaration_list(&format!("{}: {}", name, value));
+        if let Some(diagnostic) = diagnostics.into_iter().next() {
+            return Err(diagnostic.message);
+        }
+        // NOTE: 値の中に ; を書いて別の宣言を足したり、!importantを書いたりはできない
+        let declaration = match declarations.pop() {
+            Some(declaration) if declarations.is_empty() && !declaration.important && is_same_property(&declaration.name, name) => declaration,
+            _ => return Err(format!("invalid value for {}", name))
+        };
+        let declaration = Declaration {important, ..declaration};
+
+        match self.declarations.iter().rposition(|d| is_same_property(&d.name, name)) {
+            Some(position) => {
+                self.declarations[position] = declaration;
+                let mut index = 0;
+                self.declarations.retain(|d| {
+                    index += 1;
+                    index - 1 == position || !is_same_property(&d.name, name)
+                });
+            },
+            None => self.declarations.push(declaration)
+        }
+        Ok(())
+    }
+
+    // NOTE: 取り除いた宣言の値を返す。宣言されていなければ空文字列
+    pub fn remove_property(&mut self, name: &str) -> String {
+        let value = self.get_property_value(name);
+        self.declarations.retain(|declaration| !is_same_property(&declaration.name, name));
+        value
+    }
+}
+
+// NOTE: カスタムプロパティの名前だけは大文字と小文字を区別する
+fn is_same_property(a: &str, b: &str) -> bool {
+    if a.starts_with("--") {
+        a == b
+    } else {
+        a.eq_ignore_ascii_case(b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::css::{parse, serialize, CssRule, Rule};
+
+    fn first_rule(source: &str) -> Rule {
+        match parse(source.to_string()).rules.remove(0) {
+            CssRule::Style(rule) => rule,
+            rule => panic!("Expected style rule but found {:?}", rule)
+        }
+    }
+
+    #[test]
+    fn insert_and_delete_rules() {
+        let mut stylesheet = parse("@import 'a.css'; div {width: 1px;}".to_string());
+        assert_eq!(stylesheet.insert_rule("p {width: 2px;}", 2), Ok(2));
+        assert_eq!(stylesheet.insert_rule("@media print {p {width: 3px;}}", 1), Ok(1));
+        assert_eq!(stylesheet.insert_rule("@import 'b.css';", 0), Ok(0));
+        assert_eq!(serialize(&stylesheet), serialize(&parse("
+            @import 'b.css'; @import 'a.css'; @media print {p {width: 3px;}} div {width: 1px;} p {width: 2px;}
+        ".to_string())));
+
+        assert!(stylesheet.insert_rule("p {}", 6).is_err());
+        assert!(stylesheet.insert_rule("p {width: }", 0).is_err());
+        assert!(stylesheet.insert_rule("p {} div {}", 0).is_err());
+        assert!(stylesheet.insert_rule("", 0).is_err());
+        assert!(stylesheet.insert_rule("@import 'c.css';", 3).is_err());
+        assert!(stylesheet.insert_rule("p {}", 1).is_err());
+        assert_eq!(stylesheet.rules.len(), 5);
+
+        assert_eq!(stylesheet.delete_rule(2), Ok(()));
+        assert!(stylesheet.delete_rule(4).is_err());
+        assert_eq!(stylesheet.rules.len(), 4);
+    }
+
+    #[test]
+    fn get_set_and_remove_properties() {
+        let mut rule = first_rule("div {width: 1px; --Main-Color: #fff; margin: 0 auto; width: 2px;}");
+        assert_eq!(rule.get_property_value("WIDTH"), "2px");
+        assert_eq!(rule.get_property_value("--Main-Color"), "#fff");
+        assert_eq!(rule.get_property_value("--main-color"), "");
+        assert_eq!(rule.get_property_value("margin-top"), "");
+
+        // NOTE: 既にある宣言はその位置で置き換わり、重複していた宣言は一つになる
+        assert_eq!(rule.set_property("width", "calc(100% - 8px)", "important"), Ok(()));
+        assert_eq!(rule.set_property("color", "red", ""), Ok(()));
+        assert_eq!(rule, first_rule("div {--Main-Color: #fff; margin: 0 auto; width: calc(100% - 8px) !important; color: red;}"));
+
+        assert!(rule.set_property("color", "red; width: 1px", "").is_err());
+        assert!(rule.set_property("color", "red !important", "").is_err());
+        // NOTE: colorが受け付けない値や、知らないプロパティは設定できない
+        assert!(rule.set_property("color", "1px 1px", "").is_err());
+        assert!(rule.set_property("colour", "red", "").is_err());
+        assert!(rule.set_property("color", "}", "").is_err());
+        assert!(rule.set_property("color", "blue", "high").is_err());
+        assert_eq!(rule.get_property_value("color"), "red");
+
+        assert_eq!(rule.remove_property("margin"), "0 auto");
+        assert_eq!(rule.remove_property("margin"), "");
+        assert_eq!(rule.set_property("color", "", ""), Ok(()));
+        assert_eq!(rule, first_rule("div {--Main-Color: #fff; width: calc(100% - 8px) !important;}"));
+    }
+}
@@ -0,0 +1,169 @@
+// NOTE: @import の解釈と、読み込んだファイルの展開
+// https://www.w3.org/TR/css-cascade-5/#at-import
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use super::{parse_with_diagnostics, Stylesheet, CssRule, ImportRule, Diagnostic};
+use super::media::parse_media_query_list;
+use super::tokenizer::Token;
+
+// NOTE: @import url("a.css") layer(base) screen; のurl以降の部分を読む
+pub fn parse_import_prelude(prelude: &[Token]) -> Option<ImportRule> {
+    let mut pos = 0;
+    let url = match next_token(prelude, &mut pos)? {
+        Token::Url(url) | Token::String(url) => url.clone(),
+        Token::Function(name) if name.eq_ignore_ascii_case("url") => match (next_token(prelude, &mut pos)?, next_token(prelude, &mut pos)?) {
+            (Token::String(url), Token::CloseParen) => url.clone(),
+            _ => return None
+        },
+        _ => return None
+    };
+
+    // NOTE: layer単体は無名のレイヤー、layer(a.b)は名前付きのレイヤー
+    let media_start = pos;
+    let layer = match next_token(prelude, &mut pos) {
+        Some(Token::Ident(name)) if name.eq_ignore_ascii_case("layer") => Some(vec![]),
+        Some(Token::Function(name)) if name.eq_ignore_ascii_case("layer") => {
+            let mut names = Vec::new();
+            loop {
+                match next_token(prelude, &mut pos)? {
+                    Token::Ident(name) => names.push(name.clone()),
+                    _ => return None
+                }
+                match next_token(prelude, &mut pos)? {
+                    Token::Delim('.') => continue,
+                    Token::CloseParen => break,
+                    _ => return None
+                }
+            }
+            Some(names)
+        },
+        _ => None
+    };
+    let media_start = if layer.is_some() {pos} else {media_start};
+
+    Some(ImportRule {url, media: parse_media_query_list(&prelude[media_start..]), layer, stylesheet: None})
+}
+
+fn next_token<'a>(tokens: &'a [Token], pos: &mut usize) -> Option<&'a Token> {
+    while tokens.get(*pos) == Some(&Token::Whitespace) {
+        *pos += 1;
+    }
+    *pos += 1;
+    tokens.get(*pos - 1)
+}
+
+// NOTE: ファイルからスタイルシートを読み込み、@importも再帰的に読み込む
+// 読み込めなかったファイルや循環している@importはdiagnosticsに報告して無視する
+pub fn load_stylesheet(path: &Path, diagnostics: &mut Vec<Diagnostic>) -> Stylesheet {
+    load_stylesheet_rec(path, &mut vec![], diagnostics).unwrap_or(Stylesheet {rules: vec![]})
+}
+
+// NOTE: ancestorsは今読み込んでいる途中のファイル達。ここに含まれるファイルを読もうとしたら循環している
+fn load_stylesheet_rec(path: &Path, ancestors: &mut Vec<PathBuf>, diagnostics: &mut Vec<Diagnostic>) -> Option<Stylesheet> {
+    let source = match fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(error) => {
+            diagnostics.push(Diagnostic::new(path, format!("failed to read stylesheet: {}", error)));
+            return None;
+        }
+    };
+    let canonical_path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let (mut stylesheet, warnings) = parse_with_diagnostics(source);
+    diagnostics.extend(warnings.into_iter().map(|warning| Diagnostic {path: Some(path.to_path_buf()), ..warning}));
+
+    ancestors.push(canonical_path);
+    for rule in stylesheet.rules.iter_mut() {
+        if let CssRule::Import(import_rule) = rule {
+            let import_path = path.parent().unwrap_or_else(|| Path::new("")).join(&import_rule.url);
+            let canonical_import_path = fs::canonicalize(&import_path).unwrap_or_else(|_| import_path.clone());
+            if ancestors.contains(&canonical_import_path) {
+                diagnostics.push(Diagnostic::new(path, format!("@import cycle detected: {}", import_rule.url)));
+                continue;
+            }
+            import_rule.stylesheet = load_stylesheet_rec(&import_path, ancestors, diagnostics).map(Box::new);
+        }
+    }
+    ancestors.pop();
+    Some(stylesheet)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_import_prelude, load_stylesheet};
+    use crate::css::{ImportRule, CssRule};
+    use crate::css::media::parse_media_query_list;
+    use crate::css::tokenizer::tokenize;
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn create_temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("violet-import-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn parse_import_url_layer_and_media() {
+        let import = |source: &str| parse_import_prelude(&tokenize(source));
+        assert_eq!(import(" url(\"base.css\") screen"), Some(ImportRule {
+            url: "base.css".to_string(),
+            media: parse_media_query_list(&tokenize("screen")),
+            layer: None,
+            stylesheet: None
+        }));
+        assert_eq!(import(" 'base.css' layer"), Some(ImportRule {url: "base.css".to_string(), media: vec![], layer: Some(vec![]), stylesheet: None}));
+        assert_eq!(import(" url(base.css) layer(a.b) (min-width: 600px)"), Some(ImportRule {
+            url: "base.css".to_string(),
+            media: parse_media_query_list(&tokenize("(min-width: 600px)")),
+            layer: Some(vec!["a".to_string(), "b".to_string()]),
+            stylesheet: None
+        }));
+        assert_eq!(import(" screen"), None);
+    }
+
+    #[test]
+    fn load_imports_relative_to_importing_file() {
+        let dir = create_temp_dir("relative");
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("main.css"), "@import url(\"sub/a.css\"); div {width: 1px;}").unwrap();
+        fs::write(dir.join("sub/a.css"), "@import 'b.css' print; p {width: 2px;}").unwrap();
+        fs::write(dir.join("sub/b.css"), "span {width: 3px;}").unwrap();
+
+        let mut diagnostics = vec![];
+        let stylesheet = load_stylesheet(&dir.join("main.css"), &mut diagnostics);
+        assert!(diagnostics.is_empty());
+        let a = match &stylesheet.rules[0] {
+            CssRule::Import(import_rule) => import_rule.stylesheet.as_ref().unwrap(),
+            rule => panic!("Expected import rule but found {:?}", rule)
+        };
+        match &a.rules[0] {
+            CssRule::Import(import_rule) => {
+                assert_eq!(import_rule.media, parse_media_query_list(&tokenize("print")));
+                assert_eq!(import_rule.stylesheet.as_ref().unwrap().rules.len(), 1);
+            },
+            rule => panic!("Expected import rule but found {:?}", rule)
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn report_missing_files_and_cycles() {
+        let dir = create_temp_dir("cycle");
+        fs::write(dir.join("a.css"), "@import 'b.css'; @import 'missing.css'; div {width: 1px;}").unwrap();
+        fs::write(dir.join("b.css"), "@import 'a.css'; p {width: 2px;}").unwrap();
+
+        let mut diagnostics = vec![];
+        let stylesheet = load_stylesheet(&dir.join("a.css"), &mut diagnostics);
+        assert_eq!(stylesheet.rules.len(), 3);
+        assert_eq!(diagnostics.len(), 2);
+        assert!(diagnostics[0].message.contains("cycle"));
+        assert!(diagnostics[1].message.contains("failed to read"));
+
+        let mut diagnostics = vec![];
+        load_stylesheet(&dir.join("none.css"), &mut diagnostics);
+        assert_eq!(diagnostics.len(), 1);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
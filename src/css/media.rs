@@ -0,0 +1,666 @@
+// NOTE: Media Queries Level 4 のパースと評価
+// https://www.w3.org/TR/mediaqueries-4/
+// @containerの条件も同じ書き方なので、ここでパースしてコンテナの大きさに対して評価する
+// https://www.w3.org/TR/css-contain-3/#container-rule
+
+use super::{Unit, parse_unit, trim_whitespace};
+use super::tokenizer::Token;
+
+// NOTE: @media screen, (max-width: 600px) のカンマ区切りのクエリ達。空の場合は全てに一致する
+pub type MediaList = Vec<MediaQuery>;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MediaQuery {
+    pub qualifier: Option<MediaQualifier>,
+    pub media_type: Option<String>,
+    pub condition: Option<MediaCondition>
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MediaQualifier {
+    Only,
+    Not
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum MediaCondition {
+    Feature(MediaFeature),
+    Not(Box<MediaCondition>),
+    And(Vec<MediaCondition>),
+    Or(Vec<MediaCondition>),
+    // NOTE: 知らない構文の ( ... )。常に一致しない。書き戻せるように括弧の中のトークンを持っておく
+    Unknown(Vec<Token>)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum MediaFeature {
+    // NOTE: (color) や (min-width: 600px) のような書き方
+    Plain(String, Option<MediaValue>),
+    // NOTE: (width >= 600px) や (400px < width <= 700px) のような書き方
+    // startは「値 比較 名前」、endは「名前 比較 値」の部分
+    Range {
+        name: String,
+        start: Option<(MediaValue, Comparison)>,
+        end: Option<(Comparison, MediaValue)>
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Comparison {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum MediaValue {
+    Length(f32, Unit),
+    Number(f32),
+    Ident(String),
+    // NOTE: dppxに換算した値と、書かれた単位
+    Resolution(f32, String),
+    Ratio(f32, f32)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColorScheme {
+    Light,
+    // NOTE: ダークモードを指定する手段はまだないので今はテストでしか使われない
+    #[allow(dead_code)]
+    Dark
+}
+
+// NOTE: メディアクエリを評価する対象の画面の情報
+#[derive(Clone, Debug, PartialEq)]
+pub struct Device {
+    pub media_type: String,
+    pub width: f32,
+    pub height: f32,
+    // NOTE: dppx
+    pub resolution: f32,
+    pub color_scheme: ColorScheme
+}
+
+impl Device {
+    pub fn new(width: f32, height: f32) -> Device {
+        Device {
+            media_type: "screen".to_string(),
+            width,
+            height,
+            resolution: 1.0,
+            color_scheme: ColorScheme::Light
+        }
+    }
+}
+
+// NOTE: 仕様ではメディアクエリ中のemは初期値のfont-sizeが基準になる
+const MEDIA_FONT_SIZE: f32 = 16.0;
+
+// NOTE: 不正なクエリは「not all」として扱い、他のクエリには影響させない
+pub fn parse_media_query_list(tokens: &[Token]) -> MediaList {
+    let tokens: Vec<Token> = trim_whitespace(tokens).to_vec();
+    if tokens.is_empty() {
+        return vec![];
+    }
+    tokens.split(|token| *token == Token::Comma)
+        .map(|query| parse_media_query(query).unwrap_or_else(not_all))
+        .collect()
+}
+
+// NOTE: @containerの (min-width: 400px) のような条件。不正な場合はNone
+pub fn parse_container_condition(tokens: &[Token]) -> Option<MediaCondition> {
+    let mut parser = MediaParser {tokens: trim_whitespace(tokens), pos: 0};
+    let condition = parser.parse_condition(true)?;
+    if parser.eof() {Some(condition)} else {None}
+}
+
+pub fn evaluate_media_list(media_list: &[MediaQuery], device: &Device) -> bool {
+    media_list.is_empty() || media_list.iter().any(|query| evaluate_media_query(query, device))
+}
+
+fn not_all() -> MediaQuery {
+    MediaQuery {qualifier: Some(MediaQualifier::Not), media_type: Some("all".to_string()), condition: None}
+}
+
+fn parse_media_query(tokens: &[Token]) -> Option<MediaQuery> {
+    let mut parser = MediaParser {tokens: trim_whitespace(tokens), pos: 0};
+    let query = parser.parse_media_query()?;
+    if parser.eof() {Some(query)} else {None}
+}
+
+pub fn evaluate_media_query(query: &MediaQuery, device: &Device) -> bool {
+    let type_matches = match query.media_type {
+        Some(ref media_type) => media_type.eq_ignore_ascii_case("all") || media_type.eq_ignore_ascii_case(&device.media_type),
+        None => true
+    };
+    let matches = match query.condition {
+        Some(ref condition) if type_matches => evaluate_condition(condition, device),
+        _ => Some(type_matches)
+    };
+    // NOTE: 分からない結果はnotで反転しても分からないままで、最後にfalseとして扱う
+    let matches = if query.qualifier == Some(MediaQualifier::Not) {matches.map(|matches| !matches)} else {matches};
+    matches.unwrap_or(false)
+}
+
+fn evaluate_condition(condition: &MediaCondition, device: &Device) -> Option<bool> {
+    evaluate_condition_with(condition, device, &|name| feature_value(name, device))
+}
+
+// NOTE: コンテナの大きさに対して評価する。inline-sizeのコンテナのように大きさが分からない軸の特性には一致しない
+// 値に書かれたvwやvhはdeviceを基準にする
+pub fn evaluate_container_condition(condition: &MediaCondition, width: Option<f32>, height: Option<f32>, device: &Device) -> bool {
+    let matches = evaluate_condition_with(condition, device, &|name| {
+        let value = match name {
+            "width" | "inline-size" => FeatureValue::Number(width?),
+            "height" | "block-size" => FeatureValue::Number(height?),
+            "aspect-ratio" => FeatureValue::Number(width? / height?),
+            "orientation" => FeatureValue::Ident(if height? >= width? {"portrait"} else {"landscape"}),
+            _ => return None
+        };
+        Some(value)
+    });
+    matches.unwrap_or(false)
+}
+
+// NOTE: 特性の名前から今の値を返す関数。メディアクエリなら画面、コンテナクエリならコンテナの値になる
+type FeatureLookup<'a> = &'a dyn Fn(&str) -> Option<FeatureValue>;
+
+// NOTE: 結果は true, false, 分からない(None) の三値。知らない特性や読めない条件は分からないになる
+// https://www.w3.org/TR/mediaqueries-4/#evaluating
+fn evaluate_condition_with(condition: &MediaCondition, device: &Device, features: FeatureLookup) -> Option<bool> {
+    match condition {
+        MediaCondition::Feature(feature) => evaluate_feature(feature, device, features),
+        MediaCondition::Not(condition) => evaluate_condition_with(condition, device, features).map(|matches| !matches),
+        MediaCondition::And(conditions) => all(conditions.iter().map(|c| evaluate_condition_with(c, device, features))),
+        MediaCondition::Or(conditions) => any(conditions.iter().map(|c| evaluate_condition_with(c, device, features))),
+        MediaCondition::Unknown(_) => None
+    }
+}
+
+// NOTE: 一つでもfalseならfalse、そうでなく分からないものがあれば分からない
+fn all(results: impl Iterator<Item = Option<bool>>) -> Option<bool> {
+    let mut unknown = false;
+    for result in results {
+        match result {
+            Some(false) => return Some(false),
+            Some(true) => {},
+            None => unknown = true
+        }
+    }
+    if unknown {None} else {Some(true)}
+}
+
+// NOTE: 一つでもtrueならtrue、そうでなく分からないものがあれば分からない
+fn any(results: impl Iterator<Item = Option<bool>>) -> Option<bool> {
+    all(results.map(|result| result.map(|matches| !matches))).map(|matches| !matches)
+}
+
+fn evaluate_feature(feature: &MediaFeature, device: &Device, features: FeatureLookup) -> Option<bool> {
+    match feature {
+        MediaFeature::Plain(name, None) => {
+            // NOTE: (width) のように値がない場合はその特性が0やnoneでなければ一致する
+            match features(&name.to_ascii_lowercase())? {
+                FeatureValue::Number(n) => Some(n != 0.0),
+                FeatureValue::Ident(_) => Some(true)
+            }
+        },
+        MediaFeature::Plain(name, Some(value)) => {
+            let name = name.to_ascii_lowercase();
+            let (name, comparison) = if let Some(name) = name.strip_prefix("min-") {
+                (name, Comparison::Ge)
+            } else if let Some(name) = name.strip_prefix("max-") {
+                (name, Comparison::Le)
+            } else {
+                (&*name, Comparison::Eq)
+            };
+            if comparison != Comparison::Eq && !is_range_feature(name) {
+                return None;
+            }
+            compare_feature(name, comparison, value, device, features)
+        },
+        MediaFeature::Range {name, start, end} => {
+            let name = name.to_ascii_lowercase();
+            if !is_range_feature(&name) {
+                return None;
+            }
+            // NOTE: 「値 < 名前」は「名前 > 値」と同じ
+            let start_matches = start.as_ref().map(|(value, comparison)| compare_feature(&name, flip(*comparison), value, device, features));
+            let end_matches = end.as_ref().map(|(comparison, value)| compare_feature(&name, *comparison, value, device, features));
+            all(start_matches.into_iter().chain(end_matches))
+        }
+    }
+}
+
+enum FeatureValue {
+    Number(f32),
+    Ident(&'static str)
+}
+
+// NOTE: nameは小文字になっている前提
+fn feature_value(name: &str, device: &Device) -> Option<FeatureValue> {
+    let value = match name {
+        "width" => FeatureValue::Number(device.width),
+        "height" => FeatureValue::Number(device.height),
+        "aspect-ratio" => FeatureValue::Number(device.width / device.height),
+        "resolution" => FeatureValue::Number(device.resolution),
+        "orientation" => FeatureValue::Ident(if device.height >= device.width {"portrait"} else {"landscape"}),
+        "prefers-color-scheme" => FeatureValue::Ident(match device.color_scheme {
+            ColorScheme::Light => "light",
+            ColorScheme::Dark => "dark"
+        }),
+        _ => return None
+    };
+    Some(value)
+}
+
+fn is_range_feature(name: &str) -> bool {
+    matches!(name, "width" | "height" | "inline-size" | "block-size" | "aspect-ratio" | "resolution")
+}
+
+fn is_size_feature(name: &str) -> bool {
+    matches!(name, "width" | "height" | "inline-size" | "block-size")
+}
+
+// NOTE: 知らない特性や、特性に合わない種類の値との比較は分からないになる
+fn compare_feature(name: &str, comparison: Comparison, value: &MediaValue, device: &Device, features: FeatureLookup) -> Option<bool> {
+    let expected = match (name, value) {
+        (name, MediaValue::Length(..)) if is_size_feature(name) => media_value_to_px(value, device),
+        // NOTE: 0は単位を省略できる
+        (name, MediaValue::Number(n)) if is_size_feature(name) && *n == 0.0 => Some(0.0),
+        ("aspect-ratio", MediaValue::Ratio(w, h)) => Some(w / h),
+        ("aspect-ratio", MediaValue::Number(n)) => Some(*n),
+        ("resolution", MediaValue::Resolution(dppx, _)) => Some(*dppx),
+        _ => None
+    };
+    let matches = match (features(name)?, expected) {
+        (FeatureValue::Number(actual), Some(expected)) => match comparison {
+            Comparison::Lt => actual < expected,
+            Comparison::Le => actual <= expected,
+            Comparison::Gt => actual > expected,
+            Comparison::Ge => actual >= expected,
+            Comparison::Eq => actual == expected
+        },
+        (FeatureValue::Ident(actual), None) => match (comparison, value) {
+            (Comparison::Eq, MediaValue::Ident(expected)) => expected.eq_ignore_ascii_case(actual),
+            _ => return None
+        },
+        _ => return None
+    };
+    Some(matches)
+}
+
+fn media_value_to_px(value: &MediaValue, device: &Device) -> Option<f32> {
+    match *value {
+        MediaValue::Length(f, unit) => Some(match unit {
+            Unit::Em | Unit::Rem => f * MEDIA_FONT_SIZE,
+            Unit::Ex | Unit::Ch => f * MEDIA_FONT_SIZE * 0.5,
+            Unit::Vw => f * device.width / 100.0,
+            Unit::Vh => f * device.height / 100.0,
+            Unit::Vmin => f * device.width.min(device.height) / 100.0,
+            Unit::Vmax => f * device.width.max(device.height) / 100.0,
+            unit => f * unit.absolute_px()?
+        }),
+        _ => None
+    }
+}
+
+fn flip(comparison: Comparison) -> Comparison {
+    match comparison {
+        Comparison::Lt => Comparison::Gt,
+        Comparison::Le => Comparison::Ge,
+        Comparison::Gt => Comparison::Lt,
+        Comparison::Ge => Comparison::Le,
+        Comparison::Eq => Comparison::Eq
+    }
+}
+
+struct MediaParser<'a> {
+    tokens: &'a [Token],
+    pos: usize
+}
+
+impl<'a> MediaParser<'a> {
+
+    // NOTE: <media-condition> | [not | only]? <media-type> [and <media-condition-without-or>]?
+    fn parse_media_query(&mut self) -> Option<MediaQuery> {
+        let starts_with_condition = match self.next_token() {
+            Some(Token::OpenParen) => true,
+            Some(Token::Ident(name)) if name.eq_ignore_ascii_case("not") => self.peek_past_whitespace(1) == Some(&Token::OpenParen),
+            _ => false
+        };
+        if starts_with_condition {
+            return Some(MediaQuery {qualifier: None, media_type: None, condition: Some(self.parse_condition(true)?)});
+        }
+
+        let mut qualifier = None;
+        let mut media_type = self.consume_ident()?;
+        if media_type.eq_ignore_ascii_case("not") || media_type.eq_ignore_ascii_case("only") {
+            qualifier = Some(if media_type.eq_ignore_ascii_case("not") {MediaQualifier::Not} else {MediaQualifier::Only});
+            self.consume_whitespace();
+            media_type = self.consume_ident()?;
+        }
+        if ["only", "not", "and", "or", "layer"].iter().any(|reserved| media_type.eq_ignore_ascii_case(reserved)) {
+            return None;
+        }
+        self.consume_whitespace();
+        let condition = if self.eof() {
+            None
+        } else {
+            if !self.consume_ident()?.eq_ignore_ascii_case("and") {
+                return None;
+            }
+            self.consume_whitespace();
+            Some(self.parse_condition(false)?)
+        };
+        Some(MediaQuery {qualifier, media_type: Some(media_type), condition})
+    }
+
+    // NOTE: and と or を混ぜて書くことはできないので、どちらか一方だけを読み進める
+    fn parse_condition(&mut self, allow_or: bool) -> Option<MediaCondition> {
+        if let Some(Token::Ident(name)) = self.next_token() {
+            if name.eq_ignore_ascii_case("not") {
+                self.consume_token();
+                self.consume_whitespace();
+                return Some(MediaCondition::Not(Box::new(self.parse_in_parens()?)));
+            }
+        }
+        let first = self.parse_in_parens()?;
+        self.consume_whitespace();
+        let operator = match self.next_token() {
+            Some(Token::Ident(name)) if name.eq_ignore_ascii_case("and") => "and",
+            Some(Token::Ident(name)) if name.eq_ignore_ascii_case("or") && allow_or => "or",
+            _ => return Some(first)
+        };
+        let mut conditions = vec![first];
+        while let Some(Token::Ident(name)) = self.next_token() {
+            if !name.eq_ignore_ascii_case(operator) {
+                return None;
+            }
+            self.consume_token();
+            self.consume_whitespace();
+            conditions.push(self.parse_in_parens()?);
+            self.consume_whitespace();
+        }
+        Some(if operator == "and" {MediaCondition::And(conditions)} else {MediaCondition::Or(conditions)})
+    }
+
+    fn parse_in_parens(&mut self) -> Option<MediaCondition> {
+        if self.consume_token() != Some(&Token::OpenParen) {
+            return None;
+        }
+        let start = self.pos;
+        let mut depth = 0;
+        loop {
+            match self.consume_token()? {
+                Token::OpenParen | Token::Function(_) => depth += 1,
+                Token::CloseParen if depth == 0 => break,
+                Token::CloseParen => depth -= 1,
+                _ => {}
+            }
+        }
+        let inner = trim_whitespace(&self.tokens[start..self.pos - 1]);
+        let mut inner_parser = MediaParser {tokens: inner, pos: 0};
+        let nested = match inner.first() {
+            Some(Token::OpenParen) => true,
+            Some(Token::Ident(name)) => name.eq_ignore_ascii_case("not") && inner_parser.peek_past_whitespace(1) == Some(&Token::OpenParen),
+            _ => false
+        };
+        if nested {
+            return match inner_parser.parse_condition(true) {
+                Some(condition) if inner_parser.eof() => Some(condition),
+                _ => Some(MediaCondition::Unknown(inner.to_vec()))
+            };
+        }
+        Some(parse_media_feature(inner).map_or_else(|| MediaCondition::Unknown(inner.to_vec()), MediaCondition::Feature))
+    }
+
+    fn consume_ident(&mut self) -> Option<String> {
+        match self.consume_token() {
+            Some(Token::Ident(name)) => Some(name.clone()),
+            _ => None
+        }
+    }
+
+    fn next_token(&self) -> Option<&'a Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn peek_past_whitespace(&self, offset: usize) -> Option<&'a Token> {
+        self.tokens.get(self.pos + offset..)?.iter().find(|t| **t != Token::Whitespace)
+    }
+
+    fn consume_token(&mut self) -> Option<&'a Token> {
+        let token = self.tokens.get(self.pos);
+        self.pos += 1;
+        token
+    }
+
+    fn consume_whitespace(&mut self) {
+        while self.next_token() == Some(&Token::Whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    fn eof(&self) -> bool {
+        self.pos >= self.tokens.len()
+    }
+}
+
+fn parse_media_feature(tokens: &[Token]) -> Option<MediaFeature> {
+    let tokens: Vec<&Token> = tokens.iter().filter(|t| **t != Token::Whitespace).collect();
+    match tokens.as_slice() {
+        [Token::Ident(name)] => Some(MediaFeature::Plain(name.clone(), None)),
+        [Token::Ident(name), Token::Colon, value @ ..] => Some(MediaFeature::Plain(name.clone(), Some(parse_media_value(value)?))),
+        _ => parse_range_feature(&tokens)
+    }
+}
+
+// NOTE: 「名前 比較 値」「値 比較 名前」「値 比較 名前 比較 値」の3通り
+fn parse_range_feature(tokens: &[&Token]) -> Option<MediaFeature> {
+    let mut parts: Vec<Vec<&Token>> = vec![vec![]];
+    let mut comparisons = Vec::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        let comparison = match (tokens[i], tokens.get(i + 1)) {
+            (Token::Delim('<'), Some(Token::Delim('='))) => Some((Comparison::Le, 2)),
+            (Token::Delim('>'), Some(Token::Delim('='))) => Some((Comparison::Ge, 2)),
+            (Token::Delim('<'), _) => Some((Comparison::Lt, 1)),
+            (Token::Delim('>'), _) => Some((Comparison::Gt, 1)),
+            (Token::Delim('='), _) => Some((Comparison::Eq, 1)),
+            _ => None
+        };
+        match comparison {
+            Some((comparison, length)) => {
+                comparisons.push(comparison);
+                parts.push(vec![]);
+                i += length;
+            },
+            None => {
+                parts.last_mut().unwrap().push(tokens[i]);
+                i += 1;
+            }
+        }
+    }
+
+    let as_name = |part: &Vec<&Token>| match part.as_slice() {
+        [Token::Ident(name)] => Some(name.clone()),
+        _ => None
+    };
+    let as_value = |part: &Vec<&Token>| parse_media_value(part);
+    match (parts.len(), comparisons.as_slice()) {
+        (2, [comparison]) => match as_name(&parts[0]) {
+            Some(name) => Some(MediaFeature::Range {name, start: None, end: Some((*comparison, as_value(&parts[1])?))}),
+            None => Some(MediaFeature::Range {name: as_name(&parts[1])?, start: Some((as_value(&parts[0])?, *comparison)), end: None})
+        },
+        (3, [first, second]) => {
+            // NOTE: a < width > b のように向きが揃っていないものは不正
+            let is_less = |c: &Comparison| matches!(c, Comparison::Lt | Comparison::Le);
+            let is_greater = |c: &Comparison| matches!(c, Comparison::Gt | Comparison::Ge);
+            if !(is_less(first) && is_less(second) || is_greater(first) && is_greater(second)) {
+                return None;
+            }
+            Some(MediaFeature::Range {
+                name: as_name(&parts[1])?,
+                start: Some((as_value(&parts[0])?, *first)),
+                end: Some((*second, as_value(&parts[2])?))
+            })
+        },
+        _ => None
+    }
+}
+
+// NOTE: 空白は取り除かれている前提
+fn parse_media_value(tokens: &[&Token]) -> Option<MediaValue> {
+    match tokens {
+        [Token::Number(width), Token::Delim('/'), Token::Number(height)] => Some(MediaValue::Ratio(*width, *height)),
+        [Token::Number(n)] => Some(MediaValue::Number(*n)),
+        [Token::Ident(name)] => Some(MediaValue::Ident(name.clone())),
+        [Token::Dimension(n, unit)] => {
+            let dppx = match &*unit.to_ascii_lowercase() {
+                "dpi" => Some(n / 96.0),
+                "dpcm" => Some(n * 2.54 / 96.0),
+                "dppx" | "x" => Some(*n),
+                _ => None
+            };
+            match dppx {
+                Some(dppx) => Some(MediaValue::Resolution(dppx, unit.clone())),
+                None => Some(MediaValue::Length(*n, parse_unit(unit)?))
+            }
+        },
+        _ => None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_media_query_list, evaluate_media_list, parse_container_condition, evaluate_container_condition, Device, ColorScheme, MediaQuery, MediaQualifier,
+        MediaCondition, MediaFeature, MediaValue, Comparison};
+    use crate::css::Unit;
+    use crate::css::tokenizer::tokenize;
+
+    fn matches(query: &str, device: &Device) -> bool {
+        evaluate_media_list(&parse_media_query_list(&tokenize(query)), device)
+    }
+
+    #[test]
+    fn parse_media_type_and_condition() {
+        let parsed = parse_media_query_list(&tokenize("only screen and (min-width: 600px) and (orientation: landscape), print"));
+        assert_eq!(parsed, vec![
+            MediaQuery {
+                qualifier: Some(MediaQualifier::Only),
+                media_type: Some("screen".to_string()),
+                condition: Some(MediaCondition::And(vec![
+                    MediaCondition::Feature(MediaFeature::Plain("min-width".to_string(), Some(MediaValue::Length(600.0, Unit::Px)))),
+                    MediaCondition::Feature(MediaFeature::Plain("orientation".to_string(), Some(MediaValue::Ident("landscape".to_string()))))
+                ]))
+            },
+            MediaQuery {qualifier: None, media_type: Some("print".to_string()), condition: None}
+        ]);
+    }
+
+    #[test]
+    fn parse_range_syntax() {
+        let parsed = parse_media_query_list(&tokenize("(400px <= width < 700px)"));
+        assert_eq!(parsed, vec![MediaQuery {
+            qualifier: None,
+            media_type: None,
+            condition: Some(MediaCondition::Feature(MediaFeature::Range {
+                name: "width".to_string(),
+                start: Some((MediaValue::Length(400.0, Unit::Px), Comparison::Le)),
+                end: Some((Comparison::Lt, MediaValue::Length(700.0, Unit::Px)))
+            }))
+        }]);
+    }
+
+    #[test]
+    fn invalid_query_becomes_not_all() {
+        let parsed = parse_media_query_list(&tokenize("screen and (width > 1px) or (height > 1px), (min-width: 1px)"));
+        assert_eq!(parsed[0], MediaQuery {qualifier: Some(MediaQualifier::Not), media_type: Some("all".to_string()), condition: None});
+        let device = Device::new(800.0, 600.0);
+        assert!(!matches("screen and (width > 1px) or (height > 1px)", &device));
+        assert!(matches("screen and (width > 1px) or (height > 1px), (min-width: 1px)", &device));
+        assert!(!matches("(400px < width > 700px)", &device));
+    }
+
+    #[test]
+    fn evaluate_width_and_height() {
+        let device = Device::new(800.0, 600.0);
+        assert!(matches("(max-width: 800px)", &device));
+        assert!(!matches("(max-width: 799px)", &device));
+        assert!(matches("(min-height: 37.5em)", &device));
+        assert!(matches("(width > 700px) and (height <= 600px)", &device));
+        assert!(matches("(700px < width <= 800px)", &device));
+        assert!(!matches("(900px <= width)", &device));
+        assert!(matches("not (width < 600px)", &device));
+        assert!(matches("(width < 600px) or (height = 600px)", &device));
+        assert!(matches("(width)", &device));
+        assert!(!matches("(min-orientation: portrait)", &device));
+        assert!(!matches("(unknown-feature: 1)", &device));
+    }
+
+    #[test]
+    fn evaluate_unknown_as_false_only_at_top_level() {
+        let device = Device::new(800.0, 600.0);
+        // NOTE: 分からない条件はnotで反転しても分からないままなので一致しない
+        assert!(!matches("not (unknown-feature: 1)", &device));
+        assert!(!matches("not (min-orientation: portrait)", &device));
+        assert!(!matches("not (width: portrait)", &device));
+        assert!(!matches("not ((unknown-feature) and (width > 1px))", &device));
+        assert!(!matches("not all and (unknown-feature)", &device));
+        // NOTE: andとorは分からない条件があっても、他の条件で結果が決まればその結果になる
+        assert!(matches("not ((unknown-feature) and (width < 1px))", &device));
+        assert!(matches("(unknown-feature) or (width > 1px)", &device));
+        assert!(!matches("(unknown-feature) or (width < 1px)", &device));
+        assert!(matches("(not (unknown-feature)) or (width > 1px)", &device));
+    }
+
+    #[test]
+    fn evaluate_media_types_and_qualifiers() {
+        let device = Device::new(800.0, 600.0);
+        assert!(matches("all", &device));
+        assert!(matches("screen", &device));
+        assert!(!matches("print", &device));
+        assert!(matches("not print", &device));
+        assert!(!matches("not screen and (min-width: 100px)", &device));
+        assert!(matches("", &device));
+    }
+
+    #[test]
+    fn evaluate_orientation_color_scheme_and_resolution() {
+        let mut device = Device::new(800.0, 600.0);
+        assert!(matches("(orientation: landscape)", &device));
+        assert!(!matches("(orientation: portrait)", &device));
+        assert!(matches("(prefers-color-scheme: light)", &device));
+        assert!(matches("(resolution: 96dpi)", &device));
+        assert!(!matches("(min-resolution: 2dppx)", &device));
+
+        device.color_scheme = ColorScheme::Dark;
+        device.resolution = 2.0;
+        device.width = 400.0;
+        assert!(matches("(orientation: portrait) and (prefers-color-scheme: dark)", &device));
+        assert!(matches("(min-resolution: 2x) and (resolution < 200dpi)", &device));
+    }
+
+    #[test]
+    fn evaluate_container_conditions() {
+        let device = Device::new(800.0, 600.0);
+        let matches = |condition: &str, width: Option<f32>, height: Option<f32>| {
+            let condition = parse_container_condition(&tokenize(condition)).unwrap();
+            evaluate_container_condition(&condition, width, height, &device)
+        };
+        assert!(matches("(min-width: 400px)", Some(400.0), Some(100.0)));
+        assert!(!matches("(min-width: 400px)", Some(399.0), Some(100.0)));
+        assert!(matches("(inline-size > 25em) and (orientation: landscape)", Some(401.0), Some(100.0)));
+        assert!(matches("(width < 50vw) or (height > 1px)", Some(300.0), None));
+        // NOTE: 大きさが分からない軸や、コンテナクエリにない特性には一致しない
+        assert!(!matches("(min-height: 0)", Some(300.0), None));
+        assert!(!matches("(aspect-ratio > 1/2)", Some(300.0), None));
+        assert!(!matches("(prefers-color-scheme: light)", Some(300.0), Some(100.0)));
+        assert!(!matches("not (min-height: 0)", Some(300.0), None));
+
+        assert_eq!(parse_container_condition(&tokenize("(width > 1px) and (height > 1px) or (width)")), None);
+        assert_eq!(parse_container_condition(&tokenize("")), None);
+    }
+}
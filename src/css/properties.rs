@@ -0,0 +1,427 @@
+// NOTE: violetが解釈できるプロパティと、それぞれが継承するか、初期値、値として受け付けるもの
+// ここに載っていない宣言はパースの段階で捨て、@supportsで「対応している」と答えるのもここに載っているものだけ
+
+use super::{Value, Separator, named_color};
+
+const CSS_WIDE_KEYWORDS: [&str; 5] = ["inherit", "initial", "unset", "revert", "revert-layer"];
+
+const BORDER_STYLES: [&str; 10] = ["none", "hidden", "dotted", "dashed", "solid", "double", "groove", "ridge", "inset", "outset"];
+
+const FONT_STRETCHES: [&str; 9] = ["normal", "ultra-condensed", "extra-condensed", "condensed", "semi-condensed",
+    "semi-expanded", "expanded", "extra-expanded", "ultra-expanded"];
+
+// NOTE: レイアウトで扱えるのはblock, inline, noneだけで、それ以外はinlineとして並べる
+const DISPLAYS: [&str; 9] = ["block", "inline", "none", "inline-block", "flex", "inline-flex", "flow-root", "list-item", "contents"];
+
+const TIMING_FUNCTIONS: [&str; 7] = ["linear", "ease", "ease-in", "ease-out", "ease-in-out", "step-start", "step-end"];
+
+// NOTE: プロパティ一つ分の定義。継承するか、初期値、受け付ける値、ショートハンドなら展開先のロングハンド
+pub struct PropertyDefinition {
+    pub name: &'static str,
+    pub inherited: bool,
+    // NOTE: CSSとして読める初期値。ショートハンドは初期値を持たず、展開先のロングハンドの初期値を使う
+    pub initial: &'static str,
+    grammar: fn(&Value) -> bool,
+    // NOTE: ショートハンドの場合だけ、展開した値の順番に並べる。allは全てのロングハンドに展開するので空にしておく
+    longhands: &'static [&'static str]
+}
+
+const fn longhand(name: &'static str, inherited: bool, initial: &'static str, grammar: fn(&Value) -> bool) -> PropertyDefinition {
+    PropertyDefinition {name, inherited, initial, grammar, longhands: &[]}
+}
+
+const fn shorthand(name: &'static str, longhands: &'static [&'static str], grammar: fn(&Value) -> bool) -> PropertyDefinition {
+    PropertyDefinition {name, inherited: false, initial: "", grammar, longhands}
+}
+
+// NOTE: violetが解釈できる全てのプロパティ。ここにないプロパティの宣言はパースの段階で捨てる
+// https://www.w3.org/TR/css-cascade-4/#property
+pub const PROPERTIES: &[PropertyDefinition] = &[
+    longhand("display", false, "inline", |v| is_one_of(v, &DISPLAYS)),
+    // NOTE: まだレイアウトでは使っていないが、computed valueとしては読んでいる
+    longhand("position", false, "static", |v| is_one_of(v, &["static", "relative", "absolute", "fixed", "sticky"])),
+    longhand("float", false, "none", |v| is_one_of(v, &["none", "left", "right"])),
+    longhand("width", false, "auto", is_size),
+    longhand("height", false, "auto", is_size),
+    longhand("margin-top", false, "0", is_margin),
+    longhand("margin-right", false, "0", is_margin),
+    longhand("margin-bottom", false, "0", is_margin),
+    longhand("margin-left", false, "0", is_margin),
+    longhand("padding-top", false, "0", is_length_percentage),
+    longhand("padding-right", false, "0", is_length_percentage),
+    longhand("padding-bottom", false, "0", is_length_percentage),
+    longhand("padding-left", false, "0", is_length_percentage),
+    longhand("border-top-width", false, "medium", is_border_width),
+    longhand("border-right-width", false, "medium", is_border_width),
+    longhand("border-bottom-width", false, "medium", is_border_width),
+    longhand("border-left-width", false, "medium", is_border_width),
+    longhand("border-top-style", false, "none", is_border_style),
+    longhand("border-right-style", false, "none", is_border_style),
+    longhand("border-bottom-style", false, "none", is_border_style),
+    longhand("border-left-style", false, "none", is_border_style),
+    longhand("border-top-color", false, "currentcolor", is_color),
+    longhand("border-right-color", false, "currentcolor", is_color),
+    longhand("border-bottom-color", false, "currentcolor", is_color),
+    longhand("border-left-color", false, "currentcolor", is_color),
+    longhand("color", true, "black", is_color),
+    longhand("background-color", false, "transparent", is_color),
+    longhand("background-image", false, "none", |v| is_list_of(v, is_image)),
+    longhand("background-repeat", false, "repeat", |v| is_list_of(v, |v| is_up_to(v, 2, is_repeat))),
+    longhand("background-attachment", false, "scroll", |v| is_list_of(v, is_attachment)),
+    longhand("background-position", false, "0% 0%", |v| is_list_of(v, |v| is_up_to(v, 4, is_position))),
+    longhand("background-size", false, "auto", |v| is_list_of(v, is_background_size)),
+    longhand("background-origin", false, "padding-box", |v| is_list_of(v, is_box_keyword)),
+    longhand("background-clip", false, "border-box", |v| is_list_of(v, is_box_keyword)),
+    // NOTE: mediumに当たる16px
+    longhand("font-size", true, "16px", is_font_size),
+    longhand("font-weight", true, "normal", is_font_weight),
+    longhand("line-height", true, "normal", is_line_height),
+    longhand("font-style", true, "normal", |v| is_one_of(v, &["normal", "italic", "oblique"])),
+    longhand("font-variant", true, "normal", |v| is_one_of(v, &["normal", "small-caps"])),
+    longhand("font-stretch", true, "normal", |v| is_one_of(v, &FONT_STRETCHES)),
+    longhand("font-family", true, "serif", |v| is_list_of(v, is_font_family)),
+    longhand("animation-name", false, "none", |v| is_list_of(v, |v| matches!(v, Value::Keyword(_) | Value::String(_)))),
+    longhand("animation-duration", false, "0s", |v| is_list_of(v, is_time)),
+    longhand("animation-delay", false, "0s", |v| is_list_of(v, is_time)),
+    longhand("animation-iteration-count", false, "1", |v| is_list_of(v, |v| is_keyword(v, "infinite") || is_non_negative_number(v))),
+    longhand("animation-direction", false, "normal", |v| is_list_of(v, |v| is_one_of(v, &["normal", "reverse", "alternate", "alternate-reverse"]))),
+    longhand("animation-fill-mode", false, "none", |v| is_list_of(v, |v| is_one_of(v, &["none", "forwards", "backwards", "both"]))),
+    longhand("animation-play-state", false, "running", |v| is_list_of(v, |v| is_one_of(v, &["running", "paused"]))),
+    longhand("animation-timing-function", false, "ease", |v| is_list_of(v, is_timing_function)),
+    longhand("transition-property", false, "all", |v| is_list_of(v, |v| matches!(v, Value::Keyword(_)))),
+    longhand("transition-duration", false, "0s", |v| is_list_of(v, is_time)),
+    longhand("transition-delay", false, "0s", |v| is_list_of(v, is_time)),
+    longhand("transition-timing-function", false, "ease", |v| is_list_of(v, is_timing_function)),
+    // NOTE: まだレイアウトや描画では使っていないが、宣言としては受け付けて値を残しておく
+    longhand("opacity", false, "1", |v| matches!(v, Value::Number(_) | Value::Percentage(_))),
+    longhand("transform", false, "none", |v| is_keyword(v, "none") || is_function_list(v)),
+    longhand("filter", false, "none", |v| is_keyword(v, "none") || is_function_list(v)),
+    longhand("content", false, "normal", is_content),
+    longhand("outline-color", false, "currentcolor", |v| is_color(v) || is_keyword(v, "invert")),
+    longhand("fill", true, "black", is_paint),
+    longhand("stroke", true, "none", is_paint),
+    longhand("list-style-image", true, "none", is_image),
+    longhand("row-gap", false, "normal", is_gap),
+    longhand("column-gap", false, "normal", is_gap),
+    longhand("container-type", false, "normal", |v| is_one_of(v, &["normal", "size", "inline-size"])),
+    longhand("container-name", false, "none", is_container_name),
+    // NOTE: ショートハンド。styleで展開できる組み合わせだけを受け付ける
+    shorthand("margin", &["margin-top", "margin-right", "margin-bottom", "margin-left"], |v| is_box(v, is_margin)),
+    shorthand("padding", &["padding-top", "padding-right", "padding-bottom", "padding-left"], |v| is_box(v, is_length_percentage)),
+    shorthand("border-width", &["border-top-width", "border-right-width", "border-bottom-width", "border-left-width"], |v| is_box(v, is_border_width)),
+    shorthand("border-style", &["border-top-style", "border-right-style", "border-bottom-style", "border-left-style"], |v| is_box(v, is_border_style)),
+    shorthand("border-color", &["border-top-color", "border-right-color", "border-bottom-color", "border-left-color"], |v| is_box(v, is_color)),
+    // NOTE: borderは4辺それぞれのwidth, style, colorの順に並べる
+    shorthand("border", &["border-top-width", "border-top-style", "border-top-color",
+        "border-right-width", "border-right-style", "border-right-color",
+        "border-bottom-width", "border-bottom-style", "border-bottom-color",
+        "border-left-width", "border-left-style", "border-left-color"], is_border),
+    shorthand("border-top", &["border-top-width", "border-top-style", "border-top-color"], is_border),
+    shorthand("border-right", &["border-right-width", "border-right-style", "border-right-color"], is_border),
+    shorthand("border-bottom", &["border-bottom-width", "border-bottom-style", "border-bottom-color"], is_border),
+    shorthand("border-left", &["border-left-width", "border-left-style", "border-left-color"], is_border),
+    shorthand("background", &["background-color", "background-image", "background-repeat", "background-attachment",
+        "background-position", "background-size", "background-origin", "background-clip"], is_background),
+    shorthand("font", &["font-style", "font-variant", "font-weight", "font-stretch", "font-size", "line-height", "font-family"], is_font),
+    shorthand("container", &["container-name", "container-type"], is_container),
+    shorthand("gap", &["row-gap", "column-gap"], |v| is_up_to(v, 2, is_gap)),
+    // NOTE: CSS全体のキーワードだけを受け付ける
+    // https://www.w3.org/TR/css-cascade-4/#all-shorthand
+    shorthand("all", &[], |_| false)
+];
+
+// NOTE: プロパティ名は大文字と小文字を区別しない
+pub fn property_definition(name: &str) -> Option<&'static PropertyDefinition> {
+    PROPERTIES.iter().find(|property| property.name.eq_ignore_ascii_case(name))
+}
+
+impl PropertyDefinition {
+    pub fn is_shorthand(&self) -> bool {
+        !self.longhands.is_empty() || self.name == "all"
+    }
+
+    pub fn longhands(&self) -> Vec<&'static str> {
+        if self.name == "all" {
+            PROPERTIES.iter().filter(|property| !property.is_shorthand()).map(|property| property.name).collect()
+        } else {
+            self.longhands.to_vec()
+        }
+    }
+
+    // NOTE: CSS全体のキーワードとvar()を含む値は別に扱うので、ここではプロパティ自身の文法だけを見る
+    pub fn accepts(&self, value: &Value) -> bool {
+        (self.grammar)(value)
+    }
+
+    pub fn initial_value(&self) -> Value {
+        super::parse_value_tokens(super::tokenizer::tokenize_with_positions(self.initial).0).expect("initial value of a property must be parsable")
+    }
+}
+
+// NOTE: 宣言をパースする時と@supportsで使う。どちらも解釈できない宣言は「対応していない」ものとして扱う
+// カスタムプロパティはどんな値でも受け付ける。var()を含む値は置換するまで分からないので、知っているプロパティなら受け付ける
+pub fn is_supported_declaration(name: &str, value: &Value) -> bool {
+    if name.starts_with("--") {
+        return true;
+    }
+    let property = match property_definition(name) {
+        Some(property) => property,
+        None => return false
+    };
+    match value {
+        Value::Tokens(_) => true,
+        value if is_css_wide_keyword(value) => true,
+        value => property.accepts(value)
+    }
+}
+
+pub fn is_css_wide_keyword(value: &Value) -> bool {
+    is_one_of(value, &CSS_WIDE_KEYWORDS)
+}
+
+fn is_keyword(value: &Value, name: &str) -> bool {
+    matches!(value, Value::Keyword(keyword) if keyword.eq_ignore_ascii_case(name))
+}
+
+fn is_one_of(value: &Value, names: &[&str]) -> bool {
+    names.iter().any(|name| is_keyword(value, name))
+}
+
+// NOTE: カンマ区切りのリストなら全ての要素が、そうでなければ値そのものがis_validを満たすか
+fn is_list_of(value: &Value, is_valid: fn(&Value) -> bool) -> bool {
+    match value {
+        Value::List(items, Separator::Comma) => items.iter().all(is_valid),
+        value => is_valid(value)
+    }
+}
+
+fn components(value: &Value) -> Vec<&Value> {
+    match value {
+        Value::List(items, Separator::Space) => items.iter().collect(),
+        value => vec![value]
+    }
+}
+
+// NOTE: 単位のない0は長さとして使える
+fn is_length_percentage(value: &Value) -> bool {
+    match value {
+        Value::Length(..) | Value::Percentage(_) => true,
+        Value::Number(n) => *n == 0.0,
+        Value::Calc(node) => !node.is_number(),
+        _ => false
+    }
+}
+
+fn is_size(value: &Value) -> bool {
+    is_length_percentage(value) || is_keyword(value, "auto")
+}
+
+fn is_margin(value: &Value) -> bool {
+    is_length_percentage(value) || is_keyword(value, "auto")
+}
+
+fn is_border_width(value: &Value) -> bool {
+    match value {
+        Value::Length(..) => true,
+        Value::Number(n) => *n == 0.0,
+        Value::Calc(node) => !node.is_number(),
+        value => is_one_of(value, &["thin", "medium", "thick"])
+    }
+}
+
+fn is_border_style(value: &Value) -> bool {
+    is_one_of(value, &BORDER_STYLES)
+}
+
+fn is_color(value: &Value) -> bool {
+    match value {
+        Value::ColorValue(_) => true,
+        Value::Keyword(keyword) => keyword.eq_ignore_ascii_case("currentcolor") || named_color(keyword).is_some(),
+        _ => false
+    }
+}
+
+fn is_font_size(value: &Value) -> bool {
+    is_length_percentage(value) || is_one_of(value, &["xx-small", "x-small", "small", "medium", "large",
+        "x-large", "xx-large", "xxx-large", "larger", "smaller"])
+}
+
+fn is_font_weight(value: &Value) -> bool {
+    matches!(*value, Value::Number(n) if (1.0..=1000.0).contains(&n)) || is_one_of(value, &["normal", "bold", "bolder", "lighter"])
+}
+
+fn is_line_height(value: &Value) -> bool {
+    is_length_percentage(value) || is_non_negative_number(value) || is_keyword(value, "normal")
+}
+
+// NOTE: Times New Roman のようにクォートされていない名前はスペース区切りの識別子になる
+fn is_font_family(value: &Value) -> bool {
+    matches!(value, Value::String(_)) || components(value).iter().all(|v| matches!(v, Value::Keyword(_)))
+}
+
+fn is_non_negative_number(value: &Value) -> bool {
+    matches!(*value, Value::Number(n) if n >= 0.0)
+}
+
+fn is_time(value: &Value) -> bool {
+    matches!(value, Value::Time(_))
+}
+
+fn is_timing_function(value: &Value) -> bool {
+    match value {
+        Value::Function {name, args} => (name.eq_ignore_ascii_case("cubic-bezier") && args.len() == 4)
+            || (name.eq_ignore_ascii_case("steps") && (1..=2).contains(&args.len())),
+        value => is_one_of(value, &TIMING_FUNCTIONS)
+    }
+}
+
+// NOTE: スペース区切りで1~max個の値が全てis_validを満たすか
+fn is_up_to(value: &Value, max: usize, is_valid: fn(&Value) -> bool) -> bool {
+    let components = components(value);
+    components.len() <= max && components.into_iter().all(is_valid)
+}
+
+fn is_image(value: &Value) -> bool {
+    is_keyword(value, "none") || matches!(value, Value::Url(_) | Value::Function {..})
+}
+
+// NOTE: translate(10px, 0) scale(1.5) のようなスペース区切りの関数
+fn is_function_list(value: &Value) -> bool {
+    components(value).into_iter().all(|v| matches!(v, Value::Function {..}))
+}
+
+fn is_content(value: &Value) -> bool {
+    is_one_of(value, &["normal", "none"])
+        || components(value).into_iter().all(|v| matches!(v, Value::String(_) | Value::Url(_) | Value::Function {..} | Value::Keyword(_)))
+}
+
+fn is_paint(value: &Value) -> bool {
+    is_color(value) || is_keyword(value, "none") || matches!(value, Value::Url(_))
+}
+
+fn is_gap(value: &Value) -> bool {
+    is_length_percentage(value) || is_keyword(value, "normal")
+}
+
+fn is_repeat(value: &Value) -> bool {
+    is_one_of(value, &["repeat", "repeat-x", "repeat-y", "no-repeat", "space", "round"])
+}
+
+fn is_attachment(value: &Value) -> bool {
+    is_one_of(value, &["scroll", "fixed", "local"])
+}
+
+fn is_position(value: &Value) -> bool {
+    is_length_percentage(value) || is_one_of(value, &["left", "right", "top", "bottom", "center"])
+}
+
+fn is_background_size(value: &Value) -> bool {
+    is_one_of(value, &["cover", "contain"]) || is_up_to(value, 2, is_size)
+}
+
+fn is_box_keyword(value: &Value) -> bool {
+    is_one_of(value, &["border-box", "padding-box", "content-box"])
+}
+
+// NOTE: top, right, bottom, leftに割り当てる1~4個の値
+fn is_box(value: &Value, is_valid: fn(&Value) -> bool) -> bool {
+    let components = components(value);
+    (1..=4).contains(&components.len()) && components.into_iter().all(is_valid)
+}
+
+// NOTE: width, style, colorを順不同で1つずつまで
+fn is_border(value: &Value) -> bool {
+    let kinds: [fn(&Value) -> bool; 3] = [is_border_width, is_border_style, is_color];
+    let mut seen = [false; 3];
+    for component in components(value) {
+        match kinds.iter().position(|is_kind| is_kind(component)) {
+            Some(kind) if !seen[kind] => seen[kind] = true,
+            _ => return false
+        }
+    }
+    true
+}
+
+// NOTE: 色は最後のレイヤーにだけ書ける
+fn is_background(value: &Value) -> bool {
+    let layers = match value {
+        Value::List(layers, Separator::Comma) => layers.iter().collect(),
+        value => vec![value]
+    };
+    layers.iter().enumerate().all(|(i, layer)| components(layer).into_iter().all(|component| match component {
+        Value::List(items, Separator::Slash) => items.len() == 2 && components(&items[0]).into_iter().all(is_position),
+        component => (is_color(component) && i == layers.len() - 1)
+            || is_position(component)
+            || is_image(component) || is_repeat(component) || is_attachment(component) || is_box_keyword(component)
+    }))
+}
+
+// NOTE: [style || variant || weight || stretch]? size [/ line-height]? family
+fn is_font(value: &Value) -> bool {
+    let head = match value {
+        Value::List(items, Separator::Comma) => {
+            if !items[1..].iter().all(is_font_family) {
+                return false;
+            }
+            components(&items[0])
+        },
+        value => components(value)
+    };
+    let size_position = match head.iter().position(|component| match component {
+        Value::List(items, Separator::Slash) => items.len() == 2 && is_font_size(&items[0]) && is_line_height(&items[1]),
+        component => is_font_size(component)
+    }) {
+        Some(position) => position,
+        None => return false
+    };
+    let is_prefix = |v: &&Value| is_one_of(v, &["normal", "italic", "oblique", "small-caps"]) || is_font_weight(v)
+        || is_one_of(v, &FONT_STRETCHES);
+    let family = &head[size_position + 1..];
+    size_position <= 4 && head[..size_position].iter().all(is_prefix)
+        && !family.is_empty() && family.iter().all(|v| matches!(v, Value::Keyword(_) | Value::String(_)))
+}
+
+// NOTE: none か、一つ以上の名前
+fn is_container_name(value: &Value) -> bool {
+    is_keyword(value, "none") || components(value).into_iter().all(|v| match v {
+        Value::Keyword(name) => !is_one_of(v, &CSS_WIDE_KEYWORDS) && !["none", "and", "or", "not"].iter().any(|reserved| name.eq_ignore_ascii_case(reserved)),
+        _ => false
+    })
+}
+
+// NOTE: <container-name> [/ <container-type>]?
+fn is_container(value: &Value) -> bool {
+    match value {
+        Value::List(items, Separator::Slash) => items.len() == 2 && is_container_name(&items[0]) && is_one_of(&items[1], &["normal", "size", "inline-size"]),
+        value => is_container_name(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PROPERTIES, property_definition, is_supported_declaration};
+    use crate::css::{Value, Unit, Separator};
+
+    #[test]
+    fn every_property_has_a_consistent_definition() {
+        for property in PROPERTIES {
+            if !property.is_shorthand() {
+                // NOTE: 初期値は自身の文法で受け付けられる値になっている
+                assert!(is_supported_declaration(property.name, &property.initial_value()), "{}", property.name);
+            } else {
+                assert!(property.initial.is_empty() && !property.inherited, "{}", property.name);
+                assert!(property.longhands().iter().all(|longhand| property_definition(longhand).is_some_and(|l| !l.is_shorthand())), "{}", property.name);
+            }
+        }
+        assert!(property_definition("Font-Size").is_some_and(|property| property.inherited));
+        assert!(property_definition("margin-top").is_some_and(|property| !property.inherited));
+        assert!(property_definition("colour").is_none());
+        let all = property_definition("all").unwrap().longhands();
+        assert!(all.contains(&"color") && all.contains(&"margin-top") && !all.contains(&"margin"));
+        assert!(is_supported_declaration("all", &Value::Keyword("revert-layer".to_string())));
+        assert!(!is_supported_declaration("all", &Value::Keyword("auto".to_string())));
+        let font = Value::List(vec![Value::Number(700.0), Value::Length(12.0, Unit::Px), Value::Keyword("serif".to_string())], Separator::Space);
+        assert!(is_supported_declaration("font", &font));
+    }
+
+}
@@ -0,0 +1,769 @@
+// NOTE: スタイルシートをCSSの文字列に書き戻す
+// https://www.w3.org/TR/cssom-1/#serializing-css-values
+
+use super::{Stylesheet, CssRule, MediaRule, ImportRule, KeyframesRule, SupportsRule, LayerRule, LayerStatementRule, ContainerRule, Rule, Selector, SimpleSelector, AttributeMatcher, Combinator, PseudoClass, Declaration, Value, Separator, Unit, Color, CalcNode};
+use super::media::{MediaQuery, MediaQualifier, MediaCondition, MediaFeature, MediaValue, Comparison};
+use super::supports::SupportsCondition;
+use super::tokenizer::Token;
+use super::{is_reserved_keyframes_name, unclosed_block_ends};
+
+// NOTE: 差分を見たりデバッグしたりしやすいように、1行に1宣言でインデントを揃えて書く
+pub fn serialize(stylesheet: &Stylesheet) -> String {
+    let mut serializer = Serializer {minify: false, output: String::new()};
+    serializer.write_rules(&stylesheet.rules, 0);
+    serializer.output
+}
+
+// NOTE: 不要な空白を取り除き、#ffffffを#fffにするように値も短く書く
+pub fn minify(stylesheet: &Stylesheet) -> String {
+    let mut serializer = Serializer {minify: true, output: String::new()};
+    serializer.write_rules(&stylesheet.rules, 0);
+    serializer.output
+}
+
+// NOTE: get_property_valueのように値一つだけを書く時に使う
+pub fn serialize_value(value: &Value) -> String {
+    Serializer {minify: false, output: String::new()}.serialize_value(value)
+}
+
+struct Serializer {
+    minify: bool,
+    output: String
+}
+
+impl Serializer {
+    fn write_rules(&mut self, rules: &[CssRule], depth: usize) {
+        for (i, rule) in rules.iter().enumerate() {
+            if i > 0 && !self.minify {
+                self.output.push('\n');
+            }
+            match rule {
+                CssRule::Style(rule) => self.write_style_rule(rule, depth),
+                CssRule::Media(media_rule) => self.write_media_rule(media_rule, depth),
+                CssRule::Import(import_rule) => self.write_import_rule(import_rule, depth),
+                CssRule::Keyframes(keyframes_rule) => self.write_keyframes_rule(keyframes_rule, depth),
+                CssRule::Supports(supports_rule) => self.write_supports_rule(supports_rule, depth),
+                CssRule::Layer(layer_rule) => self.write_layer_rule(layer_rule, depth),
+                CssRule::LayerStatement(statement) => self.write_layer_statement(statement, depth),
+                CssRule::Container(container_rule) => self.write_container_rule(container_rule, depth)
+            }
+        }
+    }
+
+    fn write_style_rule(&mut self, rule: &Rule, depth: usize) {
+        let selectors: Vec<String> = rule.selectors.iter().map(serialize_selector).collect();
+        self.write_declaration_block(&selectors.join(self.comma()), &rule.declarations, depth);
+    }
+
+    fn write_declaration_block(&mut self, prelude: &str, declarations: &[Declaration], depth: usize) {
+        self.write_indent(depth);
+        self.output.push_str(prelude);
+        self.open_block();
+        for (i, declaration) in declarations.iter().enumerate() {
+            self.write_indent(depth + 1);
+            self.write_declaration(declaration);
+            // NOTE: 最小化する時は最後の宣言の ; を省く
+            if !self.minify || i + 1 < declarations.len() {
+                self.output.push(';');
+            }
+            self.write_newline();
+        }
+        self.close_block(depth);
+    }
+
+    fn write_media_rule(&mut self, media_rule: &MediaRule, depth: usize) {
+        self.write_indent(depth);
+        self.output.push_str("@media");
+        if !media_rule.media.is_empty() {
+            self.output.push(' ');
+            self.output.push_str(&self.serialize_media_list(&media_rule.media));
+        }
+        self.open_block();
+        self.write_rules(&media_rule.rules, depth + 1);
+        self.close_block(depth);
+    }
+
+    fn write_supports_rule(&mut self, supports_rule: &SupportsRule, depth: usize) {
+        self.write_indent(depth);
+        self.output.push_str("@supports ");
+        self.output.push_str(&self.serialize_supports_condition(&supports_rule.condition));
+        self.open_block();
+        self.write_rules(&supports_rule.rules, depth + 1);
+        self.close_block(depth);
+    }
+
+    fn write_layer_rule(&mut self, layer_rule: &LayerRule, depth: usize) {
+        self.write_indent(depth);
+        self.output.push_str("@layer");
+        if !layer_rule.name.is_empty() {
+            self.output.push(' ');
+            self.output.push_str(&serialize_layer_name(&layer_rule.name));
+        }
+        self.open_block();
+        self.write_rules(&layer_rule.rules, depth + 1);
+        self.close_block(depth);
+    }
+
+    fn write_layer_statement(&mut self, statement: &LayerStatementRule, depth: usize) {
+        self.write_indent(depth);
+        self.output.push_str("@layer ");
+        let names: Vec<String> = statement.names.iter().map(|name| serialize_layer_name(name)).collect();
+        self.output.push_str(&names.join(self.comma()));
+        self.output.push(';');
+        self.write_newline();
+    }
+
+    fn write_container_rule(&mut self, container_rule: &ContainerRule, depth: usize) {
+        self.write_indent(depth);
+        self.output.push_str("@container ");
+        if let Some(ref name) = container_rule.name {
+            self.output.push_str(&serialize_identifier(name));
+            self.output.push(' ');
+        }
+        self.output.push_str(&self.serialize_condition(&container_rule.condition));
+        self.open_block();
+        self.write_rules(&container_rule.rules, depth + 1);
+        self.close_block(depth);
+    }
+
+    // NOTE: from, toも含めてキーフレームセレクタはパーセンテージで書く
+    fn write_keyframes_rule(&mut self, keyframes_rule: &KeyframesRule, depth: usize) {
+        self.write_indent(depth);
+        self.output.push_str("@keyframes ");
+        if is_reserved_keyframes_name(&keyframes_rule.name) {
+            self.output.push_str(&serialize_string(&keyframes_rule.name));
+        } else {
+            self.output.push_str(&serialize_identifier(&keyframes_rule.name));
+        }
+        self.open_block();
+        for (i, keyframe) in keyframes_rule.keyframes.iter().enumerate() {
+            if i > 0 && !self.minify {
+                self.output.push('\n');
+            }
+            let offsets: Vec<String> = keyframe.offsets.iter().map(|offset| format!("{}%", self.serialize_number(*offset))).collect();
+            self.write_declaration_block(&offsets.join(self.comma()), &keyframe.declarations, depth + 1);
+        }
+        self.close_block(depth);
+    }
+
+    // NOTE: 読み込んだスタイルシートの中身は書き出さない
+    fn write_import_rule(&mut self, import_rule: &ImportRule, depth: usize) {
+        self.write_indent(depth);
+        self.output.push_str("@import ");
+        self.output.push_str(&self.serialize_url(&import_rule.url));
+        match import_rule.layer {
+            Some(ref names) if names.is_empty() => self.output.push_str(" layer"),
+            Some(ref names) => self.output.push_str(&format!(" layer({})", serialize_layer_name(names))),
+            None => {}
+        }
+        if !import_rule.media.is_empty() {
+            self.output.push(' ');
+            self.output.push_str(&self.serialize_media_list(&import_rule.media));
+        }
+        self.output.push(';');
+        self.write_newline();
+    }
+
+    fn write_declaration(&mut self, declaration: &Declaration) {
+        self.output.push_str(&serialize_identifier(&declaration.name));
+        self.output.push(':');
+        if !self.minify {
+            self.output.push(' ');
+        }
+        self.output.push_str(&self.serialize_value(&declaration.value));
+        if declaration.important {
+            self.output.push_str(if self.minify {"!important"} else {" !important"});
+        }
+    }
+
+    fn open_block(&mut self) {
+        self.output.push_str(if self.minify {"{"} else {" {"});
+        self.write_newline();
+    }
+
+    fn close_block(&mut self, depth: usize) {
+        self.write_indent(depth);
+        self.output.push('}');
+        self.write_newline();
+    }
+
+    fn write_indent(&mut self, depth: usize) {
+        if !self.minify {
+            self.output.push_str(&"    ".repeat(depth));
+        }
+    }
+
+    fn write_newline(&mut self) {
+        if !self.minify {
+            self.output.push('\n');
+        }
+    }
+
+    fn serialize_value(&self, value: &Value) -> String {
+        match value {
+            Value::Keyword(keyword) => serialize_identifier(keyword),
+            Value::Length(f, unit) => self.serialize_length(*f, *unit),
+            Value::Percentage(p) => format!("{}%", self.serialize_number(*p)),
+            Value::Number(n) => self.serialize_number(*n),
+            Value::Time(seconds) => format!("{}s", self.serialize_number(*seconds)),
+            Value::Angle(degrees) => format!("{}deg", self.serialize_number(*degrees)),
+            Value::ColorValue(color) => self.serialize_color(color),
+            Value::String(string) => serialize_string(string),
+            Value::Url(url) => self.serialize_url(url),
+            Value::Function {name, args} => {
+                let args: Vec<String> = args.iter().map(|arg| self.serialize_value(arg)).collect();
+                format!("{}({})", serialize_identifier(name), args.join(self.comma()))
+            },
+            Value::List(values, separator) => {
+                let values: Vec<String> = values.iter().map(|value| self.serialize_value(value)).collect();
+                let separator = match separator {
+                    Separator::Space => " ",
+                    Separator::Comma => self.comma(),
+                    Separator::Slash => if self.minify {"/"} else {" / "}
+                };
+                values.join(separator)
+            },
+            Value::Tokens(tokens) => serialize_tokens(tokens),
+            // NOTE: min(), max(), clamp()はそのまま書けるので、それ以外の式だけcalc()で囲む
+            Value::Calc(node) => match node {
+                CalcNode::Min(_) | CalcNode::Max(_) | CalcNode::Clamp(..) => self.serialize_calc(node),
+                node => format!("calc({})", self.serialize_calc(node))
+            }
+        }
+    }
+
+    fn serialize_number(&self, n: f32) -> String {
+        let number = n.to_string();
+        // NOTE: 0.5は.5のように先頭の0を省ける
+        if self.minify {
+            if let Some(fraction) = number.strip_prefix("0.") {
+                return format!(".{}", fraction);
+            }
+            if let Some(fraction) = number.strip_prefix("-0.") {
+                return format!("-.{}", fraction);
+            }
+        }
+        number
+    }
+
+    fn serialize_length(&self, f: f32, unit: Unit) -> String {
+        // NOTE: 単位のない0はpxの0として読まれるので、0pxだけは単位を省ける
+        if self.minify && f == 0.0 && unit == Unit::Px {
+            return "0".to_string();
+        }
+        format!("{}{}", self.serialize_number(f), unit_name(unit))
+    }
+
+    fn serialize_color(&self, color: &Color) -> String {
+        let mut channels = vec![color.r, color.g, color.b];
+        if color.a != 255 {
+            channels.push(color.a);
+        }
+        // NOTE: 全ての成分が0x11の倍数なら#rgbや#rgbaの短い形で書ける
+        if self.minify && channels.iter().all(|channel| channel % 17 == 0) {
+            return channels.iter().fold("#".to_string(), |hex, channel| format!("{}{:x}", hex, channel / 17));
+        }
+        channels.iter().fold("#".to_string(), |hex, channel| format!("{}{:02x}", hex, channel))
+    }
+
+    // NOTE: 最小化する時は、クォートしなくても読めるURLのクォートを省く
+    fn serialize_url(&self, url: &str) -> String {
+        let needs_quote = url.is_empty() || url.chars().any(|c| c.is_whitespace() || c.is_control() || "\"'()\\".contains(c));
+        if self.minify && !needs_quote {
+            format!("url({})", url)
+        } else {
+            format!("url({})", serialize_string(url))
+        }
+    }
+
+    // NOTE: 優先順位が低い演算が中にある時と、右側に同じ優先順位の演算がある時だけ括弧で囲む
+    fn serialize_calc(&self, node: &CalcNode) -> String {
+        let is_sum = |node: &CalcNode| matches!(node, CalcNode::Sum(..) | CalcNode::Difference(..));
+        let is_product = |node: &CalcNode| matches!(node, CalcNode::Product(..) | CalcNode::Quotient(..));
+        let operand = |node: &CalcNode, parenthesize: bool| if parenthesize {
+            format!("({})", self.serialize_calc(node))
+        } else {
+            self.serialize_calc(node)
+        };
+        let functions = |name: &str, nodes: &[&CalcNode]| {
+            let nodes: Vec<String> = nodes.iter().map(|node| self.serialize_calc(node)).collect();
+            format!("{}({})", name, nodes.join(self.comma()))
+        };
+        match node {
+            CalcNode::Number(n) => self.serialize_number(*n),
+            CalcNode::Length(f, unit) => format!("{}{}", self.serialize_number(*f), unit_name(*unit)),
+            CalcNode::Percentage(p) => format!("{}%", self.serialize_number(*p)),
+            CalcNode::Sum(a, b) => format!("{} + {}", operand(a, false), operand(b, is_sum(b))),
+            CalcNode::Difference(a, b) => format!("{} - {}", operand(a, false), operand(b, is_sum(b))),
+            CalcNode::Product(a, b) => format!("{}{}{}", operand(a, is_sum(a)), if self.minify {"*"} else {" * "}, operand(b, is_sum(b) || is_product(b))),
+            CalcNode::Quotient(a, b) => format!("{}{}{}", operand(a, is_sum(a)), if self.minify {"/"} else {" / "}, operand(b, is_sum(b) || is_product(b))),
+            CalcNode::Min(nodes) => functions("min", &nodes.iter().collect::<Vec<_>>()),
+            CalcNode::Max(nodes) => functions("max", &nodes.iter().collect::<Vec<_>>()),
+            CalcNode::Clamp(min, value, max) => functions("clamp", &[min, value, max])
+        }
+    }
+
+    fn serialize_media_list(&self, media: &[MediaQuery]) -> String {
+        let queries: Vec<String> = media.iter().map(|query| self.serialize_media_query(query)).collect();
+        queries.join(self.comma())
+    }
+
+    fn serialize_media_query(&self, query: &MediaQuery) -> String {
+        let mut parts = Vec::new();
+        match query.qualifier {
+            Some(MediaQualifier::Only) => parts.push("only".to_string()),
+            Some(MediaQualifier::Not) => parts.push("not".to_string()),
+            None => {}
+        }
+        if let Some(ref media_type) = query.media_type {
+            parts.push(serialize_identifier(media_type));
+        }
+        if let Some(ref condition) = query.condition {
+            // NOTE: メディアタイプの後ろにorの条件を書く時は括弧が必要
+            if query.media_type.is_some() {
+                parts.push("and".to_string());
+                parts.push(match condition {
+                    MediaCondition::Or(_) => self.serialize_condition_in_parens(condition),
+                    condition => self.serialize_condition(condition)
+                });
+            } else {
+                parts.push(self.serialize_condition(condition));
+            }
+        }
+        parts.join(" ")
+    }
+
+    fn serialize_condition(&self, condition: &MediaCondition) -> String {
+        let join = |conditions: &[MediaCondition], operator: &str| {
+            let conditions: Vec<String> = conditions.iter().map(|c| self.serialize_condition_in_parens(c)).collect();
+            conditions.join(operator)
+        };
+        match condition {
+            MediaCondition::Not(condition) => format!("not {}", self.serialize_condition_in_parens(condition)),
+            MediaCondition::And(conditions) => join(conditions, " and "),
+            MediaCondition::Or(conditions) => join(conditions, " or "),
+            condition => self.serialize_condition_in_parens(condition)
+        }
+    }
+
+    fn serialize_condition_in_parens(&self, condition: &MediaCondition) -> String {
+        match condition {
+            MediaCondition::Feature(feature) => format!("({})", self.serialize_media_feature(feature)),
+            MediaCondition::Unknown(tokens) => format!("({})", serialize_tokens(tokens)),
+            condition => format!("({})", self.serialize_condition(condition))
+        }
+    }
+
+    fn serialize_supports_condition(&self, condition: &SupportsCondition) -> String {
+        let join = |conditions: &[SupportsCondition], operator: &str| {
+            let conditions: Vec<String> = conditions.iter().map(|c| self.serialize_supports_condition_in_parens(c)).collect();
+            conditions.join(operator)
+        };
+        match condition {
+            SupportsCondition::Not(condition) => format!("not {}", self.serialize_supports_condition_in_parens(condition)),
+            SupportsCondition::And(conditions) => join(conditions, " and "),
+            SupportsCondition::Or(conditions) => join(conditions, " or "),
+            condition => self.serialize_supports_condition_in_parens(condition)
+        }
+    }
+
+    fn serialize_supports_condition_in_parens(&self, condition: &SupportsCondition) -> String {
+        match condition {
+            SupportsCondition::Declaration {name, value} => {
+                format!("({}:{}{})", serialize_identifier(name), if self.minify {""} else {" "}, serialize_tokens(value))
+            },
+            SupportsCondition::Selector(tokens) => format!("selector({})", serialize_tokens(tokens)),
+            SupportsCondition::Unknown(tokens) => serialize_tokens(tokens),
+            condition => format!("({})", self.serialize_supports_condition(condition))
+        }
+    }
+
+    fn serialize_media_feature(&self, feature: &MediaFeature) -> String {
+        let space = if self.minify {""} else {" "};
+        match feature {
+            MediaFeature::Plain(name, None) => serialize_identifier(name),
+            MediaFeature::Plain(name, Some(value)) => format!("{}:{}{}", serialize_identifier(name), space, self.serialize_media_value(value)),
+            MediaFeature::Range {name, start, end} => {
+                let mut feature = String::new();
+                if let Some((value, comparison)) = start {
+                    feature.push_str(&format!("{}{}{}{}", self.serialize_media_value(value), space, comparison_symbol(*comparison), space));
+                }
+                feature.push_str(&serialize_identifier(name));
+                if let Some((comparison, value)) = end {
+                    feature.push_str(&format!("{}{}{}{}", space, comparison_symbol(*comparison), space, self.serialize_media_value(value)));
+                }
+                feature
+            }
+        }
+    }
+
+    fn serialize_media_value(&self, value: &MediaValue) -> String {
+        match value {
+            MediaValue::Length(f, unit) => self.serialize_length(*f, *unit),
+            MediaValue::Number(n) => self.serialize_number(*n),
+            MediaValue::Ident(name) => serialize_identifier(name),
+            // NOTE: dppxに換算した値を書かれた単位に戻す
+            MediaValue::Resolution(dppx, unit) => {
+                let n = match &*unit.to_ascii_lowercase() {
+                    "dpi" => dppx * 96.0,
+                    "dpcm" => dppx * 96.0 / 2.54,
+                    _ => *dppx
+                };
+                format!("{}{}", self.serialize_number(n), unit)
+            },
+            MediaValue::Ratio(width, height) => format!("{}{}{}", self.serialize_number(*width), if self.minify {"/"} else {" / "}, self.serialize_number(*height))
+        }
+    }
+
+    fn comma(&self) -> &'static str {
+        if self.minify {","} else {", "}
+    }
+}
+
+fn serialize_selector(selector: &Selector) -> String {
+    match selector {
+        Selector::Simple(simple) => serialize_simple_selector(simple),
+        Selector::Complex(compounds, subject) => {
+            let mut serialized = String::new();
+            for (compound, combinator) in compounds {
+                serialized.push_str(&serialize_simple_selector(compound));
+                serialized.push_str(match combinator {
+                    Combinator::Descendant => " ",
+                    Combinator::Child => " > ",
+                    Combinator::NextSibling => " + ",
+                    Combinator::SubsequentSibling => " ~ "
+                });
+            }
+            serialized.push_str(&serialize_simple_selector(subject));
+            serialized
+        }
+    }
+}
+
+fn serialize_simple_selector(selector: &SimpleSelector) -> String {
+    let mut serialized = selector.tag_name.as_ref().map_or(String::new(), |tag_name| serialize_identifier(tag_name));
+    if let Some(ref id) = selector.id {
+        serialized.push_str(&format!("#{}", serialize_identifier(id)));
+    }
+    for class in &selector.class {
+        serialized.push_str(&format!(".{}", serialize_identifier(class)));
+    }
+    for attribute in &selector.attributes {
+        serialized.push_str(&format!("[{}", serialize_identifier(&attribute.name)));
+        if let Some((matcher, ref value)) = attribute.matcher {
+            let operator = match matcher {
+                AttributeMatcher::Equals => "=",
+                AttributeMatcher::Includes => "~=",
+                AttributeMatcher::DashMatch => "|=",
+                AttributeMatcher::Prefix => "^=",
+                AttributeMatcher::Suffix => "$=",
+                AttributeMatcher::Substring => "*="
+            };
+            serialized.push_str(&format!("{}{}", operator, serialize_string(value)));
+            if attribute.case_insensitive {
+                serialized.push_str(" i");
+            }
+        }
+        serialized.push(']');
+    }
+    for pseudo_class in &selector.pseudo_classes {
+        let (name, selectors) = match pseudo_class {
+            PseudoClass::Is(selectors) => ("is", selectors),
+            PseudoClass::Where(selectors) => ("where", selectors),
+            PseudoClass::Not(selectors) => ("not", selectors),
+            PseudoClass::UserAction(name) => {
+                serialized.push_str(&format!(":{}", serialize_identifier(name)));
+                continue;
+            }
+        };
+        let selectors: Vec<String> = selectors.iter().map(serialize_selector).collect();
+        serialized.push_str(&format!(":{}({})", name, selectors.join(", ")));
+    }
+    if serialized.is_empty() {
+        serialized.push('*');
+    }
+    serialized
+}
+
+fn unit_name(unit: Unit) -> &'static str {
+    match unit {
+        Unit::Px => "px",
+        Unit::Pt => "pt",
+        Unit::Pc => "pc",
+        Unit::In => "in",
+        Unit::Cm => "cm",
+        Unit::Mm => "mm",
+        Unit::Q => "q",
+        Unit::Em => "em",
+        Unit::Rem => "rem",
+        Unit::Ex => "ex",
+        Unit::Ch => "ch",
+        Unit::Vw => "vw",
+        Unit::Vh => "vh",
+        Unit::Vmin => "vmin",
+        Unit::Vmax => "vmax"
+    }
+}
+
+fn comparison_symbol(comparison: Comparison) -> &'static str {
+    match comparison {
+        Comparison::Lt => "<",
+        Comparison::Le => "<=",
+        Comparison::Gt => ">",
+        Comparison::Ge => ">=",
+        Comparison::Eq => "="
+    }
+}
+
+fn serialize_layer_name(name: &[String]) -> String {
+    let names: Vec<String> = name.iter().map(|name| serialize_identifier(name)).collect();
+    names.join(".")
+}
+
+// NOTE: 識別子として読めない文字や、先頭に来ると数値と間違われる数字をエスケープする
+// https://www.w3.org/TR/cssom-1/#serialize-an-identifier
+fn serialize_identifier(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    let mut serialized = String::new();
+    for (i, &c) in chars.iter().enumerate() {
+        let starts_like_number = c.is_ascii_digit() && (i == 0 || (i == 1 && chars[0] == '-'));
+        if c == '\0' {
+            serialized.push('\u{FFFD}');
+        } else if c.is_control() || starts_like_number {
+            serialized.push_str(&format!("\\{:x} ", c as u32));
+        } else if i == 0 && c == '-' && chars.len() == 1 {
+            serialized.push_str("\\-");
+        } else if c.is_ascii_alphanumeric() || c == '-' || c == '_' || !c.is_ascii() {
+            serialized.push(c);
+        } else {
+            serialized.push('\\');
+            serialized.push(c);
+        }
+    }
+    serialized
+}
+
+// NOTE: #123のようにハッシュの後ろは数字から始まってもよいので、識別子に使えない文字だけをエスケープする
+fn serialize_name(name: &str) -> String {
+    name.chars().fold(String::new(), |mut serialized, c| {
+        if c.is_ascii_alphanumeric() || c == '-' || c == '_' || !c.is_ascii() {
+            serialized.push(c);
+        } else {
+            serialized.push_str(&serialize_identifier(&c.to_string()));
+        }
+        serialized
+    })
+}
+
+fn serialize_string(string: &str) -> String {
+    let mut serialized = String::from("\"");
+    for c in string.chars() {
+        match c {
+            '\0' => serialized.push('\u{FFFD}'),
+            '"' | '\\' => {
+                serialized.push('\\');
+                serialized.push(c);
+            },
+            c if c.is_control() => serialized.push_str(&format!("\\{:x} ", c as u32)),
+            c => serialized.push(c)
+        }
+    }
+    serialized.push('"');
+    serialized
+}
+
+// NOTE: カスタムプロパティの値のようなトークン列を書き戻す
+// コメントを取り除いたせいで隣のトークンとくっついてしまう場合は間に空のコメントを挟む
+fn serialize_tokens(tokens: &[Token]) -> String {
+    let mut serialized = String::new();
+    for (i, token) in tokens.iter().enumerate() {
+        if i > 0 && needs_separator(&tokens[i - 1], token) {
+            serialized.push_str("/**/");
+        }
+        serialized.push_str(&serialize_token(token));
+    }
+    for end in unclosed_block_ends(tokens) {
+        serialized.push_str(&serialize_token(&end));
+    }
+    serialized
+}
+
+// NOTE: 不正な文字列やURLは元の形に戻せないので、空の文字列やURLとして書く
+fn serialize_token(token: &Token) -> String {
+    match token {
+        Token::Ident(name) => serialize_identifier(name),
+        Token::Function(name) => format!("{}(", serialize_identifier(name)),
+        Token::AtKeyword(name) => format!("@{}", serialize_identifier(name)),
+        Token::Hash(name, _) => format!("#{}", serialize_name(name)),
+        Token::String(string) => serialize_string(string),
+        Token::BadString => "\"\"".to_string(),
+        // NOTE: url("...")と書くと関数と文字列のトークンになってしまうので、クォートせずにエスケープする
+        Token::Url(url) => url.chars().fold("url(".to_string(), |mut serialized, c| {
+            if c.is_whitespace() || c.is_control() || "\"'()\\".contains(c) {
+                serialized.push_str(&format!("\\{:x} ", c as u32));
+            } else {
+                serialized.push(c);
+            }
+            serialized
+        }) + ")",
+        Token::BadUrl => "url()".to_string(),
+        Token::Delim(c) => c.to_string(),
+        Token::Number(n) => n.to_string(),
+        Token::Percentage(p) => format!("{}%", p),
+        Token::Dimension(n, unit) => format!("{}{}", n, serialize_identifier(unit)),
+        Token::Whitespace => " ".to_string(),
+        Token::Cdo => "<!--".to_string(),
+        Token::Cdc => "-->".to_string(),
+        Token::Colon => ":".to_string(),
+        Token::Semicolon => ";".to_string(),
+        Token::Comma => ",".to_string(),
+        Token::OpenSquare => "[".to_string(),
+        Token::CloseSquare => "]".to_string(),
+        Token::OpenParen => "(".to_string(),
+        Token::CloseParen => ")".to_string(),
+        Token::OpenCurly => "{".to_string(),
+        Token::CloseCurly => "}".to_string()
+    }
+}
+
+// NOTE: https://www.w3.org/TR/css-syntax-3/#serialization の表のうち、ここで使うトークンの組み合わせ
+fn needs_separator(previous: &Token, next: &Token) -> bool {
+    let ident_like = matches!(next, Token::Ident(_) | Token::Function(_) | Token::Url(_) | Token::BadUrl);
+    let number_like = matches!(next, Token::Number(_) | Token::Percentage(_) | Token::Dimension(..));
+    match previous {
+        Token::Ident(_) => ident_like || number_like || matches!(next, Token::Delim('-') | Token::Cdc | Token::OpenParen),
+        Token::AtKeyword(_) | Token::Hash(..) | Token::Dimension(..) | Token::Delim('#') | Token::Delim('-') =>
+            ident_like || number_like || matches!(next, Token::Delim('-') | Token::Cdc),
+        Token::Number(_) => ident_like || number_like || matches!(next, Token::Delim('%')),
+        Token::Delim('@') => ident_like || matches!(next, Token::Delim('-')),
+        Token::Delim('.') | Token::Delim('+') => number_like,
+        Token::Delim('/') => matches!(next, Token::Delim('*')),
+        _ => false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{serialize, minify};
+    use crate::css::{parse, Stylesheet, CssRule, Rule, Declaration, Value, Separator, Unit, Color, CalcNode};
+    use crate::css::tokenizer::tokenize;
+
+    fn assert_round_trip(stylesheet: &Stylesheet) {
+        assert_eq!(&parse(serialize(stylesheet)), stylesheet, "{}", serialize(stylesheet));
+        assert_eq!(&parse(minify(stylesheet)), stylesheet, "{}", minify(stylesheet));
+    }
+
+    #[test]
+    fn pretty_print_and_minify() {
+        let stylesheet = parse("@import 'base.css' screen; div,#main.a.b{color:#FFFFFF;margin:0.5em 0 !important} @media (min-width: 600px) {* {width: 50%}}".to_string());
+        assert_eq!(serialize(&stylesheet), "\
+@import url(\"base.css\") screen;
+
+div, #main.a.b {
+    color: #ffffff;
+    margin: 0.5em 0 !important;
+}
+
+@media (min-width: 600px) {
+    * {
+        width: 50%;
+    }
+}
+");
+        assert_eq!(minify(&stylesheet), "@import url(base.css) screen;div,#main.a.b{color:#fff;margin:.5em 0!important}@media (min-width:600px){*{width:50%}}");
+    }
+
+    #[test]
+    fn round_trip_selectors_and_at_rules() {
+        assert_round_trip(&parse("
+            * {width: 1px;}
+            div {width: 1px;}
+            #id {width: 1px;}
+            .a.b {width: 1px;}
+            p#\\31 23.x\\.y, .-- {}
+            [hidden], a[href^='https:' i][lang|=en], p[class~=\"a b\"] {}
+            div > .a + p ~ #b  c, :is(a, .b > c):HOVER:not(#c) :where(p) {}
+            @import url('a b.css');
+            @import 'base.css' layer;
+            @import 'theme.css' layer(theme.dark) only screen and (max-width: 600px), print;
+            @media {div {width: 1px;}}
+            @media not print, screen and ((color) or (monochrome)) {
+                @media (400px < width <= 700px) and (resolution >= 192dpi) and (aspect-ratio: 16 / 9) and (orientation: portrait) {
+                    div {width: 1px;}
+                }
+            }
+            @media not (hover: hover), (unknown syntax), (prefers-color-scheme) {div {width: 1px;}}
+            @keyframes slide {from {width: 0;} 25%, 75.5% {width: 10px; color: red;} to {}}
+            @keyframes \"none\" {}
+            @media print {@keyframes fade {to {opacity: 0.5;}}}
+            @supports (display: grid) and (not (gap: 1px)) {@supports selector(div#main) or (unknown syntax) or font-tech(color-COLRv1) {div {width: 1px;}}}
+            @supports not ((--x: { a }) and (border: 1px  solid red)) {}
+            @layer reset, theme.dark;
+            @layer base {@layer {div {width: 1px;}} @layer \\31 a.b {}}
+            @container card (400px < inline-size) and (orientation: landscape) {@container not (width < 10em) {div {width: 1px;}}}
+        ".to_string()));
+        // NOTE: @importの位置が不正なのでパースの段階で取り除かれている
+        assert_eq!(parse("div {} @import 'a.css';".to_string()).rules.len(), 1);
+    }
+
+    #[test]
+    fn round_trip_values() {
+        assert_round_trip(&parse("
+            div {
+                display: block;
+                width: 10.5px;
+                height: 2.5em;
+                margin: 0 -0.5rem 1vw 2Q;
+                padding: 12.5%;
+                line-height: 1.5;
+                opacity: 0.25;
+                color: rgba(255, 0, 51, 0.5);
+                background-color: #123456;
+                border-color: transparent;
+                font-family: \"Noto \\\"Sans\\\" JP\", sans-serif;
+                font: italic bold 12px / 30px Georgia, serif;
+                background-image: url(a.png), url(\"b c.png\");
+                content: \"\\2192\\A\";
+                transform: translate(10px, 0) scale(1.5) none() rotate(0.5turn);
+                animation-duration: 1.5s, 250ms;
+                width: calc(100% - (16px + 2em) * 2 / (1 + 1));
+                width: min(10px, max(5vw, 1em), clamp(1px, 50%, 3px));
+                --empty:;
+                --tokens: { a: [1 ] } 1.5e3 -x url(x.png) url(a\\(b) #123 @foo +.5 a/**/b 1/**/2 'str';
+                width: var(--a, 1px) !important;
+            }
+        ".to_string()));
+    }
+
+    #[test]
+    fn round_trip_values_not_produced_by_parser() {
+        let keyword = |k: &str| Value::Keyword(k.to_string());
+        let stylesheet = Stylesheet {rules: vec![CssRule::Style(Rule {selectors: parse("div {}".to_string()).rules.iter().flat_map(|rule| match rule {
+            CssRule::Style(rule) => rule.selectors.clone(),
+            _ => vec![]
+        }).collect(), declarations: vec![
+            Declaration {name: "font".to_string(), value: Value::List(vec![keyword("italic"), Value::List(vec![Value::Length(1.0, Unit::Px), Value::Number(2.0)], Separator::Slash), keyword("serif")], Separator::Space), important: false},
+            Declaration {name: "color".to_string(), value: Value::ColorValue(Color {r: 0x11, g: 0x22, b: 0x33, a: 0x44}), important: true},
+            Declaration {name: "width".to_string(), value: Value::Calc(CalcNode::Length(4.0, Unit::Px)), important: false},
+            Declaration {name: "--d".to_string(), value: Value::Tokens(tokenize("a b")), important: false}
+        ]})]};
+        assert_round_trip(&stylesheet);
+        assert!(minify(&stylesheet).contains("color:#1234!important"));
+    }
+
+    #[test]
+    fn round_trip_unclosed_blocks() {
+        let stylesheet = parse("div {--x: not(1e".to_string());
+        assert_eq!(serialize(&stylesheet), "div {\n    --x: not(1e);\n}\n");
+        assert_round_trip(&stylesheet);
+        assert_round_trip(&parse("div {--x: [a {b (c); color: red".to_string()));
+
+        // NOTE: 閉じていないトークン列を書き戻しても、後に続く宣言を飲み込まない
+        let mut stylesheet = parse("div {--x: 1; color: red}".to_string());
+        if let CssRule::Style(ref mut rule) = stylesheet.rules[0] {
+            rule.declarations[0].value = Value::Tokens(tokenize("a(b [c"));
+        }
+        assert_eq!(minify(&stylesheet), "div{--x:a(b [c]);color:red}");
+        assert_eq!(&parse(minify(&stylesheet)), &parse("div {--x: a(b [c]); color: red}".to_string()));
+    }
+}
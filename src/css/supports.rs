@@ -0,0 +1,218 @@
+// NOTE: @supportsの条件のパースと評価
+// https://www.w3.org/TR/css-conditional-4/#at-supports
+
+use super::{Parser, trim_whitespace};
+use super::tokenizer::Token;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SupportsCondition {
+    Not(Box<SupportsCondition>),
+    And(Vec<SupportsCondition>),
+    Or(Vec<SupportsCondition>),
+    // NOTE: (display: grid)。値はトークンのまま持っておき、評価する時に読む
+    Declaration {name: String, value: Vec<Token>},
+    // NOTE: selector(a.b) の括弧の中のトークン
+    Selector(Vec<Token>),
+    // NOTE: 知らない構文の ( ... ) や関数。常に偽になる。書き戻せるように括弧や関数も含めたトークンを持っておく
+    Unknown(Vec<Token>)
+}
+
+// NOTE: 構文として不正な条件はNoneを返し、@supportsのルールごと無視する
+pub fn parse_supports_condition(tokens: &[Token]) -> Option<SupportsCondition> {
+    let mut parser = SupportsParser {tokens: trim_whitespace(tokens), pos: 0};
+    let condition = parser.parse_condition()?;
+    if parser.eof() {Some(condition)} else {None}
+}
+
+pub fn evaluate_supports_condition(condition: &SupportsCondition) -> bool {
+    match condition {
+        SupportsCondition::Not(condition) => !evaluate_supports_condition(condition),
+        SupportsCondition::And(conditions) => conditions.iter().all(evaluate_supports_condition),
+        SupportsCondition::Or(conditions) => conditions.iter().any(evaluate_supports_condition),
+        SupportsCondition::Declaration {name, value} => supports_declaration(name, value),
+        SupportsCondition::Selector(tokens) => supports_selector(tokens),
+        SupportsCondition::Unknown(_) => false
+    }
+}
+
+// NOTE: 宣言として読めて、プロパティと値の組み合わせをvioletが解釈できるか
+// 解釈できない宣言はパースの段階で捨てられるので、読めるかどうかだけを見ればよい
+fn supports_declaration(name: &str, value: &[Token]) -> bool {
+    let mut tokens = vec![Token::Ident(name.to_string()), Token::Colon];
+    tokens.extend(value.iter().cloned());
+    let mut parser = Parser {pos: 0, tokens, positions: Vec::new(), diagnostics: Vec::new()};
+    parser.parse_declaration().is_ok()
+}
+
+// NOTE: セレクタ一つとして読めるか
+fn supports_selector(tokens: &[Token]) -> bool {
+    let mut parser = Parser {pos: 0, tokens: tokens.to_vec(), positions: Vec::new(), diagnostics: Vec::new()};
+    matches!(parser.parse_selectors(None, false), Ok(selectors) if selectors.len() == 1)
+}
+
+struct SupportsParser<'a> {
+    tokens: &'a [Token],
+    pos: usize
+}
+
+impl<'a> SupportsParser<'a> {
+
+    // NOTE: not <in-parens> | <in-parens> [and <in-parens>]* | <in-parens> [or <in-parens>]*
+    fn parse_condition(&mut self) -> Option<SupportsCondition> {
+        if let Some(Token::Ident(name)) = self.next_token() {
+            if name.eq_ignore_ascii_case("not") {
+                self.consume_token();
+                self.consume_whitespace();
+                let condition = SupportsCondition::Not(Box::new(self.parse_in_parens()?));
+                self.consume_whitespace();
+                return Some(condition);
+            }
+        }
+        let first = self.parse_in_parens()?;
+        self.consume_whitespace();
+        let operator = match self.next_token() {
+            Some(Token::Ident(name)) if name.eq_ignore_ascii_case("and") => "and",
+            Some(Token::Ident(name)) if name.eq_ignore_ascii_case("or") => "or",
+            _ => return Some(first)
+        };
+        let mut conditions = vec![first];
+        while let Some(Token::Ident(name)) = self.next_token() {
+            if !name.eq_ignore_ascii_case(operator) {
+                return None;
+            }
+            self.consume_token();
+            // NOTE: and や or の後には空白が必要
+            if self.next_token() != Some(&Token::Whitespace) {
+                return None;
+            }
+            self.consume_whitespace();
+            conditions.push(self.parse_in_parens()?);
+            self.consume_whitespace();
+        }
+        Some(if operator == "and" {SupportsCondition::And(conditions)} else {SupportsCondition::Or(conditions)})
+    }
+
+    fn parse_in_parens(&mut self) -> Option<SupportsCondition> {
+        let start = self.pos;
+        let function = match self.consume_token()? {
+            Token::OpenParen => None,
+            Token::Function(name) => Some(name),
+            _ => return None
+        };
+        let inner_start = self.pos;
+        let mut depth = 0;
+        loop {
+            match self.consume_token()? {
+                Token::OpenParen | Token::Function(_) => depth += 1,
+                Token::CloseParen if depth == 0 => break,
+                Token::CloseParen => depth -= 1,
+                _ => {}
+            }
+        }
+        let inner = trim_whitespace(&self.tokens[inner_start..self.pos - 1]);
+        let unknown = || SupportsCondition::Unknown(self.tokens[start..self.pos].to_vec());
+        if let Some(name) = function {
+            let condition = if name.eq_ignore_ascii_case("selector") {SupportsCondition::Selector(inner.to_vec())} else {unknown()};
+            return Some(condition);
+        }
+
+        let mut inner_parser = SupportsParser {tokens: inner, pos: 0};
+        let nested = match inner.first() {
+            Some(Token::OpenParen) | Some(Token::Function(_)) => true,
+            Some(Token::Ident(name)) => name.eq_ignore_ascii_case("not") && inner.get(1) == Some(&Token::Whitespace),
+            _ => false
+        };
+        if nested {
+            return match inner_parser.parse_condition() {
+                Some(condition) if inner_parser.eof() => Some(condition),
+                _ => Some(unknown())
+            };
+        }
+        // NOTE: 名前の後ろには空白を挟んで : が来る
+        let condition = match inner {
+            [Token::Ident(name), rest @ ..] => match trim_whitespace(rest) {
+                [Token::Colon, value @ ..] => SupportsCondition::Declaration {name: name.clone(), value: trim_whitespace(value).to_vec()},
+                _ => unknown()
+            },
+            _ => unknown()
+        };
+        Some(condition)
+    }
+
+    fn next_token(&self) -> Option<&'a Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn consume_token(&mut self) -> Option<&'a Token> {
+        let token = self.tokens.get(self.pos);
+        self.pos += 1;
+        token
+    }
+
+    fn consume_whitespace(&mut self) {
+        while self.next_token() == Some(&Token::Whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    fn eof(&self) -> bool {
+        self.pos >= self.tokens.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_supports_condition, evaluate_supports_condition, SupportsCondition};
+    use crate::css::tokenizer::tokenize;
+
+    fn supports(condition: &str) -> Option<bool> {
+        parse_supports_condition(&tokenize(condition)).map(|condition| evaluate_supports_condition(&condition))
+    }
+
+    #[test]
+    fn parse_conditions() {
+        let condition = parse_supports_condition(&tokenize("(display: grid) and (not (gap: 1px))")).unwrap();
+        match condition {
+            SupportsCondition::And(ref conditions) => {
+                assert!(matches!(conditions[0], SupportsCondition::Declaration {ref name, ..} if name == "display"));
+                assert!(matches!(conditions[1], SupportsCondition::Not(_)));
+            },
+            condition => panic!("Expected and condition but found {:?}", condition)
+        }
+        assert!(matches!(parse_supports_condition(&tokenize("selector(a.b)")), Some(SupportsCondition::Selector(_))));
+        assert!(matches!(parse_supports_condition(&tokenize("(unknown syntax)")), Some(SupportsCondition::Unknown(_))));
+        assert!(matches!(parse_supports_condition(&tokenize("font-tech(color-COLRv1)")), Some(SupportsCondition::Unknown(_))));
+
+        // NOTE: and と or を混ぜたり、括弧で囲まずに書いたりはできない
+        assert_eq!(parse_supports_condition(&tokenize("(color: red) and (width: 1px) or (height: 1px)")), None);
+        assert_eq!(parse_supports_condition(&tokenize("color: red")), None);
+        assert_eq!(parse_supports_condition(&tokenize("not (color: red) and (width: 1px)")), None);
+        assert_eq!(parse_supports_condition(&tokenize("")), None);
+    }
+
+    #[test]
+    fn evaluate_conditions() {
+        assert_eq!(supports("(display: block)"), Some(true));
+        assert_eq!(supports("(display: grid)"), Some(false));
+        assert_eq!(supports("(DISPLAY: none) and (not (grid-template-columns: 1fr))"), Some(true));
+        assert_eq!(supports("(grid-template-columns: 1fr) or (margin: 0 auto)"), Some(true));
+        assert_eq!(supports("(display: flex) and (gap: 1px 2%)"), Some(true));
+        assert_eq!(supports("((width: calc(100% - 8px)) and (color: rgb(0 0 0 / 50%)))"), Some(true));
+        assert_eq!(supports("(width: 10 px)"), Some(false));
+        assert_eq!(supports("(width: var(--w))"), Some(true));
+        assert_eq!(supports("(--anything: { } [ ])"), Some(true));
+        assert_eq!(supports("(color: inherit)"), Some(true));
+        assert_eq!(supports("(border: 1px solid red)"), Some(true));
+        assert_eq!(supports("(border: 1px 2px)"), Some(false));
+        assert_eq!(supports("(font: bold 12px/1.5 \"Helvetica Neue\", serif)"), Some(true));
+        assert_eq!(supports("(animation-duration: 1s, 200ms)"), Some(true));
+        assert_eq!(supports("(unknown syntax)"), Some(false));
+        assert_eq!(supports("not (unknown syntax)"), Some(true));
+
+        assert_eq!(supports("selector(div#main.a)"), Some(true));
+        assert_eq!(supports("selector(a > b:hover)"), Some(true));
+        assert_eq!(supports("selector(a::before)"), Some(false));
+        assert_eq!(supports("selector(& a)"), Some(false));
+        assert_eq!(supports("selector(a, b)"), Some(false));
+    }
+}
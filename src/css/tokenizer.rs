@@ -0,0 +1,562 @@
+// NOTE: CSS Syntax Module Level 3 の Tokenization に沿ったトークナイザ
+// https://www.w3.org/TR/css-syntax-3/#tokenization
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Token {
+    Ident(String),
+    // NOTE: rgb( のように名前の直後に ( が来るもの。値は ( を含まない名前
+    Function(String),
+    AtKeyword(String),
+    Hash(String, HashType),
+    String(String),
+    BadString,
+    Url(String),
+    BadUrl,
+    Delim(char),
+    Number(f32),
+    Percentage(f32),
+    Dimension(f32, String),
+    Whitespace,
+    Cdo,
+    Cdc,
+    Colon,
+    Semicolon,
+    Comma,
+    OpenSquare,
+    CloseSquare,
+    OpenParen,
+    CloseParen,
+    OpenCurly,
+    CloseCurly
+}
+
+// NOTE: #fff のような値と #main のようにidとして使える値を区別する
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HashType {
+    Id,
+    Unrestricted
+}
+
+// NOTE: ソース中の位置。行と列はどちらも1始まり
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SourcePosition {
+    pub line: usize,
+    pub column: usize
+}
+
+// NOTE: 位置が不要なテスト用
+#[cfg(test)]
+pub fn tokenize(source: &str) -> Vec<Token> {
+    tokenize_with_positions(source).0
+}
+
+// NOTE: 各トークンが始まる位置も一緒に返す。警告を出す時に使う
+pub fn tokenize_with_positions(source: &str) -> (Vec<Token>, Vec<SourcePosition>) {
+    let mut tokenizer = Tokenizer {pos: 0, input: preprocess(source)};
+    let mut tokens = Vec::new();
+    let mut positions = Vec::new();
+    let mut position = SourcePosition {line: 1, column: 1};
+    let mut counted = 0;
+    loop {
+        tokenizer.consume_comments();
+        for c in &tokenizer.input[counted..tokenizer.pos] {
+            if *c == '\n' {
+                position = SourcePosition {line: position.line + 1, column: 1};
+            } else {
+                position.column += 1;
+            }
+        }
+        counted = tokenizer.pos;
+        match tokenizer.consume_token() {
+            Some(token) => {
+                tokens.push(token);
+                positions.push(position);
+            },
+            None => break
+        }
+    }
+    (tokens, positions)
+}
+
+// NOTE: 改行コードをLFに揃え、NULLを置換文字にする
+fn preprocess(source: &str) -> Vec<char> {
+    source.replace("\r\n", "\n").chars().map(|c| match c {
+        '\r' | '\u{c}' => '\n',
+        '\0' => '\u{fffd}',
+        c => c
+    }).collect()
+}
+
+struct Tokenizer {
+    pos: usize,
+    input: Vec<char>
+}
+
+impl Tokenizer {
+
+    fn consume_token(&mut self) -> Option<Token> {
+        self.consume_comments();
+        let c = self.consume_char()?;
+        let token = match c {
+            c if is_whitespace(c) => {
+                while self.peek(0).is_some_and(is_whitespace) {
+                    self.pos += 1;
+                }
+                Token::Whitespace
+            },
+            '"' | '\'' => self.consume_string_token(c),
+            '#' => {
+                if self.peek(0).is_some_and(is_name_char) || self.is_valid_escape(0) {
+                    let hash_type = if self.would_start_identifier(0) {HashType::Id} else {HashType::Unrestricted};
+                    Token::Hash(self.consume_name(), hash_type)
+                } else {
+                    Token::Delim(c)
+                }
+            },
+            '(' => Token::OpenParen,
+            ')' => Token::CloseParen,
+            '+' | '.' => {
+                self.pos -= 1;
+                if self.would_start_number(0) {
+                    self.consume_numeric_token()
+                } else {
+                    self.pos += 1;
+                    Token::Delim(c)
+                }
+            },
+            ',' => Token::Comma,
+            '-' => {
+                self.pos -= 1;
+                if self.would_start_number(0) {
+                    self.consume_numeric_token()
+                } else if self.peek(1) == Some('-') && self.peek(2) == Some('>') {
+                    self.pos += 3;
+                    Token::Cdc
+                } else if self.would_start_identifier(0) {
+                    self.consume_ident_like_token()
+                } else {
+                    self.pos += 1;
+                    Token::Delim(c)
+                }
+            },
+            ':' => Token::Colon,
+            ';' => Token::Semicolon,
+            '<' => {
+                if self.peek(0) == Some('!') && self.peek(1) == Some('-') && self.peek(2) == Some('-') {
+                    self.pos += 3;
+                    Token::Cdo
+                } else {
+                    Token::Delim(c)
+                }
+            },
+            '@' => {
+                if self.would_start_identifier(0) {
+                    Token::AtKeyword(self.consume_name())
+                } else {
+                    Token::Delim(c)
+                }
+            },
+            '[' => Token::OpenSquare,
+            '\\' => {
+                self.pos -= 1;
+                if self.is_valid_escape(0) {
+                    self.consume_ident_like_token()
+                } else {
+                    // NOTE: 不正なエスケープ。パースエラーだがそのままdelimとして扱う
+                    self.pos += 1;
+                    Token::Delim(c)
+                }
+            },
+            ']' => Token::CloseSquare,
+            '{' => Token::OpenCurly,
+            '}' => Token::CloseCurly,
+            '0'..='9' => {
+                self.pos -= 1;
+                self.consume_numeric_token()
+            },
+            c if is_name_start_char(c) => {
+                self.pos -= 1;
+                self.consume_ident_like_token()
+            },
+            c => Token::Delim(c)
+        };
+        Some(token)
+    }
+
+    // NOTE: /* ... */ を読み飛ばす。閉じられていないコメントはEOFまで読み飛ばす
+    fn consume_comments(&mut self) {
+        while self.peek(0) == Some('/') && self.peek(1) == Some('*') {
+            self.pos += 2;
+            loop {
+                match self.consume_char() {
+                    Some('*') if self.peek(0) == Some('/') => {
+                        self.pos += 1;
+                        break;
+                    },
+                    Some(_) => {},
+                    None => return
+                }
+            }
+        }
+    }
+
+    fn consume_numeric_token(&mut self) -> Token {
+        let number = self.consume_number();
+        if self.would_start_identifier(0) {
+            Token::Dimension(number, self.consume_name())
+        } else if self.peek(0) == Some('%') {
+            self.pos += 1;
+            Token::Percentage(number)
+        } else {
+            Token::Number(number)
+        }
+    }
+
+    fn consume_ident_like_token(&mut self) -> Token {
+        let name = self.consume_name();
+        if self.peek(0) != Some('(') {
+            return Token::Ident(name);
+        }
+        self.pos += 1;
+        if name.eq_ignore_ascii_case("url") {
+            // NOTE: url("a.png") のようにクォートされている場合は普通の関数として扱う
+            let mut offset = 0;
+            while self.peek(offset).is_some_and(is_whitespace) {
+                offset += 1;
+            }
+            match self.peek(offset) {
+                Some('"') | Some('\'') => Token::Function(name),
+                _ => self.consume_url_token()
+            }
+        } else {
+            Token::Function(name)
+        }
+    }
+
+    fn consume_string_token(&mut self, ending: char) -> Token {
+        let mut value = String::new();
+        loop {
+            match self.consume_char() {
+                Some(c) if c == ending => return Token::String(value),
+                None => return Token::String(value),
+                Some('\n') => {
+                    // NOTE: 文字列中の改行はbad-stringになる。改行自体は次のトークンに残す
+                    self.pos -= 1;
+                    return Token::BadString;
+                },
+                Some('\\') => match self.peek(0) {
+                    None => {},
+                    Some('\n') => self.pos += 1,
+                    Some(_) => value.push(self.consume_escaped_char())
+                },
+                Some(c) => value.push(c)
+            }
+        }
+    }
+
+    fn consume_url_token(&mut self) -> Token {
+        let mut value = String::new();
+        self.consume_whitespace();
+        loop {
+            match self.consume_char() {
+                Some(')') | None => return Token::Url(value),
+                Some(c) if is_whitespace(c) => {
+                    self.consume_whitespace();
+                    return match self.peek(0) {
+                        Some(')') => {
+                            self.pos += 1;
+                            Token::Url(value)
+                        },
+                        // NOTE: 閉じ括弧がないまま入力が終わった場合は、posを進めずにそのままurlトークンにする
+                        None => Token::Url(value),
+                        _ => {
+                            self.consume_bad_url_remnants();
+                            Token::BadUrl
+                        }
+                    };
+                },
+                Some('"') | Some('\'') | Some('(') => {
+                    self.consume_bad_url_remnants();
+                    return Token::BadUrl;
+                },
+                Some(c) if is_non_printable(c) => {
+                    self.consume_bad_url_remnants();
+                    return Token::BadUrl;
+                },
+                Some('\\') => {
+                    self.pos -= 1;
+                    if self.is_valid_escape(0) {
+                        self.pos += 1;
+                        value.push(self.consume_escaped_char());
+                    } else {
+                        self.consume_bad_url_remnants();
+                        return Token::BadUrl;
+                    }
+                },
+                Some(c) => value.push(c)
+            }
+        }
+    }
+
+    fn consume_bad_url_remnants(&mut self) {
+        loop {
+            match self.consume_char() {
+                Some(')') | None => return,
+                Some('\\') if self.is_valid_escape_pair('\\', self.peek(0)) => {
+                    self.consume_escaped_char();
+                },
+                Some(_) => {}
+            }
+        }
+    }
+
+    // NOTE: \ の直後から呼ばれ、エスケープされた一文字を返す
+    fn consume_escaped_char(&mut self) -> char {
+        match self.consume_char() {
+            Some(c) if c.is_ascii_hexdigit() => {
+                let mut hex = c.to_string();
+                while hex.len() < 6 && self.peek(0).is_some_and(|c| c.is_ascii_hexdigit()) {
+                    hex.push(self.input[self.pos]);
+                    self.pos += 1;
+                }
+                if self.peek(0).is_some_and(is_whitespace) {
+                    self.pos += 1;
+                }
+                let code_point = u32::from_str_radix(&hex, 16).unwrap();
+                match std::char::from_u32(code_point) {
+                    Some(c) if code_point != 0 => c,
+                    _ => '\u{fffd}'
+                }
+            },
+            Some(c) => c,
+            None => '\u{fffd}'
+        }
+    }
+
+    fn consume_name(&mut self) -> String {
+        let mut name = String::new();
+        loop {
+            match self.peek(0) {
+                Some(c) if is_name_char(c) => {
+                    name.push(c);
+                    self.pos += 1;
+                },
+                Some('\\') if self.is_valid_escape(0) => {
+                    self.pos += 1;
+                    name.push(self.consume_escaped_char());
+                },
+                _ => return name
+            }
+        }
+    }
+
+    fn consume_number(&mut self) -> f32 {
+        let mut repr = String::new();
+        if let Some(c @ '+') | Some(c @ '-') = self.peek(0) {
+            repr.push(c);
+            self.pos += 1;
+        }
+        self.consume_digits(&mut repr);
+        if self.peek(0) == Some('.') && self.peek(1).is_some_and(|c| c.is_ascii_digit()) {
+            repr.push('.');
+            self.pos += 1;
+            self.consume_digits(&mut repr);
+        }
+        if let Some('e') | Some('E') = self.peek(0) {
+            let has_sign = matches!(self.peek(1), Some('+') | Some('-'));
+            let digit_offset = if has_sign {2} else {1};
+            if self.peek(digit_offset).is_some_and(|c| c.is_ascii_digit()) {
+                repr.push('e');
+                if has_sign {
+                    repr.push(self.input[self.pos + 1]);
+                }
+                self.pos += digit_offset;
+                self.consume_digits(&mut repr);
+            }
+        }
+        repr.parse().unwrap()
+    }
+
+    fn consume_digits(&mut self, repr: &mut String) {
+        while let Some(c) = self.peek(0).filter(|c| c.is_ascii_digit()) {
+            repr.push(c);
+            self.pos += 1;
+        }
+    }
+
+    fn consume_whitespace(&mut self) {
+        while self.peek(0).is_some_and(is_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    fn would_start_identifier(&self, offset: usize) -> bool {
+        match self.peek(offset) {
+            Some('-') => match self.peek(offset + 1) {
+                Some(c) if is_name_start_char(c) || c == '-' => true,
+                _ => self.is_valid_escape(offset + 1)
+            },
+            Some(c) if is_name_start_char(c) => true,
+            Some('\\') => self.is_valid_escape(offset),
+            _ => false
+        }
+    }
+
+    fn would_start_number(&self, offset: usize) -> bool {
+        match self.peek(offset) {
+            Some('+') | Some('-') => match self.peek(offset + 1) {
+                Some(c) if c.is_ascii_digit() => true,
+                Some('.') => self.peek(offset + 2).is_some_and(|c| c.is_ascii_digit()),
+                _ => false
+            },
+            Some('.') => self.peek(offset + 1).is_some_and(|c| c.is_ascii_digit()),
+            Some(c) => c.is_ascii_digit(),
+            None => false
+        }
+    }
+
+    fn is_valid_escape(&self, offset: usize) -> bool {
+        match self.peek(offset) {
+            Some(c) => self.is_valid_escape_pair(c, self.peek(offset + 1)),
+            None => false
+        }
+    }
+
+    fn is_valid_escape_pair(&self, first: char, second: Option<char>) -> bool {
+        first == '\\' && second.is_some_and(|c| c != '\n')
+    }
+
+    fn peek(&self, offset: usize) -> Option<char> {
+        self.input.get(self.pos + offset).copied()
+    }
+
+    fn consume_char(&mut self) -> Option<char> {
+        let c = self.peek(0)?;
+        self.pos += 1;
+        Some(c)
+    }
+}
+
+fn is_whitespace(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\n')
+}
+
+fn is_name_start_char(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_' || !c.is_ascii()
+}
+
+fn is_name_char(c: char) -> bool {
+    is_name_start_char(c) || c.is_ascii_digit() || c == '-'
+}
+
+fn is_non_printable(c: char) -> bool {
+    matches!(c, '\u{0}'..='\u{8}' | '\u{b}' | '\u{e}'..='\u{1f}' | '\u{7f}')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{tokenize, tokenize_with_positions, Token, HashType};
+
+    #[test]
+    fn tokenize_skips_comments() {
+        let tokens = tokenize("/* comment */a/**/b /* unclosed");
+        assert_eq!(tokens, vec![
+            Token::Ident("a".to_string()),
+            Token::Ident("b".to_string()),
+            Token::Whitespace
+        ]);
+    }
+
+    #[test]
+    fn tokenize_numeric_tokens() {
+        let tokens = tokenize("12px -.5em 50% +3 1e2");
+        assert_eq!(tokens, vec![
+            Token::Dimension(12.0, "px".to_string()),
+            Token::Whitespace,
+            Token::Dimension(-0.5, "em".to_string()),
+            Token::Whitespace,
+            Token::Percentage(50.0),
+            Token::Whitespace,
+            Token::Number(3.0),
+            Token::Whitespace,
+            Token::Number(100.0)
+        ]);
+    }
+
+    #[test]
+    fn tokenize_hash_at_keyword_and_function() {
+        let tokens = tokenize("#main #00ff00 @media rgb(");
+        assert_eq!(tokens, vec![
+            Token::Hash("main".to_string(), HashType::Id),
+            Token::Whitespace,
+            Token::Hash("00ff00".to_string(), HashType::Unrestricted),
+            Token::Whitespace,
+            Token::AtKeyword("media".to_string()),
+            Token::Whitespace,
+            Token::Function("rgb".to_string())
+        ]);
+    }
+
+    #[test]
+    fn tokenize_strings_and_urls() {
+        let tokens = tokenize("\"a\\\"b\" 'c\nurl( a.png ) url(\"b.png\") url(a b)");
+        assert_eq!(tokens, vec![
+            Token::String("a\"b".to_string()),
+            Token::Whitespace,
+            Token::BadString,
+            Token::Whitespace,
+            Token::Url("a.png".to_string()),
+            Token::Whitespace,
+            Token::Function("url".to_string()),
+            Token::String("b.png".to_string()),
+            Token::CloseParen,
+            Token::Whitespace,
+            Token::BadUrl
+        ]);
+    }
+
+    #[test]
+    fn tokenize_unterminated_url_at_end_of_input() {
+        let (tokens, positions) = tokenize_with_positions("url(a ");
+        assert_eq!(tokens, vec![Token::Url("a".to_string())]);
+        assert_eq!(positions.len(), 1);
+    }
+
+    #[test]
+    fn tokenize_escapes_in_identifiers() {
+        let tokens = tokenize(".\\31 0 \\@x -\\-y");
+        assert_eq!(tokens, vec![
+            Token::Delim('.'),
+            Token::Ident("10".to_string()),
+            Token::Whitespace,
+            Token::Ident("@x".to_string()),
+            Token::Whitespace,
+            Token::Ident("--y".to_string())
+        ]);
+    }
+
+    #[test]
+    fn tokenize_delimiters_and_cdo_cdc() {
+        let tokens = tokenize("<!-- a > b --> * {x:y;}");
+        assert_eq!(tokens, vec![
+            Token::Cdo,
+            Token::Whitespace,
+            Token::Ident("a".to_string()),
+            Token::Whitespace,
+            Token::Delim('>'),
+            Token::Whitespace,
+            Token::Ident("b".to_string()),
+            Token::Whitespace,
+            Token::Cdc,
+            Token::Whitespace,
+            Token::Delim('*'),
+            Token::Whitespace,
+            Token::OpenCurly,
+            Token::Ident("x".to_string()),
+            Token::Colon,
+            Token::Ident("y".to_string()),
+            Token::Semicolon,
+            Token::CloseCurly
+        ]);
+    }
+}
@@ -36,7 +36,7 @@ impl Parser {
         let (next_pos, _) = iter.next().unwrap_or((1, ' '));
         // NOTE: += next_posがなぞ
         self.pos += next_pos;
-        return cur_char;
+        cur_char
     }
 
     fn consume_while<F>(&mut self, test: F) -> String where F: Fn(char) -> bool {
@@ -44,7 +44,7 @@ impl Parser {
         while !self.eof() && test(self.next_char()) {
             result.push(self.consume_char());
         }
-        return result;
+        result
     }
 
     fn parse_attr_value(&mut self) -> String {
@@ -52,14 +52,19 @@ impl Parser {
         assert!(open_quote == '"' || open_quote == '\'');
         let value = self.consume_while(|c| c != open_quote);
         assert!(self.consume_char() == open_quote);
-        return value;
+        value
     }
 
+    // NOTE: <div hidden> のように値を省略した属性は空文字列の値を持つ
     fn parse_attr(&mut self) -> (String, String) {
         let name = self.parse_tag_name();
-        assert!(self.consume_char() == '=');
+        assert!(!name.is_empty(), "the attribute name is empty");
+        if self.next_char() != '=' {
+            return (name, String::new());
+        }
+        self.consume_char();
         let value = self.parse_attr_value();
-        return (name, value);
+        (name, value)
     }
 
     // Consume and discard zero or more whitespace characters.
@@ -77,15 +82,12 @@ impl Parser {
             let (name, value) = self.parse_attr();
             attributes.insert(name, value);
         };
-        return attributes;
+        attributes
     }
 
     // Parse a tag or attribute name.
     fn parse_tag_name(&mut self) -> String {
-        self.consume_while(|c| match c {
-            'a'..='z' | 'A'..='Z' | '0'..='9' => true,
-            _ => false
-        })
+        self.consume_while(|c| c.is_ascii_alphanumeric())
     }
 
     fn parse_text(&mut self) -> dom::Node {
@@ -106,7 +108,7 @@ impl Parser {
         assert!(self.parse_tag_name() == tag_name, "start tag name and end tag name is not equal");
         assert!(self.consume_char() == '>');
 
-        return dom::elem(tag_name, attrs, children);
+        dom::elem(tag_name, attrs, children)
     }
 
     fn consume_comment(&mut self) {
@@ -146,7 +148,7 @@ impl Parser {
             }
             nodes.push(self.parse_node());
         }
-        return nodes;
+        nodes
     }
 
 }
@@ -216,6 +218,16 @@ mod tests {
         assert_eq!(parsed_dom, expected_dom);
     }
 
+    #[test]
+    fn parse_attribute_without_value() {
+        let target_str = "<div hidden class=\"a\"></div>".to_string();
+        let parsed_dom = parse(target_str);
+        let mut attributes = HashMap::new();
+        attributes.insert("hidden".to_string(), "".to_string());
+        attributes.insert("class".to_string(), "a".to_string());
+        assert_eq!(parsed_dom, elem("div".to_string(), attributes, vec![]));
+    }
+
 }
 
 // let html_string = "<html><body><h1>Title</h1><div id=\"main\" class=\"test\"><p>Hello <em>world</em>!</p></div></body></html>";
@@ -1,9 +1,7 @@
 // 参考: https://limpet.net/mbrubeck/2014/09/08/toy-layout-engine-5-boxes.html
 
-use crate::style::{StyledNode, Display};
+use crate::style::{StyledNode, Display, ComputedStyle, ContainerType, LengthPercentage, LengthPercentageOrAuto};
 use crate::layout::BoxType::{BlockNode, InlineNode, AnonymousBlock};
-use crate::css::Value::{Keyword, Length};
-use crate::css::Unit::Px;
 use std::cell::RefCell;
 use std::rc::Rc;
 
@@ -56,9 +54,9 @@ impl<'a> LayoutBox<'a> {
         }
     }
 
-    fn get_style_node(&self) -> &StyledNode {
+    fn get_style(&self) -> &'a ComputedStyle {
         match self.box_type {
-            BlockNode(node) | InlineNode(node) => node,
+            BlockNode(node) | InlineNode(node) => &node.style,
             AnonymousBlock => panic!("Anonymous block box has no style node")
         }
     }
@@ -74,14 +72,14 @@ pub fn layout_tree<'a>(node: &'a StyledNode<'a>, containing_block: Rc<RefCell<Di
 
 // NOTE: StyledNodeをとりあえず全部LayoutBoxに変換する処理
 fn build_layout_tree<'a>(style_node: &'a StyledNode<'a>) -> LayoutBox<'a> {
-    let mut root = LayoutBox::new(match style_node.display() {
+    let mut root = LayoutBox::new(match style_node.style.display {
         Display::Block => BlockNode(style_node),
         Display::Inline => InlineNode(style_node),
         Display::None => panic!("Root node has display: none.")
     });
 
     for child in &style_node.children {
-        match child.display() {
+        match child.style.display {
             Display::Block => root.children.push(build_layout_tree(child)),
             Display::Inline => root.get_inline_container().children.push(build_layout_tree(child)),
             Display::None => {}
@@ -115,101 +113,96 @@ impl<'a> LayoutBox<'a> {
     }
 
     // NOTE: 対象の要素の横幅(width, border-right, padding-left, margin-left等を含んだもの)を決める
+    // autoの値はNoneで表す
     fn calculate_block_width(&mut self, containing_block: Rc<RefCell<Dimensions>>) {
-        let style = self.get_style_node();
+        let style = self.get_style();
 
-        let auto = Keyword("auto".to_string());
-        let mut width = style.value("width").unwrap_or(auto.clone());
+        // NOTE: width, margin, paddingのパーセンテージはcontaining blockの幅が基準
+        let containing_width = containing_block.borrow().content.width;
 
-        let zero = Length(0.0, Px);
+        let width = style.width.resolve(containing_width);
 
-        let mut margin_left = style.lookup("margin-left", "margin", &zero);
-        let mut margin_right = style.lookup("margin-right", "margin", &zero);
+        let mut margin_left = style.margin.left.resolve(containing_width);
+        let mut margin_right = style.margin.right.resolve(containing_width);
 
-        let border_left = style.lookup("border-left-width", "border-width", &zero);
-        let border_right = style.lookup("border-right-width", "border-width", &zero);
+        let border_left = style.border_width.left;
+        let border_right = style.border_width.right;
 
-        let padding_left = style.lookup("padding-left", "padding", &zero);
-        let padding_right = style.lookup("padding-right", "padding", &zero);
+        let padding_left = style.padding.left.resolve(containing_width);
+        let padding_right = style.padding.right.resolve(containing_width);
 
-        let total: f32 = [&margin_left, &margin_right, &border_left, &border_right,
-        &padding_left, &padding_right, &width].iter().map(|v| v.to_px()).sum();
+        let total: f32 = [margin_left, margin_right, width].iter().map(|v| v.unwrap_or(0.0)).sum::<f32>()
+            + border_left + border_right + padding_left + padding_right;
 
         // NOTE: もし横幅が親要素よりデカかったらmargin-leftとmargin-rightでautoになってるものの値を0にする
-        if width != auto && total > containing_block.borrow().content.width {
-            if margin_left == auto {
-                margin_left = Length(0.0, Px);
+        if width.is_some() && total > containing_width {
+            if margin_left.is_none() {
+                margin_left = Some(0.0);
             }
-            if margin_right == auto {
-                margin_right = Length(0.0, Px);
+            if margin_right.is_none() {
+                margin_right = Some(0.0);
             }
         }
 
         // 親要素とこの要素の横幅の違い(この値がマイナスだったらこの要素がoverflowしてる)
-        let underflow = containing_block.borrow().content.width - total;
+        let underflow = containing_width - total;
 
-        match (width == auto, margin_left == auto, margin_right == auto) {
+        let (width, margin_left, margin_right) = match (width, margin_left, margin_right) {
             // NOTE: width,margin_left,margin_rightが全て10pxみたいに固定値の場合margin_rightを調整する
-            (false, false, false) => {
-                margin_right = Length(margin_right.to_px() + underflow, Px);
-            },
+            (Some(width), Some(margin_left), Some(margin_right)) => (width, margin_left, margin_right + underflow),
             // NOTE: margin-right, margin-leftのどちらかの値がautoだった場合そちらの方のプロパティでunderflowを調整する
-            (false, false, true) => {margin_right = Length(underflow, Px);},
-            (false, true, false) => {margin_left = Length(underflow, Px);},
+            (Some(width), Some(margin_left), None) => (width, margin_left, underflow),
+            (Some(width), None, Some(margin_right)) => (width, underflow, margin_right),
 
-            (true, _, _) => {
+            (None, margin_left, margin_right) => {
                 // NOTE: widthがautoでmargin系プロパティがautoの場合marginはゼロになる
-                if margin_left == auto {margin_left = Length(0.0, Px);}
-                if margin_right == auto {margin_right = Length(0.0, Px);}
+                let margin_left = margin_left.unwrap_or(0.0);
+                let margin_right = margin_right.unwrap_or(0.0);
 
                 // NOTE: widthが残っている幅を全てとるようにする
                 if underflow >= 0.0 {
-                    width = Length(underflow, Px);
+                    (underflow, margin_left, margin_right)
                 } else {
                     // NOTE: もし要素がoverflowしていた場合はwidthをマイナス値にすることができないのでmargin_rightをマイナス値にする
-                    width = Length(0.0, Px);
-                    margin_right = Length(margin_right.to_px() + underflow, Px);
+                    (0.0, margin_left, margin_right + underflow)
                 }
 
             },
             // NOTE: margin_left,margin_rightがどちらともautoの場合仲良く半分ずつoverflowを担当する、こうすると要素が真ん中にくる
-            (false, true, true) => {
-                margin_left = Length(underflow / 2.0, Px);
-                margin_right = Length(underflow / 2.0, Px);
-            }
-        }
+            (Some(width), None, None) => (width, underflow / 2.0, underflow / 2.0)
+        };
 
         let this_dimension = &mut self.dimensions.borrow_mut();
-        this_dimension.content.width = width.to_px();
+        this_dimension.content.width = width;
 
-        this_dimension.padding.left = padding_left.to_px();
-        this_dimension.padding.right = padding_right.to_px();
+        this_dimension.padding.left = padding_left;
+        this_dimension.padding.right = padding_right;
 
-        this_dimension.border.left = border_left.to_px();
-        this_dimension.border.right = border_right.to_px();
+        this_dimension.border.left = border_left;
+        this_dimension.border.right = border_right;
 
-        this_dimension.margin.left = margin_left.to_px();
-        this_dimension.margin.right = margin_right.to_px();
+        this_dimension.margin.left = margin_left;
+        this_dimension.margin.right = margin_right;
     }
 
     // NOTE: 対象のページ上の位置を計算、つまりxとyを計算
     // xとyは親要素のx,yとheight(yの場合)とmargin, padding, borderの値足した値
     fn calculate_block_position(&mut self, containing_block_ref: Rc<RefCell<Dimensions>>) {
-        let style = self.get_style_node();
+        let style = self.get_style();
         let this_dimensions = &mut self.dimensions.borrow_mut();
 
-        let zero = Length(0.0, Px);
-
         let containing_block = containing_block_ref.borrow();
+        // NOTE: 上下のmarginとpaddingのパーセンテージもcontaining blockの幅が基準
+        let containing_width = containing_block.content.width;
 
-        this_dimensions.margin.top = style.lookup("margin-top", "margin", &zero).to_px();
-        this_dimensions.margin.bottom = style.lookup("margin-bottom", "margin", &zero).to_px();
+        this_dimensions.margin.top = style.margin.top.resolve(containing_width).unwrap_or(0.0);
+        this_dimensions.margin.bottom = style.margin.bottom.resolve(containing_width).unwrap_or(0.0);
 
-        this_dimensions.border.top = style.lookup("border-top-width", "border-width", &zero).to_px();
-        this_dimensions.border.bottom = style.lookup("border-bottom-width", "border-width", &zero).to_px();
+        this_dimensions.border.top = style.border_width.top;
+        this_dimensions.border.bottom = style.border_width.bottom;
 
-        this_dimensions.padding.top = style.lookup("padding-top", "padding", &zero).to_px();
-        this_dimensions.padding.bottom = style.lookup("padding-bottom", "padding", &zero).to_px();
+        this_dimensions.padding.top = style.padding.top.resolve(containing_width);
+        this_dimensions.padding.bottom = style.padding.bottom.resolve(containing_width);
 
         this_dimensions.content.x = containing_block.content.x + this_dimensions.margin.left + this_dimensions.border.left + this_dimensions.padding.left;
         this_dimensions.content.y = containing_block.content.height + containing_block.content.y + this_dimensions.margin.top + this_dimensions.border.top + this_dimensions.padding.top;
@@ -220,14 +213,18 @@ impl<'a> LayoutBox<'a> {
             child.layout(self.dimensions.clone());
             let this_dimensions = &mut self.dimensions.borrow_mut();
             // loopでこの要素のheightに子要素のmargin含めたheightを足していって最終的に正しいheightを算出する
-            this_dimensions.content.height = this_dimensions.content.height + child.dimensions.borrow().margin_box().height;
+            this_dimensions.content.height += child.dimensions.borrow().margin_box().height;
         }
     }
 
     // NOTE: デフォルトでは子要素のheightの合計から対象要素のheightを算出するけど明示的にheightプロパティで指定されていた場合はその値を使う
+    // container-type: sizeの要素は高さを問い合わせられるように、子要素の高さに関係なく中身が空の時の高さにする
     fn calculate_block_height(&mut self) {
-        if let Some(Length(h, Px)) = self.get_style_node().value("height") {
+        let style = self.get_style();
+        if let LengthPercentageOrAuto::LengthPercentage(LengthPercentage::Px(h)) = style.height {
             self.dimensions.borrow_mut().content.height = h;
+        } else if style.container_type == Some(ContainerType::Size) {
+            self.dimensions.borrow_mut().content.height = 0.0;
         }
     }
 
@@ -274,12 +271,11 @@ impl Rect {
 
 #[cfg(test)]
 mod tests {
-    use crate::style::{StyledNode, PropertyMap};
+    use crate::style::{StyledNode, PropertyMap, ComputedStyle};
     use crate::dom::{Node, AttrMap, NodeType, ElementData};
-    use crate::css::{Value, Unit};
+    use crate::css::{Value, Unit, CalcNode};
     use super::{Dimensions};
     use crate::layout::{layout_tree, LayoutBox, Rect, BoxType, EdgeSize};
-    use crate::layout::BoxType::AnonymousBlock;
     use std::cell::RefCell;
     use std::rc::Rc;
 
@@ -292,7 +288,8 @@ mod tests {
     }
 
     fn create_styled_node<'a>(node: &'a Node, specified_values: PropertyMap, children: Vec<StyledNode<'a>>) -> StyledNode<'a> {
-        StyledNode {node, specified_values, children}
+        let style = Rc::new(ComputedStyle::new(&specified_values, None));
+        StyledNode {node, specified_values, style, children}
     }
 
     fn create_viewport() -> Rc<RefCell<Dimensions>> {
@@ -311,25 +308,6 @@ mod tests {
         }
     }
 
-    fn create_anonymous_layout_block(children: Vec<LayoutBox>) -> LayoutBox {
-        let dimension = Dimensions {
-            content: Rect {
-                x: 0.0,
-                y: 0.0,
-                width: 0.0,
-                height: 0.0
-            },
-            margin: create_edge_size(None,None,None,None),
-            border: create_edge_size(None,None,None,None),
-            padding: create_edge_size(None,None,None,None),
-        };
-        LayoutBox {
-            dimensions: Rc::new(RefCell::new(dimension)),
-            box_type: AnonymousBlock,
-            children
-        }
-    }
-
     #[test]
     fn test_layout_only_block_node_tree() {
         // <div> block {margin: 8.0, padding: 4.0, width: auto}
@@ -346,8 +324,10 @@ mod tests {
 
         let mut parent_property_map = PropertyMap::new();
         parent_property_map.insert("display".to_string(), Value::Keyword("block".to_string()));
-        parent_property_map.insert("margin".to_string(), Value::Length(8.0, Unit::Px));
-        parent_property_map.insert("padding".to_string(), Value::Length(4.0, Unit::Px));
+        for side in &["top", "right", "bottom", "left"] {
+            parent_property_map.insert(format!("margin-{}", side), Value::Length(8.0, Unit::Px));
+            parent_property_map.insert(format!("padding-{}", side), Value::Length(4.0, Unit::Px));
+        }
         parent_property_map.insert("width".to_string(), Value::Keyword("auto".to_string()));
 
         let styled_child_node = create_styled_node(&child_element, child_property_map, vec![]);
@@ -396,4 +376,41 @@ mod tests {
 
     }
 
+    #[test]
+    fn test_resolve_percentages_and_calc_against_containing_block() {
+        // <div> block {width: 400px}
+        //   <div></div> block {width: calc(50% - 32px), padding-left: 10%, margin-left: min(20%, 60px)}
+        // </div>
+        let child_element = create_element_node("div".to_string(), AttrMap::new(), vec![]);
+        let parent_element = create_element_node("div".to_string(), AttrMap::new(), vec![child_element.clone()]);
+
+        let mut child_property_map = PropertyMap::new();
+        child_property_map.insert("display".to_string(), Value::Keyword("block".to_string()));
+        child_property_map.insert("width".to_string(), Value::Calc(CalcNode::Sum(
+            Box::new(CalcNode::Length(-32.0, Unit::Px)), Box::new(CalcNode::Percentage(50.0))
+        )));
+        child_property_map.insert("padding-left".to_string(), Value::Percentage(10.0));
+        child_property_map.insert("padding-top".to_string(), Value::Percentage(5.0));
+        child_property_map.insert("margin-left".to_string(), Value::Calc(CalcNode::Min(vec![
+            CalcNode::Percentage(20.0), CalcNode::Length(60.0, Unit::Px)
+        ])));
+
+        let mut parent_property_map = PropertyMap::new();
+        parent_property_map.insert("display".to_string(), Value::Keyword("block".to_string()));
+        parent_property_map.insert("width".to_string(), Value::Length(400.0, Unit::Px));
+
+        let styled_child_node = create_styled_node(&child_element, child_property_map, vec![]);
+        let styled_parent_node = create_styled_node(&parent_element, parent_property_map, vec![styled_child_node.clone()]);
+
+        let layout = layout_tree(&styled_parent_node, create_viewport());
+        let child_dimensions = layout.children[0].dimensions.borrow();
+
+        assert_eq!(child_dimensions.content.width, 168.0);
+        assert_eq!(child_dimensions.padding.left, 40.0);
+        assert_eq!(child_dimensions.padding.top, 20.0);
+        assert_eq!(child_dimensions.margin.left, 60.0);
+        // NOTE: 400 - 60 - 40 - 168
+        assert_eq!(child_dimensions.margin.right, 132.0);
+    }
+
 }
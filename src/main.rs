@@ -1,10 +1,10 @@
-use getopts;
-use image;
 use std::fs::File;
-use std::io::{Read, BufWriter};
+use std::io::Read;
+use std::path::Path;
 use std::rc::Rc;
 use std::cell::RefCell;
 use crate::layout::Dimensions;
+use crate::painting::Canvas;
 
 mod dom;
 mod html;
@@ -18,7 +18,13 @@ fn main() {
     let mut opts = getopts::Options::new();
     opts.optopt("h", "html", "HTML document", "FILENAME");
     opts.optopt("c", "css", "CSS stylesheet", "FILENAME");
+    opts.optopt("u", "user-css", "User CSS stylesheet", "FILENAME");
     opts.optopt("o", "output", "Output file", "FILENAME");
+    opts.optflag("", "print-css", "Print the parsed author stylesheet");
+    opts.optflag("", "minify", "Print the author stylesheet minified (with --print-css)");
+    opts.optopt("", "frames", "Render N animation frames as an animated GIF", "N");
+    opts.optopt("", "duration", "Seconds of animation covered by the frames (default 1)", "SECONDS");
+    opts.optopt("", "transition-css", "CSS stylesheet applied at time 0 to start transitions", "FILENAME");
 
     let matches = opts.parse(std::env::args().skip(1)).unwrap();
     let str_arg = |flag: &str, default: &str| -> String {
@@ -27,24 +33,66 @@ fn main() {
 
 
     let html = read_source(str_arg("h", "examples/test.html"));
-    let css = read_source(str_arg("c", "examples/test.css"));
 
-    let mut viewport = Rc::new(RefCell::new(Dimensions::default()));
+    let viewport = Rc::new(RefCell::new(Dimensions::default()));
     viewport.clone().borrow_mut().content.width = 800.0;
     viewport.clone().borrow_mut().content.height = 600.0;
     println!("{:?}", viewport.borrow().content.height);
 
     let root_node = html::parse(html);
-    let stylesheet = css::parse(css);
-    let style_root = style::style_tree(&root_node, &stylesheet);
-    println!("before layout: {:?}", viewport.borrow().content.height);
-    let layout_root = layout::layout_tree(&style_root, viewport.clone());
-
+    // NOTE: @importの読み込みに失敗しても描画は続け、問題だけを表示する
+    let mut diagnostics = vec![];
+    let stylesheet = css::load_stylesheet(Path::new(&str_arg("c", "examples/test.css")), &mut diagnostics);
+    // NOTE: ユーザースタイルシートは指定された時だけカスケードに加える
+    let user_stylesheet = matches.opt_str("u").map(|filename| css::load_stylesheet(Path::new(&filename), &mut diagnostics));
+    let transition_stylesheet = matches.opt_str("transition-css").map(|filename| css::load_stylesheet(Path::new(&filename), &mut diagnostics));
+    for diagnostic in &diagnostics {
+        eprintln!("warning: {}", diagnostic);
+    }
+    if matches.opt_present("print-css") {
+        if matches.opt_present("minify") {
+            println!("{}", css::minify(&stylesheet));
+        } else {
+            print!("{}", css::serialize(&stylesheet));
+        }
+    }
+    // NOTE: ユーザーエージェントスタイルシートは一番弱い出どころとして常に加える
+    let user_agent_stylesheet = style::user_agent_stylesheet();
+    let mut stylesheets = vec![(style::Origin::UserAgent, &user_agent_stylesheet), (style::Origin::Author, &stylesheet)];
+    if let Some(ref user_stylesheet) = user_stylesheet {
+        stylesheets.push((style::Origin::User, user_stylesheet));
+    }
     let filename = str_arg("o", "output.png");
-    // let mut file = BufWriter::new(File::create(&filename).unwrap());
-    viewport.clone().borrow_mut().content.height = 600.0;
 
-    let canvas = painting::paint(&layout_root, viewport.borrow().content.clone());
+    // NOTE: --framesを指定した場合はアニメーションを時刻ごとに描画してGIFにする
+    let frames: usize = str_arg("frames", "1").parse().unwrap();
+    if frames > 1 || transition_stylesheet.is_some() {
+        let duration: f32 = str_arg("duration", "1").parse().unwrap();
+        // NOTE: トランジションは時刻0に--transition-cssのスタイルシートが加わった変化として扱う
+        let before_change = transition_stylesheet.as_ref().map(|_| style::style_tree(&root_node, &stylesheets, &viewport.borrow()));
+        let mut after_change_stylesheets = stylesheets.clone();
+        if let Some(ref transition_stylesheet) = transition_stylesheet {
+            after_change_stylesheets.push((style::Origin::Author, transition_stylesheet));
+        }
+        let delay = (duration * 1000.0 / frames as f32).round() as u32;
+        let images: Vec<image::Frame> = (0..frames).map(|i| {
+            let time = duration * i as f32 / frames as f32;
+            let style_root = style::animated_style_tree(&root_node, &after_change_stylesheets, &viewport.borrow(), time, before_change.as_ref());
+            let canvas = render(&style_root, viewport.clone());
+            let imgbuf = image::RgbaImage::from_fn(canvas.width as u32, canvas.height as u32, |x, y| {
+                let color = &canvas.pixels[y as usize * canvas.width + x as usize];
+                image::Rgba([color.r, color.g, color.b, 255])
+            });
+            image::Frame::from_parts(imgbuf, 0, 0, image::Delay::from_numer_denom_ms(delay, 1))
+        }).collect();
+        let mut encoder = image::codecs::gif::GifEncoder::new(File::create(&filename).unwrap());
+        let ok = encoder.set_repeat(image::codecs::gif::Repeat::Infinite).is_ok() && encoder.encode_frames(images).is_ok();
+        println!("{}", if ok {"success"} else {"failed"});
+        return;
+    }
+
+    let style_root = style::style_tree(&root_node, &stylesheets, &viewport.borrow());
+    let canvas = render(&style_root, viewport.clone());
     let (width, height) = (canvas.width as u32, canvas.height as u32);
     println!("{:?}", canvas.height);
     println!("{:?}, {:?}", width, height);
@@ -88,6 +136,12 @@ fn main() {
 
 }
 
+fn render(style_root: &style::StyledNode, viewport: Rc<RefCell<Dimensions>>) -> Canvas {
+    let layout_root = layout::layout_tree(style_root, viewport.clone());
+    viewport.borrow_mut().content.height = 600.0;
+    painting::paint(&layout_root, viewport.borrow().content.clone())
+}
+
 fn read_source(filename: String) -> String {
     let mut str = String::new();
     File::open(filename).unwrap().read_to_string(&mut str).unwrap();
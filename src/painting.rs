@@ -1,7 +1,7 @@
 // 参考: https://limpet.net/mbrubeck/2014/11/05/toy-layout-engine-7-painting.html
-use crate::css::{Color, Value};
+use crate::css::Color;
 use crate::layout::{Rect, BoxType, LayoutBox};
-use std::io::{repeat, Read};
+use crate::style::ComputedStyle;
 
 type DisplayList = Vec<DisplayCommand>;
 
@@ -43,56 +43,62 @@ fn render_layout_box(list: &mut DisplayList, layout_box: &LayoutBox) {
 }
 
 fn render_background(list: &mut DisplayList, layout_box: &LayoutBox) {
-    get_color(layout_box, "background").map(|color|
-        list.push(DisplayCommand::SolidColor(color, layout_box.dimensions.borrow().border_box()))
-    );
+    if let Some(color) = get_color(layout_box, |style| &style.background_color) {
+        list.push(DisplayCommand::SolidColor(color, layout_box.dimensions.borrow().border_box()));
+    }
 }
 
-// NOTE: LayoutBoxが持っている色のプロパティを取得
-fn get_color(layout_box: &LayoutBox, name: &str) -> Option<Color> {
+// NOTE: LayoutBoxが持っている色のプロパティを取得。透明な色は描画しないのでNoneにする
+fn get_color(layout_box: &LayoutBox, property: impl Fn(&ComputedStyle) -> &Color) -> Option<Color> {
     match layout_box.box_type {
-        BoxType::BlockNode(style) | BoxType::InlineNode(style) => match style.value(name) {
-            Some(Value::ColorValue(color)) => Some(color),
-            _ => None
-        },
+        BoxType::BlockNode(style_node) | BoxType::InlineNode(style_node) => Some(property(&style_node.style))
+            .filter(|color| color.a != 0)
+            .cloned(),
         BoxType::AnonymousBlock => None
     }
 }
 
 fn render_borders(list: &mut DisplayList, layout_box: &LayoutBox) {
-    let color = match get_color(layout_box, "border-color") {
-        Some(color) => color,
-        _ => return
-    };
-
     let this_dimension = &layout_box.dimensions;
     let border_box = &this_dimension.clone().borrow().border_box();
     let border = &this_dimension.borrow().border;
 
-    // left border
-    list.push(DisplayCommand::SolidColor(color.clone(), Rect {
-        x: border_box.x,
-        y: border_box.y,
-        width: border.left.clone(),
-        height: border_box.height
-    }));
-
-    // right border
-    list.push(DisplayCommand::SolidColor(color.clone(), Rect {
-        x: border_box.x + border_box.width - border.right.clone(),
-        y: border_box.y,
-        width: border.right.clone(),
-        height: border_box.height
-    }));
-
-    // top border
-    list.push(DisplayCommand::SolidColor(color, Rect {
-        x: border_box.x,
-        y: border_box.y + border_box.height - border.bottom.clone(),
-        width: border_box.width,
-        height: border.bottom.clone()
-    }));
+    // NOTE: 辺ごとにborder-*-colorが指定されている場合だけ描画する
+    if let Some(color) = get_color(layout_box, |style| &style.border_color.top) {
+        list.push(DisplayCommand::SolidColor(color, Rect {
+            x: border_box.x,
+            y: border_box.y,
+            width: border_box.width,
+            height: border.top
+        }));
+    }
+
+    if let Some(color) = get_color(layout_box, |style| &style.border_color.right) {
+        list.push(DisplayCommand::SolidColor(color, Rect {
+            x: border_box.x + border_box.width - border.right,
+            y: border_box.y,
+            width: border.right,
+            height: border_box.height
+        }));
+    }
+
+    if let Some(color) = get_color(layout_box, |style| &style.border_color.bottom) {
+        list.push(DisplayCommand::SolidColor(color, Rect {
+            x: border_box.x,
+            y: border_box.y + border_box.height - border.bottom,
+            width: border_box.width,
+            height: border.bottom
+        }));
+    }
 
+    if let Some(color) = get_color(layout_box, |style| &style.border_color.left) {
+        list.push(DisplayCommand::SolidColor(color, Rect {
+            x: border_box.x,
+            y: border_box.y,
+            width: border.left,
+            height: border_box.height
+        }));
+    }
 }
 
 
@@ -128,7 +134,8 @@ impl Canvas {
 
                 for y in y0..y1 {
                     for x in x0 .. x1 {
-                        self.pixels[x + y * self.width] = color.clone();
+                        let pixel = &mut self.pixels[x + y * self.width];
+                        *pixel = blend(color, pixel);
                     }
                 }
             }
@@ -136,6 +143,17 @@ impl Canvas {
     }
 }
 
+// NOTE: 半透明の色をsource-overで下の色に重ねる
+fn blend(source: &Color, destination: &Color) -> Color {
+    let alpha = source.a as f32 / 255.0;
+    let mix = |s: u8, d: u8| (s as f32 * alpha + d as f32 * (1.0 - alpha)).round() as u8;
+    Color {
+        r: mix(source.r, destination.r),
+        g: mix(source.g, destination.g),
+        b: mix(source.b, destination.b),
+        a: (source.a as f32 + destination.a as f32 * (1.0 - alpha)).round() as u8
+    }
+}
 
 // NOTE: 自分なりに処理の手順メモ
 // 目標: 最終的にCanvasオブジェクト内にwindowのwidthとheightの大きさ
@@ -1,147 +1,846 @@
 // NOTE: https://limpet.net/mbrubeck/2014/08/23/toy-layout-engine-4-style.html
 
+mod shorthand;
+mod variables;
+mod animation;
+mod container;
+mod computed;
+mod selector_map;
+
+use std::cmp::Reverse;
 use std::collections::{HashMap};
-use crate::css::{Value, Selector, SimpleSelector, Specificity, Rule, Stylesheet, Unit};
+use std::rc::Rc;
+use std::cell::RefCell;
+use crate::css::{Value, Selector, SimpleSelector, AttributeSelector, AttributeMatcher, Combinator, PseudoClass, Specificity, Rule, CssRule, KeyframesRule, ContainerRule, Declaration, Stylesheet, Unit, Color, Token, CalcNode, named_color, parse_value_tokens, parse_declaration_list};
+use crate::css::media::{Device, evaluate_media_list};
+use crate::css::supports::evaluate_supports_condition;
+use crate::css::properties::{PROPERTIES, property_definition, is_supported_declaration};
 use crate::dom::{Node, ElementData, NodeType};
+use crate::layout::Dimensions;
+use self::animation::Timeline;
+use self::container::{ContainerSizes, QueryContainer};
+use self::selector_map::{SelectorMap, AncestorFilter};
+pub use self::computed::{ComputedStyle, Display, LengthPercentage, LengthPercentageOrAuto};
+pub use self::container::ContainerType;
 
 pub type PropertyMap = HashMap<String, Value>;
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct StyledNode<'a> {
     pub node: &'a Node,
+    // NOTE: アニメーションやトランジションで前のスタイルと比べるために、プロパティ名で引ける値も残しておく
     pub specified_values: PropertyMap,
+    // NOTE: レイアウトと描画はこちらの型の付いた値を読む
+    pub style: Rc<ComputedStyle>,
     pub children: Vec<StyledNode<'a>>,
 }
 
-pub enum Display {
-    Inline,
-    Block,
-    None
-}
-
 impl<'a> StyledNode<'a> {
+    #[allow(dead_code)]
     pub fn value(&self, name: &str) -> Option<Value> {
-        self.specified_values.get(name).map(|v| v.clone())
+        self.specified_values.get(name).cloned()
     }
+}
+
+// NOTE: スタイルシートの出どころ。https://www.w3.org/TR/css-cascade-4/#cascade-origin
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Origin {
+    UserAgent,
+    User,
+    Author
+}
 
-    pub fn lookup(&self, name: &str, fallback_name: &str, default: &Value) -> Value {
-        self.value(name).unwrap_or_else(||self.value(fallback_name).unwrap_or_else(|| default.clone()))
+// NOTE: 出どころと!importantの組み合わせによる優先度。下にあるほど優先される
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum CascadeLevel {
+    UserAgentNormal,
+    UserNormal,
+    AuthorNormal,
+    AuthorImportant,
+    UserImportant,
+    UserAgentImportant
+}
+
+impl CascadeLevel {
+    fn origin(&self) -> Origin {
+        match self {
+            CascadeLevel::UserAgentNormal | CascadeLevel::UserAgentImportant => Origin::UserAgent,
+            CascadeLevel::UserNormal | CascadeLevel::UserImportant => Origin::User,
+            CascadeLevel::AuthorNormal | CascadeLevel::AuthorImportant => Origin::Author
+        }
     }
 
-    pub fn display(&self) -> Display {
-        match self.value("display") {
-            Some(Value::Keyword(s)) => match &*s {
-                "block" => Display::Block,
-                "none" => Display::None,
-                _ => Display::Inline
-            },
-            _ => Display::Inline
+    fn new(origin: Origin, important: bool) -> CascadeLevel {
+        match (origin, important) {
+            (Origin::UserAgent, false) => CascadeLevel::UserAgentNormal,
+            (Origin::User, false) => CascadeLevel::UserNormal,
+            (Origin::Author, false) => CascadeLevel::AuthorNormal,
+            (Origin::Author, true) => CascadeLevel::AuthorImportant,
+            (Origin::User, true) => CascadeLevel::UserImportant,
+            (Origin::UserAgent, true) => CascadeLevel::UserAgentImportant
         }
     }
+}
 
+// NOTE: カスケードでの宣言の優先度。フィールドの順に比較され、大きいほど優先される
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct CascadePriority {
+    level: CascadeLevel,
+    // NOTE: style属性の宣言かどうか。同じ出どころと!importantの中ではレイヤーや詳細度より優先される
+    element_attached: bool,
+    layer: LayerPriority,
+    specificity: Specificity,
+    // NOTE: (ルールの順番, ルールの中での宣言の順番)。後に書かれたものが優先される
+    source_order: (usize, usize)
 }
 
-const INHERIT_PROPS: [&str; 4] = ["color", "font-size", "font-weight", "line-height"];
+// NOTE: !importantの宣言ではレイヤーの優先度が逆になる
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum LayerPriority {
+    Normal(LayerOrder),
+    Important(Reverse<LayerOrder>)
+}
+
+impl CascadePriority {
+    fn new(rule: &CascadeRule, important: bool, specificity: Specificity, source_order: (usize, usize)) -> CascadePriority {
+        CascadePriority {
+            level: CascadeLevel::new(rule.origin, important),
+            element_attached: false,
+            layer: if important {LayerPriority::Important(Reverse(rule.layer.clone()))} else {LayerPriority::Normal(rule.layer.clone())},
+            specificity,
+            source_order
+        }
+    }
+
+    fn is_important(&self) -> bool {
+        matches!(self.level, CascadeLevel::AuthorImportant | CascadeLevel::UserImportant | CascadeLevel::UserAgentImportant)
+    }
+
+    // NOTE: revert-layerで戻す範囲。style属性はレイヤーに属さないルールと同じレイヤーとして扱う
+    fn is_same_layer(&self, other: &CascadePriority) -> bool {
+        self.level == other.level && self.layer == other.layer
+    }
 
-pub fn style_tree<'a>(root: &'a Node, stylesheet: &'a Stylesheet) -> StyledNode<'a> {
+    // NOTE: style属性は作者の出どころで、レイヤーに属さない
+    fn element_attached(important: bool, source_order: (usize, usize)) -> CascadePriority {
+        let layer = LayerOrder(vec![usize::MAX]);
+        CascadePriority {
+            level: CascadeLevel::new(Origin::Author, important),
+            element_attached: true,
+            layer: if important {LayerPriority::Important(Reverse(layer))} else {LayerPriority::Normal(layer)},
+            specificity: (0, 0, 0),
+            source_order
+        }
+    }
+}
+
+// NOTE: カスケードレイヤーの順番。レイヤー名の各段階が兄弟の中で何番目に宣言されたかを並べ、
+// 最後にそのレイヤー直属のルールであることを表すusize::MAXを付ける
+// こうするとa.bはaの直属のルールより前になり、レイヤーに属さないルール([usize::MAX])は一番後ろになる
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct LayerOrder(Vec<usize>);
+
+// NOTE: 出どころごとに宣言されたレイヤーを宣言された順に覚えておく
+#[derive(Default)]
+struct LayerRegistry {
+    layers: Vec<(Origin, Vec<String>)>,
+    anonymous_count: usize
+}
+
+impl LayerRegistry {
+    fn order(&mut self, origin: Origin, path: &[String]) -> LayerOrder {
+        let mut order = Vec::new();
+        for depth in 0..path.len() {
+            let prefix = &path[..=depth];
+            if !self.layers.iter().any(|(o, layer)| *o == origin && layer.as_slice() == prefix) {
+                self.layers.push((origin, prefix.to_vec()));
+            }
+            let index = self.layers.iter()
+                .filter(|(o, layer)| *o == origin && layer.len() == depth + 1 && layer.starts_with(&path[..depth]))
+                .position(|(_, layer)| layer.as_slice() == prefix)
+                .unwrap();
+            order.push(index);
+        }
+        order.push(usize::MAX);
+        LayerOrder(order)
+    }
+
+    // NOTE: 無名のレイヤーは他のどのレイヤーとも別物になる名前を付ける
+    // プリプロセスでNULLは置換されるので、NULLを含む名前がCSSに書かれることはない
+    fn anonymous_name(&mut self) -> String {
+        self.anonymous_count += 1;
+        format!("\0{}", self.anonymous_count)
+    }
+}
+
+const INITIAL_FONT_SIZE: f32 = 16.0;
+
+// NOTE: コンテナの大きさが変わり続ける場合に備えて、スタイルとレイアウトを繰り返す回数に上限を設ける
+const MAX_CONTAINER_RESTYLES: usize = 8;
+
+// NOTE: violetに組み込んだユーザーエージェントスタイルシート。Origin::UserAgentとしてカスケードに加える
+const USER_AGENT_CSS: &str = include_str!("style/user_agent.css");
+
+pub fn user_agent_stylesheet() -> Stylesheet {
+    crate::css::parse(USER_AGENT_CSS.to_string())
+}
+
+// NOTE: stylesheetsはカスケードに参加する全てのスタイルシートとその出どころ
+pub fn style_tree<'a>(root: &'a Node, stylesheets: &[(Origin, &Stylesheet)], viewport: &Dimensions) -> StyledNode<'a> {
+    animated_style_tree(root, stylesheets, viewport, 0.0, None)
+}
+
+// NOTE: timeの時点のアニメーションを反映したスタイルを返す。timeは文書を読み込んでからの秒数
+// before_changeを渡すと、時刻0にそのスタイルから変化したものとしてトランジションも反映する
+pub fn animated_style_tree<'a>(root: &'a Node, stylesheets: &[(Origin, &Stylesheet)], viewport: &Dimensions, time: f32, before_change: Option<&StyledNode>) -> StyledNode<'a> {
     let default_prop_map = create_default_props();
+    let device = Device::new(viewport.content.width, viewport.content.height);
+    let mut keyframes = HashMap::new();
+    let rules = effective_rules(stylesheets, &device, &mut keyframes);
+    let selector_map = SelectorMap::new(&rules);
+    let mut context = StyleContext {
+        rules: &rules,
+        selector_map: &selector_map,
+        ancestor_filter: RefCell::new(AncestorFilter::new()),
+        timeline: Timeline {keyframes, time},
+        viewport,
+        device: &device,
+        container_sizes: ContainerSizes::new()
+    };
 
-    style_tree_rec(root, stylesheet, &default_prop_map)
+    let mut style_root = style_tree_rec(root, &context, None, &[], &default_prop_map, None, before_change);
+    // NOTE: @containerの条件はコンテナをレイアウトした大きさで決まる。スタイルを決め直すと中のコンテナの大きさも変わりうるので、
+    // コンテナの大きさが変わらなくなるまでスタイルとレイアウトを繰り返す
+    if rules.iter().any(|rule| !rule.containers.is_empty()) {
+        for _ in 0..MAX_CONTAINER_RESTYLES {
+            let container_sizes = container::layout_container_sizes(&style_root, viewport);
+            if container_sizes == context.container_sizes {
+                break;
+            }
+            context.container_sizes = container_sizes;
+            style_root = style_tree_rec(root, &context, None, &[], &default_prop_map, None, before_change);
+        }
+    }
+    style_root
+}
+
+// NOTE: 要素ごとのスタイルを決める間に変わらないもの
+struct StyleContext<'a, 'b> {
+    rules: &'b [CascadeRule<'a>],
+    selector_map: &'b SelectorMap,
+    // NOTE: スタイルを決めている要素の祖先。style_tree_recで子に進む時に出し入れする
+    ancestor_filter: RefCell<AncestorFilter>,
+    timeline: Timeline<'a>,
+    viewport: &'b Dimensions,
+    device: &'b Device,
+    // NOTE: 前回のレイアウトで決まったコンテナの大きさ。最初はまだレイアウトしていないので空
+    container_sizes: ContainerSizes
+}
+
+// NOTE: カスケードに参加するスタイルルール。@mediaや@importの中のルールも平らにして書かれた順に並べ、
+// その並びの位置をルールの順番として使う
+struct CascadeRule<'a> {
+    origin: Origin,
+    layer: LayerOrder,
+    rule: &'a Rule,
+    // NOTE: ルールを囲んでいる@container。要素ごとにコンテナの大きさで評価して、全て一致した時だけ適用する
+    containers: Vec<&'a ContainerRule>
+}
+
+// NOTE: 条件が一致しない@mediaや@supportsの中のルールはここで取り除く。@keyframesは名前ごとに最後に書かれたものをkeyframesに集める
+fn effective_rules<'a>(stylesheets: &[(Origin, &'a Stylesheet)], device: &Device, keyframes: &mut HashMap<String, &'a KeyframesRule>) -> Vec<CascadeRule<'a>> {
+    let mut collector = RuleCollector {device, layers: LayerRegistry::default(), rules: Vec::new(), keyframes};
+    for &(origin, stylesheet) in stylesheets {
+        collector.collect(origin, &stylesheet.rules, &[], &[]);
+    }
+    collector.rules
 }
 
+struct RuleCollector<'a, 'b> {
+    device: &'b Device,
+    layers: LayerRegistry,
+    rules: Vec<CascadeRule<'a>>,
+    keyframes: &'b mut HashMap<String, &'a KeyframesRule>
+}
+
+impl<'a, 'b> RuleCollector<'a, 'b> {
+    // NOTE: layerは今いるレイヤーの名前。レイヤーの外ならから。containersは今いる@containerのルール
+    fn collect(&mut self, origin: Origin, css_rules: &'a [CssRule], layer: &[String], containers: &[&'a ContainerRule]) {
+        for css_rule in css_rules {
+            match css_rule {
+                CssRule::Style(rule) => {
+                    let layer = self.layers.order(origin, layer);
+                    self.rules.push(CascadeRule {origin, layer, rule, containers: containers.to_vec()});
+                },
+                CssRule::Media(media_rule) => {
+                    if evaluate_media_list(&media_rule.media, self.device) {
+                        self.collect(origin, &media_rule.rules, layer, containers);
+                    }
+                },
+                // NOTE: 読み込んだスタイルシートのルールは@importが書かれた位置に展開する
+                CssRule::Import(import_rule) => {
+                    let mut import_layer = layer.to_vec();
+                    match import_rule.layer {
+                        Some(ref names) if names.is_empty() => import_layer.push(self.layers.anonymous_name()),
+                        Some(ref names) => import_layer.extend(names.iter().cloned()),
+                        None => {}
+                    }
+                    if let Some(ref stylesheet) = import_rule.stylesheet {
+                        if evaluate_media_list(&import_rule.media, self.device) {
+                            self.collect(origin, &stylesheet.rules, &import_layer, containers);
+                        }
+                    }
+                },
+                CssRule::Supports(supports_rule) => {
+                    if evaluate_supports_condition(&supports_rule.condition) {
+                        self.collect(origin, &supports_rule.rules, layer, containers);
+                    }
+                },
+                CssRule::Layer(layer_rule) => {
+                    let mut block_layer = layer.to_vec();
+                    if layer_rule.name.is_empty() {
+                        block_layer.push(self.layers.anonymous_name());
+                    } else {
+                        block_layer.extend(layer_rule.name.iter().cloned());
+                    }
+                    self.collect(origin, &layer_rule.rules, &block_layer, containers);
+                },
+                // NOTE: 文の形の@layerはレイヤーの順番を決めるだけ
+                CssRule::LayerStatement(statement) => {
+                    for name in &statement.names {
+                        self.layers.order(origin, &[layer, name.as_slice()].concat());
+                    }
+                },
+                // NOTE: @containerはレイアウトするまで評価できないので、ルールに条件として持たせておく
+                CssRule::Container(container_rule) => {
+                    self.collect(origin, &container_rule.rules, layer, &[containers, &[container_rule]].concat());
+                },
+                CssRule::Keyframes(keyframes_rule) => {
+                    self.keyframes.insert(keyframes_rule.name.clone(), keyframes_rule);
+                }
+            }
+        }
+    }
+}
+
+// NOTE: ルート要素が継承する値。継承するプロパティの初期値になる
 fn create_default_props() -> PropertyMap {
-    let mut default_prop_map = PropertyMap::new();
-    default_prop_map.insert("color".to_string(), Value::Keyword("#000000".to_string()));
-    default_prop_map.insert("font-size".to_string(), Value::Length(16.0, Unit::Px));
-    default_prop_map.insert("font-weight".to_string(), Value::Keyword("normal".to_string()));
-    default_prop_map.insert("line-height".to_string(), Value::Keyword("normal".to_string()));
-    default_prop_map
-}
-
-fn style_tree_rec<'a>(root: &'a Node, stylesheet: &'a Stylesheet, parent_prop_map: &PropertyMap) -> StyledNode<'a> {
-    let specified_values = match root.node_type {
-        NodeType::Element(ref elem) => specified_values(elem, stylesheet, parent_prop_map),
-        NodeType::Text(_) => HashMap::new()
+    PROPERTIES.iter()
+        .filter(|property| property.inherited)
+        .map(|property| (property.name.to_string(), property.initial_value()))
+        .collect()
+}
+
+// NOTE: カスタムプロパティは全て継承する
+fn is_inherited(name: &str) -> bool {
+    variables::is_custom_property(name) || property_definition(name).is_some_and(|property| property.inherited)
+}
+
+// NOTE: root_font_sizeはルート要素のfont-size。ルート要素自身を処理している間はまだ決まっていないのでNone
+// NOTE: before_changeはトランジションが始まる前のスタイルの中で、rootに対応するノード
+// NOTE: parentとpreceding_siblingsはrootの親の要素と、親の子の中でrootより前にあるノード
+fn style_tree_rec<'a>(root: &'a Node, context: &StyleContext, parent: Option<&Element<'a, '_>>, preceding_siblings: &'a [Node], parent_prop_map: &PropertyMap, root_font_size: Option<f32>, before_change: Option<&StyledNode>) -> StyledNode<'a> {
+    let mut element = match root.node_type {
+        NodeType::Element(ref data) => Some(Element {data, parent, preceding_siblings, container: None, style: None}),
+        NodeType::Text(_) => None
+    };
+    let parent_style = parent.and_then(|parent| parent.style.as_deref());
+    let specified_values = match element {
+        Some(ref mut element) => {
+            let before_change_values = before_change.map(|before_change| &before_change.specified_values);
+            let mut values = specified_values(element, context, parent_prop_map, before_change_values);
+            compute_values(&mut values, parent_prop_map, root_font_size, context.viewport);
+            element.container = container::query_container(element.data, &values, &context.container_sizes);
+            element.style = Some(Rc::new(ComputedStyle::new(&values, parent_style)));
+            values
+        },
+        None => HashMap::new()
     };
+    // NOTE: テキストノードは値を持たないので、親から継承した値だけを持つ
+    let style = match element {
+        Some(Element {style: Some(ref style), ..}) => Rc::clone(style),
+        _ => Rc::new(ComputedStyle::new(&specified_values, parent_style))
+    };
+    let root_font_size = root_font_size.or_else(|| Some(font_size_of(&specified_values)));
+    let before_change_children = |i: usize| before_change.and_then(|before_change| before_change.children.get(i));
+    if let Some(ref element) = element {
+        context.ancestor_filter.borrow_mut().push(element.data);
+    }
+    let children = root.children.iter().enumerate()
+        .map(|(i, child)| style_tree_rec(child, context, element.as_ref(), &root.children[..i], &specified_values, root_font_size, before_change_children(i)))
+        .collect();
+    if let Some(ref element) = element {
+        context.ancestor_filter.borrow_mut().pop(element.data);
+    }
     StyledNode {
         node: root,
-        specified_values: specified_values.clone(),
-        children: root.children.iter().map(|child| style_tree_rec(child, stylesheet, &specified_values)).collect(),
+        specified_values,
+        style,
+        children,
     }
 }
 
 // その要素に渡すDeclarationのプロパティ名と値のマップを返す
-fn specified_values(elem: &ElementData, stylesheet: &Stylesheet, parent_prop_map: &PropertyMap) -> PropertyMap {
+fn specified_values(elem: &Element, context: &StyleContext, parent_prop_map: &PropertyMap, before_change: Option<&PropertyMap>) -> PropertyMap {
     let mut values: PropertyMap = HashMap::new();
 
         // 継承するのがデフォルトの値に対して全部親から値をとる
-        for prop_name in INHERIT_PROPS.iter() {
-            match parent_prop_map.get(&prop_name.to_string()) {
-                Some(x) => {values.insert(prop_name.to_string(), x.clone());},
-                None => ()
-            };
+        for property in PROPERTIES.iter().filter(|property| property.inherited) {
+            if let Some(x) = parent_prop_map.get(property.name) {
+                values.insert(property.name.to_string(), x.clone());
+            }
         }
+        // NOTE: カスタムプロパティは全て継承する
+        for (name, value) in parent_prop_map.iter().filter(|(name, _)| variables::is_custom_property(name)) {
+            values.insert(name.clone(), value.clone());
+        }
+
+    // NOTE: style属性の中の不正な宣言は、スタイルシートと同じように読み飛ばす
+    let inline_declarations = match elem.data.attributes.get("style") {
+        Some(style) => parse_declaration_list(style).0,
+        None => vec![]
+    };
+
+    // NOTE: 出どころと!important、style属性かどうか、レイヤー、詳細度、書かれた順で並べる
+    let mut declarations = Vec::new();
+    for (rule_index, specificity, cascade_rule) in matching_rules(elem, context) {
+        for (declaration_index, declaration) in cascade_rule.rule.declarations.iter().enumerate() {
+            let priority = CascadePriority::new(cascade_rule, declaration.important, specificity, (rule_index, declaration_index));
+            declarations.push((priority, declaration));
+        }
+    }
+    for (declaration_index, declaration) in inline_declarations.iter().enumerate() {
+        let priority = CascadePriority::element_attached(declaration.important, (context.rules.len(), declaration_index));
+        declarations.push((priority, declaration));
+    }
+    declarations.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    // NOTE: var()で参照できるように、カスタムプロパティを先に決めて置換しておく
+    let (custom_declarations, declarations): (Vec<_>, Vec<_>) = declarations.iter()
+        .partition(|(_, declaration)| variables::is_custom_property(&declaration.name));
+    let mut custom_candidates = CascadeCandidates::new();
+    for (priority, declaration) in custom_declarations {
+        custom_candidates.entry(declaration.name.clone()).or_default().push((priority, declaration.value.clone()));
+    }
+    apply_candidates(&mut values, &custom_candidates, false, parent_prop_map);
+    apply_candidates(&mut values, &custom_candidates, true, parent_prop_map);
+    variables::resolve_custom_properties(&mut values);
+
+    // NOTE: アニメーションは通常の宣言より強く!importantの宣言より弱い。トランジションはそのどちらよりも強い
+    let candidates = cascade_candidates(&declarations, &values);
+    apply_candidates(&mut values, &candidates, false, parent_prop_map);
+    if !context.timeline.keyframes.is_empty() {
+        let mut cascaded_values = values.clone();
+        apply_candidates(&mut cascaded_values, &candidates, true, parent_prop_map);
+        animation::apply_animations(&mut values, &cascaded_values, &context.timeline);
+    }
+    apply_candidates(&mut values, &candidates, true, parent_prop_map);
+    if let Some(before_change) = before_change {
+        animation::apply_transitions(&mut values, before_change, context.timeline.time);
+    }
+    values
+}
 
-    let mut rules = matching_rules(elem, stylesheet);
-
-    rules.sort_by(|&(a, _), &(b,_)| a.cmp(&b));
-    for (_, rule) in rules {
-        for declaration in &rule.declarations {
-            if declaration.value == Value::Keyword("inherit".to_string()) {
-                let parent_value_opt = parent_prop_map.get(declaration.name.as_str());
-                match parent_value_opt {
-                    Some(x) => {values.insert(declaration.name.clone(), x.clone());},
-                    None => ()
-                };
-            }else {
-                values.insert(declaration.name.clone(), declaration.value.clone());
+// NOTE: ロングハンドごとに、そのプロパティに当てはまる宣言の値をカスケードの優先度の低い順に並べたもの
+type CascadeCandidates<'p> = HashMap<String, Vec<(&'p CascadePriority, Value)>>;
+
+// NOTE: CSS全体のキーワードを解決した結果
+enum CascadedValue<'v> {
+    Specified(&'v Value),
+    Inherit,
+    Initial
+}
+
+// NOTE: declarationsは優先度の低い順。ショートハンドは展開し、var()は置換してからロングハンドごとに分ける
+fn cascade_candidates<'p>(declarations: &[&'p (CascadePriority, &Declaration)], values: &PropertyMap) -> CascadeCandidates<'p> {
+    let mut candidates = CascadeCandidates::new();
+    for (priority, declaration) in declarations.iter().copied() {
+        // NOTE: var()を置換した結果が解釈できない値や、プロパティが受け付けない値になった場合はunsetとして扱う
+        let value = match declaration.value {
+            Value::Tokens(ref tokens) => variables::substitute(tokens, values)
+                .and_then(parse_value_tokens)
+                .filter(|value| is_supported_declaration(&declaration.name, value))
+                .unwrap_or_else(|| Value::Keyword("unset".to_string())),
+            ref value => value.clone()
+        };
+        for (name, value) in shorthand::expand(&declaration.name, &value) {
+            candidates.entry(name).or_default().push((priority, value));
+        }
+    }
+    candidates
+}
+
+// NOTE: important_passがfalseなら!importantでない宣言だけで、trueなら!importantの宣言が勝つプロパティだけを決める
+fn apply_candidates(values: &mut PropertyMap, candidates: &CascadeCandidates, important_pass: bool, parent_prop_map: &PropertyMap) {
+    for (name, candidates) in candidates {
+        let candidates = if important_pass {
+            match candidates.last() {
+                Some((priority, _)) if priority.is_important() => &candidates[..],
+                _ => continue
+            }
+        } else {
+            &candidates[..candidates.iter().take_while(|(priority, _)| !priority.is_important()).count()]
+        };
+        if candidates.is_empty() {
+            continue;
+        }
+        let value = match resolve_css_wide_keywords(name, candidates) {
+            CascadedValue::Specified(value) => Some(value.clone()),
+            CascadedValue::Inherit => parent_prop_map.get(name).cloned().or_else(|| initial_value(name)),
+            CascadedValue::Initial => initial_value(name)
+        };
+        match value {
+            Some(value) => values.insert(name.clone(), value),
+            None => values.remove(name)
+        };
+    }
+}
+
+// NOTE: 一番優先度の高い値から、CSS全体のキーワードを解決する
+// https://www.w3.org/TR/css-cascade-5/#defaulting-keywords
+fn resolve_css_wide_keywords<'v>(name: &str, candidates: &'v [(&CascadePriority, Value)]) -> CascadedValue<'v> {
+    let unset = if is_inherited(name) {CascadedValue::Inherit} else {CascadedValue::Initial};
+    let (priority, value) = match candidates.last() {
+        Some(candidate) => candidate,
+        None => return unset
+    };
+    let rollback = match css_wide_keyword(value).as_deref() {
+        None => return CascadedValue::Specified(value),
+        Some("initial") => return CascadedValue::Initial,
+        Some("inherit") => return CascadedValue::Inherit,
+        // NOTE: revertは一つ前の出どころまで、revert-layerは一つ前のレイヤーまで、この宣言がなかったものとして戻す
+        // !importantの宣言は通常の宣言とは別の出どころとして扱うので、!importantのrevertは同じ出どころの通常の宣言に戻る
+        // ユーザーエージェントのrevertのように戻る先がない場合はunsetになる
+        // https://www.w3.org/TR/css-cascade-4/#default
+        Some("revert") => candidates.iter().rposition(|(other, _)| if priority.is_important() {
+            !other.is_important() && other.level.origin() <= priority.level.origin()
+        } else {
+            other.level.origin() < priority.level.origin()
+        }),
+        Some("revert-layer") => candidates.iter().rposition(|(other, _)| !other.is_same_layer(priority)),
+        _ => return unset
+    };
+    match rollback {
+        Some(index) => resolve_css_wide_keywords(name, &candidates[..=index]),
+        None => unset
+    }
+}
+
+// NOTE: カスタムプロパティの値はトークンのまま持っている
+fn css_wide_keyword(value: &Value) -> Option<String> {
+    let keyword = match value {
+        Value::Keyword(keyword) => keyword,
+        Value::Tokens(tokens) => match tokens.as_slice() {
+            [Token::Ident(keyword)] => keyword,
+            _ => return None
+        },
+        _ => return None
+    };
+    let keyword = keyword.to_ascii_lowercase();
+    match &*keyword {
+        "initial" | "inherit" | "unset" | "revert" | "revert-layer" => Some(keyword),
+        _ => None
+    }
+}
+
+// NOTE: カスタムプロパティの初期値は「値がない」ことなのでNone
+fn initial_value(name: &str) -> Option<Value> {
+    property_definition(name).filter(|property| !property.is_shorthand()).map(|property| property.initial_value())
+}
+
+// NOTE: em, rem, vw等の相対単位をpxに直してcomputed valueにする
+// font-sizeのemは親のfont-sizeが基準で、それ以外のプロパティのemは自身のfont-sizeが基準になる
+fn compute_values(values: &mut PropertyMap, parent_prop_map: &PropertyMap, root_font_size: Option<f32>, viewport: &Dimensions) {
+    // NOTE: 単位のない0は、数値を受け付けないプロパティでは長さの0になる
+    for (name, value) in values.iter_mut() {
+        if has_unitless_zero(value) && property_definition(name).is_some_and(|property| !property.accepts(&Value::Number(1.0))) {
+            unitless_zero_to_length(value);
+        }
+        // NOTE: 色の名前は色を受け付けるプロパティでだけ色になる
+        if let Some(color) = named_color_keyword(value) {
+            if property_definition(name).is_some_and(|property| property.accepts(&Value::ColorValue(color.clone()))) {
+                *value = Value::ColorValue(color);
             }
         }
     }
-    return values;
+    let parent_font_size = font_size_of(parent_prop_map);
+
+    if let Some(font_size) = values.get_mut("font-size") {
+        let computed = match *font_size {
+            // NOTE: ルート要素のfont-sizeで使われたremは初期値が基準になる
+            Value::Length(f, unit) => Some(resolve_length(f, unit, parent_font_size, root_font_size.unwrap_or(INITIAL_FONT_SIZE), viewport)),
+            Value::Percentage(p) => Some(parent_font_size * p / 100.0),
+            // NOTE: font-sizeのパーセンテージも親のfont-sizeが基準なので式は最後まで計算できる
+            Value::Calc(ref node) => {
+                let mut node = node.clone();
+                node.resolve_lengths(&|f, unit| resolve_length(f, unit, parent_font_size, root_font_size.unwrap_or(INITIAL_FONT_SIZE), viewport));
+                Some(node.evaluate(parent_font_size))
+            },
+            _ => None
+        };
+        if let Some(px) = computed {
+            *font_size = Value::Length(px, Unit::Px);
+        }
+    }
+
+    // NOTE: bolder, lighterとパーセンテージのline-heightは、子が同じ指定で計算し直さないように計算した後の値にしておく
+    if let Some(weight) = values.get_mut("font-weight").filter(|weight| is_relative_font_weight(weight)) {
+        let parent_font_weight = parent_prop_map.get("font-weight").map_or(400.0, |parent| computed::font_weight(parent, 400.0));
+        *weight = Value::Number(computed::font_weight(weight, parent_font_weight));
+    }
+    if let Some(Value::Percentage(p)) = values.get("line-height") {
+        let line_height = Value::Length(font_size_of(values) * p / 100.0, Unit::Px);
+        values.insert("line-height".to_string(), line_height);
+    }
+
+    // NOTE: color自体にcurrentColorが指定された場合は親のcolorを使う
+    if values.get("color").is_some_and(is_current_color) {
+        values.insert("color".to_string(), Value::ColorValue(color_of(parent_prop_map)));
+    }
+    let current_color = color_of(values);
+
+    let font_size = font_size_of(values);
+    let root_font_size = root_font_size.unwrap_or(font_size);
+    for (name, value) in values.iter_mut() {
+        if name == "font-size" {
+            continue;
+        }
+        if name.starts_with("border-") && name.ends_with("-width") {
+            *value = compute_border_width(value);
+        }
+        compute_value(value, &current_color, font_size, root_font_size, viewport);
+    }
+}
+
+fn has_unitless_zero(value: &Value) -> bool {
+    match value {
+        Value::Number(n) => *n == 0.0,
+        Value::List(items, _) => items.iter().any(has_unitless_zero),
+        _ => false
+    }
+}
+
+fn unitless_zero_to_length(value: &mut Value) {
+    match value {
+        Value::Number(n) if *n == 0.0 => *value = Value::Length(0.0, Unit::Px),
+        Value::List(items, _) => items.iter_mut().for_each(unitless_zero_to_length),
+        _ => {}
+    }
+}
+
+fn named_color_keyword(value: &Value) -> Option<Color> {
+    match value {
+        Value::Keyword(keyword) => named_color(keyword),
+        _ => None
+    }
+}
+
+fn is_relative_font_weight(value: &Value) -> bool {
+    matches!(value, Value::Keyword(keyword) if keyword.eq_ignore_ascii_case("bolder") || keyword.eq_ignore_ascii_case("lighter"))
+}
+
+// NOTE: リストの場合は中の値も再帰的にcomputed valueにする
+fn compute_value(value: &mut Value, current_color: &Color, font_size: f32, root_font_size: f32, viewport: &Dimensions) {
+    match *value {
+        Value::Length(f, unit) => *value = Value::Length(resolve_length(f, unit, font_size, root_font_size, viewport), Unit::Px),
+        Value::List(ref mut items, _) => {
+            for item in items {
+                compute_value(item, current_color, font_size, root_font_size, viewport);
+            }
+        },
+        // NOTE: パーセンテージを含まない式はここで長さや数値になる。含む場合はレイアウトで計算する
+        Value::Calc(ref mut node) => {
+            node.resolve_lengths(&|f, unit| resolve_length(f, unit, font_size, root_font_size, viewport));
+            *value = match node.clone().simplify() {
+                CalcNode::Length(px, unit) => Value::Length(px, unit),
+                CalcNode::Percentage(p) => Value::Percentage(p),
+                node => Value::Calc(node)
+            };
+        },
+        ref v if is_current_color(v) => *value = Value::ColorValue(current_color.clone()),
+        _ => {}
+    }
+}
+
+// NOTE: https://www.w3.org/TR/css-backgrounds-3/#border-width
+fn compute_border_width(value: &Value) -> Value {
+    let px = match value {
+        Value::Keyword(keyword) => match &*keyword.to_ascii_lowercase() {
+            "thin" => 1.0,
+            "medium" => 3.0,
+            "thick" => 5.0,
+            _ => return value.clone()
+        },
+        _ => return value.clone()
+    };
+    Value::Length(px, Unit::Px)
+}
+
+fn is_current_color(value: &Value) -> bool {
+    match value {
+        Value::Keyword(keyword) => keyword.eq_ignore_ascii_case("currentcolor"),
+        _ => false
+    }
 }
 
+fn color_of(values: &PropertyMap) -> Color {
+    match values.get("color") {
+        Some(Value::ColorValue(color)) => color.clone(),
+        _ => Color {r: 0, g: 0, b: 0, a: 255}
+    }
+}
 
-type MatchedRule<'a> = (Specificity, &'a Rule);
+fn resolve_length(value: f32, unit: Unit, font_size: f32, root_font_size: f32, viewport: &Dimensions) -> f32 {
+    let vw = viewport.content.width / 100.0;
+    let vh = viewport.content.height / 100.0;
+    match unit {
+        Unit::Em => value * font_size,
+        Unit::Rem => value * root_font_size,
+        // NOTE: フォントのメトリクスを持っていないので仕様にあるフォールバックの0.5emを使う
+        Unit::Ex | Unit::Ch => value * font_size * 0.5,
+        Unit::Vw => value * vw,
+        Unit::Vh => value * vh,
+        Unit::Vmin => value * vw.min(vh),
+        Unit::Vmax => value * vw.max(vh),
+        _ => Value::Length(value, unit).to_px()
+    }
+}
 
-//NOTE: ルールの配列に対してその要素に対応するかをそれぞれ判定
-fn matching_rules<'a>(elem: &ElementData, stylesheet: &'a Stylesheet) -> Vec<MatchedRule<'a>> {
-    stylesheet.rules.iter().filter_map(|rule| match_rule(elem, rule)).collect()
+fn font_size_of(values: &PropertyMap) -> f32 {
+    match values.get("font-size") {
+        Some(Value::Length(f, Unit::Px)) => *f,
+        _ => INITIAL_FONT_SIZE
+    }
 }
 
+// NOTE: (ルールの順番, 一致したセレクタの詳細度, ルール)
+type MatchedRule<'a, 'b> = (usize, Specificity, &'b CascadeRule<'a>);
+
+// NOTE: セレクタを照合する要素。結合子で辿れるように親の要素と、親の子の中でこの要素より前にあるノードを持つ
+// containerはスタイルが決まった後に、この要素が@containerの問い合わせ先になる場合に入れる
+// styleもスタイルが決まった後に入れ、子のノードが継承する値を引き継ぐのに使う
+struct Element<'a, 'b> {
+    data: &'a ElementData,
+    parent: Option<&'b Element<'a, 'b>>,
+    preceding_siblings: &'a [Node],
+    container: Option<QueryContainer>,
+    style: Option<Rc<ComputedStyle>>
+}
 
+impl<'a, 'b> Element<'a, 'b> {
+    fn ancestors(&self) -> impl Iterator<Item = &'b Element<'a, 'b>> {
+        std::iter::successors(self.parent, |element| element.parent)
+    }
 
-// そのルールの持つセレクタに要素が合致するか判定
-fn match_rule<'a>(elem: &ElementData, rule: &'a Rule) -> Option<MatchedRule<'a>> {
-    rule.selectors.iter()
-        .find(|selector| matches(elem, *selector))
-        .map(|selector| (selector.specificity(), rule))
+    // NOTE: 近い方から順に、手前にある兄弟の要素を返す。テキストノードは飛ばす
+    fn previous_siblings(&self) -> impl Iterator<Item = Element<'a, 'b>> {
+        let parent = self.parent;
+        let siblings = self.preceding_siblings;
+        siblings.iter().enumerate().rev().filter_map(move |(i, node)| match node.node_type {
+            NodeType::Element(ref data) => Some(Element {data, parent, preceding_siblings: &siblings[..i], container: None, style: None}),
+            NodeType::Text(_) => None
+        })
+    }
+}
+
+// NOTE: SelectorMapで絞り込んだ候補のセレクタだけを照合し、ルールごとに一致したセレクタの中で一番高い詳細度を返す
+fn matching_rules<'a, 'b>(elem: &Element, context: &StyleContext<'a, 'b>) -> Vec<MatchedRule<'a, 'b>> {
+    let candidates = context.selector_map.candidates(elem.data, &context.ancestor_filter.borrow());
+    let mut matched: Vec<MatchedRule<'a, 'b>> = Vec::new();
+    for (rule_index, selector_index) in candidates {
+        let rule = &context.rules[rule_index];
+        let selector = &rule.rule.selectors[selector_index];
+        if !matches(elem, selector) {
+            continue;
+        }
+        match matched.last_mut() {
+            Some((index, specificity, _)) if *index == rule_index => *specificity = (*specificity).max(selector.specificity()),
+            _ => matched.push((rule_index, selector.specificity(), rule))
+        }
+    }
+    matched.retain(|(_, _, rule)| rule.containers.iter().all(|container_rule| {
+        let containers = elem.ancestors().filter_map(|ancestor| ancestor.container.as_ref());
+        container::matches_container_rule(container_rule, containers, context.device)
+    }));
+    matched
 }
 
 
 // NOTE: そのセレクタがそのElementに合致するか判定
-fn matches(elem: &ElementData, selector: &Selector) -> bool {
+fn matches(elem: &Element, selector: &Selector) -> bool {
     match *selector {
-        Selector::Simple(ref simple_selector) => matches_simple_selector(elem, simple_selector)
+        Selector::Simple(ref simple_selector) => matches_simple_selector(elem, simple_selector),
+        Selector::Complex(ref compounds, ref subject) => matches_simple_selector(elem, subject) && matches_compounds(elem, compounds)
     }
 }
 
-fn matches_simple_selector(elem: &ElementData, selector: &SimpleSelector) -> bool {
+// NOTE: 右の複合セレクタから順に、結合子が指す親や兄弟の要素と照合する
+// 子孫結合子と後続兄弟結合子は、残りも含めて一致する要素が見つかるまで遡る
+fn matches_compounds(elem: &Element, compounds: &[(SimpleSelector, Combinator)]) -> bool {
+    let ((selector, combinator), rest) = match compounds.split_last() {
+        Some(last) => last,
+        None => return true
+    };
+    let matches_rest = |element: &Element| matches_simple_selector(element, selector) && matches_compounds(element, rest);
+    match combinator {
+        Combinator::Child => elem.parent.is_some_and(matches_rest),
+        Combinator::Descendant => elem.ancestors().any(matches_rest),
+        Combinator::NextSibling => elem.previous_siblings().next().is_some_and(|sibling| matches_rest(&sibling)),
+        Combinator::SubsequentSibling => elem.previous_siblings().any(|sibling| matches_rest(&sibling))
+    }
+}
+
+fn matches_simple_selector(elem: &Element, selector: &SimpleSelector) -> bool {
+    let data = elem.data;
     // tag_name.iter(): Optionのiterでtag_nameの存在確認 -> anyにより存在していたうえでtag_nameと合致するかを確認、合致しなければreturn false
-    if selector.tag_name.iter().any(|name| elem.tag_name != *name) {
+    if selector.tag_name.iter().any(|name| data.tag_name != *name) {
         return false;
     }
 
-    if selector.id.iter().any(|id| elem.id() != Some(id)) {
+    if selector.id.iter().any(|id| data.id() != Some(id)) {
         return false;
     }
 
-    let elem_classes = elem.classes();
+    let elem_classes = data.classes();
     if selector.class.iter().any(|class| !elem_classes.contains(&**class)) {
         return false;
     }
 
-    return true;
+    if !selector.attributes.iter().all(|attribute| matches_attribute(data, attribute)) {
+        return false;
+    }
+
+    selector.pseudo_classes.iter().all(|pseudo_class| match pseudo_class {
+        PseudoClass::Is(selectors) | PseudoClass::Where(selectors) => selectors.iter().any(|selector| matches(elem, selector)),
+        PseudoClass::Not(selectors) => !selectors.iter().any(|selector| matches(elem, selector)),
+        PseudoClass::UserAction(_) => false
+    })
+}
+
+// NOTE: HTMLの属性名は大文字と小文字を区別しない
+fn matches_attribute(data: &ElementData, selector: &AttributeSelector) -> bool {
+    let value = match data.attributes.iter().find(|(name, _)| name.eq_ignore_ascii_case(&selector.name)) {
+        Some((_, value)) => value,
+        None => return false
+    };
+    let (matcher, expected) = match selector.matcher {
+        Some((matcher, ref expected)) => (matcher, expected),
+        None => return true
+    };
+    let (value, expected) = if selector.case_insensitive {
+        (value.to_ascii_lowercase(), expected.to_ascii_lowercase())
+    } else {
+        (value.clone(), expected.clone())
+    };
+    match matcher {
+        AttributeMatcher::Equals => value == expected,
+        AttributeMatcher::Includes => value.split_ascii_whitespace().any(|word| word == expected),
+        AttributeMatcher::DashMatch => value == expected || value.starts_with(&format!("{}-", expected)),
+        // NOTE: ^= $= *= は値が空の場合は何にも一致しない
+        AttributeMatcher::Prefix => !expected.is_empty() && value.starts_with(&expected),
+        AttributeMatcher::Suffix => !expected.is_empty() && value.ends_with(&expected),
+        AttributeMatcher::Substring => !expected.is_empty() && value.contains(&expected)
+    }
 }
 
 // NOTE: 処理の手順を自分なりにまとめます
@@ -149,17 +848,21 @@ fn matches_simple_selector(elem: &ElementData, selector: &SimpleSelector) -> boo
 
 // 手順:
 // 以下を子ノードに対して再帰的に繰り返す
+// 0. @mediaの条件をビューポートに対して評価し、有効なRuleだけを平らに並べる
 // 1. Rulesのセレクタの中からそのノードに一致するセレクタを探し、一致するRuleを配列にする
-// 2. そのRuleの配列をセレクタの優先順位の合計に沿ってソートする
+// 2. そのRuleのDeclarationを出どころと!important、レイヤー、一致したセレクタの詳細度、書かれた順に沿ってソートする
 // 3. Ruleの配列からDeclarationのプロパティ名とプロパティの値をHashMapに代入しそれを配列化する
 // 4. 配列にしたDeclarationをspecified_valueとしてNodeのプロパティに入れる.
+// 5. em, vw等の相対単位をfont-sizeやビューポートを基準にpxに直す
 
 #[cfg(test)]
 mod tests {
-    use super::style_tree;
+    use super::{style_tree, animated_style_tree, user_agent_stylesheet, USER_AGENT_CSS};
     use crate::dom::{Node, NodeType, AttrMap, ElementData};
-    use crate::css::{Stylesheet, Rule, Selector, SimpleSelector, Value, Declaration, Unit};
-    use crate::style::{StyledNode, PropertyMap};
+    use crate::css::{CalcNode, Stylesheet, CssRule, Rule, Selector, SimpleSelector, Value, Declaration, Unit, Color, Separator};
+    use crate::style::{StyledNode, PropertyMap, Origin, ComputedStyle};
+    use std::rc::Rc;
+    use crate::layout::Dimensions;
 
 
     fn create_element_node(tag_name: String, attributes: AttrMap, children: Vec<Node>) -> Node {
@@ -173,7 +876,8 @@ mod tests {
     }
 
     fn create_styled_node<'a>(node: &'a Node, specified_values: PropertyMap, children: Vec<StyledNode<'a>>) -> StyledNode<'a> {
-        StyledNode {node, specified_values, children}
+        let style = Rc::new(ComputedStyle::new(&specified_values, None));
+        StyledNode {node, specified_values, style, children}
     }
 
     fn create_simple_selector_rule(selector_data: Vec<(Option<&str>, Option<&str>, Vec<&str>)>, declaration_data: Vec<(&str, Value)>) -> Rule {
@@ -182,7 +886,7 @@ mod tests {
 
         for data in selector_data {
             let selector = Selector::Simple(SimpleSelector {
-                tag_name: data.0.and_then(|x| Some(x.to_string())), id: data.1.and_then(|x| Some(x.to_string())), class: data.2.iter().map(|x|x.to_string()).collect()
+                tag_name: data.0.map(|x| x.to_string()), id: data.1.map(|x| x.to_string()), class: data.2.iter().map(|x|x.to_string()).collect(), attributes: vec![], pseudo_classes: vec![]
             });
             selectors.push(selector);
         }
@@ -190,7 +894,8 @@ mod tests {
         for data in declaration_data {
             let declaration = Declaration {
                 name: data.0.to_string(),
-                value: data.1
+                value: data.1,
+                important: false
             };
             declarations.push(declaration);
         }
@@ -201,12 +906,36 @@ mod tests {
         }
     }
 
+    fn create_stylesheet(rules: Vec<Rule>) -> Stylesheet {
+        Stylesheet {rules: rules.into_iter().map(CssRule::Style).collect()}
+    }
+
+    fn create_viewport() -> Dimensions {
+        let mut viewport: Dimensions = Default::default();
+        viewport.content.width = 800.0;
+        viewport.content.height = 600.0;
+        viewport
+    }
+
+    fn insert_box_longhands(property_map: &mut PropertyMap, shorthand: &str, value: Value) {
+        for side in ["top", "right", "bottom", "left"].iter() {
+            property_map.insert(format!("{}-{}", shorthand, side), value.clone());
+        }
+    }
+
     fn create_inherit_props_map() -> PropertyMap {
         let mut inherit_prop_map = PropertyMap::new();
-        inherit_prop_map.insert("color".to_string(), Value::Keyword("#000000".to_string()));
+        inherit_prop_map.insert("color".to_string(), Value::ColorValue(Color {r: 0, g: 0, b: 0, a: 255}));
         inherit_prop_map.insert("font-size".to_string(), Value::Length(16.0, Unit::Px));
         inherit_prop_map.insert("font-weight".to_string(), Value::Keyword("normal".to_string()));
         inherit_prop_map.insert("line-height".to_string(), Value::Keyword("normal".to_string()));
+        inherit_prop_map.insert("font-style".to_string(), Value::Keyword("normal".to_string()));
+        inherit_prop_map.insert("font-variant".to_string(), Value::Keyword("normal".to_string()));
+        inherit_prop_map.insert("font-stretch".to_string(), Value::Keyword("normal".to_string()));
+        inherit_prop_map.insert("font-family".to_string(), Value::Keyword("serif".to_string()));
+        inherit_prop_map.insert("fill".to_string(), Value::ColorValue(Color {r: 0, g: 0, b: 0, a: 255}));
+        inherit_prop_map.insert("stroke".to_string(), Value::Keyword("none".to_string()));
+        inherit_prop_map.insert("list-style-image".to_string(), Value::Keyword("none".to_string()));
         inherit_prop_map
     }
 
@@ -217,16 +946,16 @@ mod tests {
         let body = create_element_node("body".to_string(), AttrMap::new(), vec![target_element.clone()]);
         let html = create_element_node("html".to_string(), AttrMap::new(), vec![body.clone()]);
 
-        let target_stylesheet = Stylesheet {rules: vec![
+        let target_stylesheet = create_stylesheet(vec![
             create_simple_selector_rule(vec![(Some("div"), None, vec![])], vec![
             ("margin", Value::Keyword("auto".to_string())), ("padding", Value::Length(4.0, Unit::Px))
             ])
-        ]};
-        let styled_html = style_tree(&html, &target_stylesheet);
+        ]);
+        let styled_html = style_tree(&html, &[(Origin::Author, &target_stylesheet)], &create_viewport());
 
         let mut expected_property_map = create_inherit_props_map();
-        expected_property_map.insert("margin".to_string(), Value::Keyword("auto".to_string()));
-        expected_property_map.insert("padding".to_string(), Value::Length(4.0, Unit::Px));
+        insert_box_longhands(&mut expected_property_map, "margin", Value::Keyword("auto".to_string()));
+        insert_box_longhands(&mut expected_property_map, "padding", Value::Length(4.0, Unit::Px));
 
         let expected_styled_target_node = create_styled_node(&target_element, expected_property_map, vec![]);
         let expected_styled_body = create_styled_node(&body, create_inherit_props_map(), vec![expected_styled_target_node]);
@@ -244,17 +973,17 @@ mod tests {
         let body = create_element_node("body".to_string(), AttrMap::new(), vec![target_element.clone()]);
         let html = create_element_node("html".to_string(), AttrMap::new(), vec![body.clone()]);
 
-        let target_stylesheet = Stylesheet {rules: vec![
+        let target_stylesheet = create_stylesheet(vec![
             create_simple_selector_rule(vec![(None, Some(id.clone().as_str()), vec![])], vec![
                 ("margin", Value::Keyword("auto".to_string())), ("padding", Value::Length(4.0, Unit::Px))
             ])
-        ]};
+        ]);
 
-        let styled_html = style_tree(&html, &target_stylesheet);
+        let styled_html = style_tree(&html, &[(Origin::Author, &target_stylesheet)], &create_viewport());
 
         let mut expected_property_map = create_inherit_props_map();
-        expected_property_map.insert("margin".to_string(), Value::Keyword("auto".to_string()));
-        expected_property_map.insert("padding".to_string(), Value::Length(4.0, Unit::Px));
+        insert_box_longhands(&mut expected_property_map, "margin", Value::Keyword("auto".to_string()));
+        insert_box_longhands(&mut expected_property_map, "padding", Value::Length(4.0, Unit::Px));
 
         let expected_styled_target_node = create_styled_node(&target_element, expected_property_map, vec![]);
         let expected_styled_body = create_styled_node(&body, create_inherit_props_map(), vec![expected_styled_target_node]);
@@ -273,17 +1002,17 @@ mod tests {
         let body = create_element_node("body".to_string(), AttrMap::new(), vec![target_element.clone()]);
         let html = create_element_node("html".to_string(), AttrMap::new(), vec![body.clone()]);
 
-        let target_stylesheet = Stylesheet {rules: vec![
+        let target_stylesheet = create_stylesheet(vec![
             create_simple_selector_rule(vec![(None, None, vec![class.as_str()])], vec![
                 ("margin", Value::Keyword("auto".to_string())), ("padding", Value::Length(4.0, Unit::Px))
             ])
-        ]};
+        ]);
 
-        let styled_html = style_tree(&html, &target_stylesheet);
+        let styled_html = style_tree(&html, &[(Origin::Author, &target_stylesheet)], &create_viewport());
 
         let mut expected_property_map = create_inherit_props_map();
-        expected_property_map.insert("margin".to_string(), Value::Keyword("auto".to_string()));
-        expected_property_map.insert("padding".to_string(), Value::Length(4.0, Unit::Px));
+        insert_box_longhands(&mut expected_property_map, "margin", Value::Keyword("auto".to_string()));
+        insert_box_longhands(&mut expected_property_map, "padding", Value::Length(4.0, Unit::Px));
 
         let expected_styled_target_node = create_styled_node(&target_element, expected_property_map, vec![]);
         let expected_styled_body = create_styled_node(&body, create_inherit_props_map(), vec![expected_styled_target_node]);
@@ -300,16 +1029,16 @@ mod tests {
         let body = create_element_node("body".to_string(), AttrMap::new(), vec![target_element.clone()]);
         let html = create_element_node("html".to_string(), AttrMap::new(), vec![body.clone()]);
 
-        let target_stylesheet = Stylesheet {rules: vec![
+        let target_stylesheet = create_stylesheet(vec![
             create_simple_selector_rule(vec![(Some("div"), None, vec![])], vec![
                 ("margin", Value::Keyword("auto".to_string())), ("padding", Value::Length(4.0, Unit::Px))
             ])
-        ]};
-        let styled_html = style_tree(&html, &target_stylesheet);
+        ]);
+        let styled_html = style_tree(&html, &[(Origin::Author, &target_stylesheet)], &create_viewport());
 
         let mut expected_property_map = create_inherit_props_map();
-        expected_property_map.insert("margin".to_string(), Value::Keyword("auto".to_string()));
-        expected_property_map.insert("padding".to_string(), Value::Length(4.0, Unit::Px));
+        insert_box_longhands(&mut expected_property_map, "margin", Value::Keyword("auto".to_string()));
+        insert_box_longhands(&mut expected_property_map, "padding", Value::Length(4.0, Unit::Px));
 
         let expected_styled_text_node = create_styled_node(&text_node, PropertyMap::new(), vec![]);
         let expected_styled_target_node = create_styled_node(&target_element, expected_property_map, vec![expected_styled_text_node]);
@@ -328,17 +1057,17 @@ mod tests {
         let body = create_element_node("body".to_string(), AttrMap::new(), vec![target_element.clone()]);
         let html = create_element_node("html".to_string(), AttrMap::new(), vec![body.clone()]);
 
-        let target_stylesheet = Stylesheet {rules: vec![
+        let target_stylesheet = create_stylesheet(vec![
             create_simple_selector_rule(vec![(Some("div"), None, vec![])], vec![
                 ("margin", Value::Keyword("auto".to_string()))
             ]),
             create_simple_selector_rule(vec![(None, Some(id.as_str()),vec![])], vec![("padding", Value::Length(4.0, Unit::Px))])
-        ]};
-        let styled_html = style_tree(&html, &target_stylesheet);
+        ]);
+        let styled_html = style_tree(&html, &[(Origin::Author, &target_stylesheet)], &create_viewport());
 
         let mut expected_property_map = create_inherit_props_map();
-        expected_property_map.insert("margin".to_string(), Value::Keyword("auto".to_string()));
-        expected_property_map.insert("padding".to_string(), Value::Length(4.0, Unit::Px));
+        insert_box_longhands(&mut expected_property_map, "margin", Value::Keyword("auto".to_string()));
+        insert_box_longhands(&mut expected_property_map, "padding", Value::Length(4.0, Unit::Px));
 
         let expected_styled_target_node = create_styled_node(&target_element, expected_property_map, vec![]);
         let expected_styled_body = create_styled_node(&body, create_inherit_props_map(), vec![expected_styled_target_node]);
@@ -355,15 +1084,15 @@ mod tests {
         let body = create_element_node("body".to_string(), AttrMap::new(), vec![target_element1.clone(), target_element2.clone()]);
         let html = create_element_node("html".to_string(), AttrMap::new(), vec![body.clone()]);
 
-        let target_stylesheet = Stylesheet {rules: vec![
+        let target_stylesheet = create_stylesheet(vec![
             create_simple_selector_rule(vec![(Some("div"), None, vec![])], vec![
                 ("margin", Value::Keyword("auto".to_string()))
             ])
-        ]};
-        let styled_html = style_tree(&html, &target_stylesheet);
+        ]);
+        let styled_html = style_tree(&html, &[(Origin::Author, &target_stylesheet)], &create_viewport());
 
         let mut expected_property_map = create_inherit_props_map();
-        expected_property_map.insert("margin".to_string(), Value::Keyword("auto".to_string()));
+        insert_box_longhands(&mut expected_property_map, "margin", Value::Keyword("auto".to_string()));
 
         let expected_styled_target_node1 = create_styled_node(&target_element1, expected_property_map.clone(), vec![]);
         let expected_styled_target_node2 = create_styled_node(&target_element1, expected_property_map, vec![]);
@@ -379,19 +1108,19 @@ mod tests {
         let body = create_element_node("body".to_string(), AttrMap::new(), vec![target_element.clone()]);
         let html = create_element_node("html".to_string(), AttrMap::new(), vec![body.clone()]);
 
-        let target_stylesheet = Stylesheet {rules: vec![
+        let target_stylesheet = create_stylesheet(vec![
             create_simple_selector_rule(vec![(Some("div"), None, vec![])], vec![
                 ("margin", Value::Keyword("inherit".to_string()))
             ]),
             create_simple_selector_rule(vec![(Some("body"), None, vec![])], vec![
                 ("margin", Value::Length(4.0, Unit::Px))
             ]),
-        ]};
+        ]);
 
-        let styled_html = style_tree(&html, &target_stylesheet);
+        let styled_html = style_tree(&html, &[(Origin::Author, &target_stylesheet)], &create_viewport());
 
         let mut expected_property_map = create_inherit_props_map();
-        expected_property_map.insert("margin".to_string(), Value::Length(4.0, Unit::Px));
+        insert_box_longhands(&mut expected_property_map, "margin", Value::Length(4.0, Unit::Px));
 
         let expected_styled_target_node = create_styled_node(&target_element, expected_property_map.clone(), vec![]);
         let expected_styled_body = create_styled_node(&body, expected_property_map, vec![expected_styled_target_node]);
@@ -402,4 +1131,711 @@ mod tests {
     }
 
 
-}
\ No newline at end of file
+    #[test]
+    fn test_resolve_font_relative_units() {
+        let target_element = create_element_node("div".to_string(), AttrMap::new(), vec![]);
+        let body = create_element_node("body".to_string(), AttrMap::new(), vec![target_element.clone()]);
+        let html = create_element_node("html".to_string(), AttrMap::new(), vec![body.clone()]);
+
+        let target_stylesheet = create_stylesheet(vec![
+            create_simple_selector_rule(vec![(Some("html"), None, vec![])], vec![
+                ("font-size", Value::Length(1.25, Unit::Rem))
+            ]),
+            create_simple_selector_rule(vec![(Some("body"), None, vec![])], vec![
+                ("font-size", Value::Percentage(50.0))
+            ]),
+            create_simple_selector_rule(vec![(Some("div"), None, vec![])], vec![
+                ("font-size", Value::Length(3.0, Unit::Em)), ("margin", Value::Length(0.5, Unit::Em)),
+                ("padding", Value::Length(1.0, Unit::Rem)), ("border-width", Value::Length(2.0, Unit::Ex))
+            ]),
+        ]);
+        let styled_html = style_tree(&html, &[(Origin::Author, &target_stylesheet)], &create_viewport());
+
+        let styled_body = &styled_html.children[0];
+        let styled_div = &styled_body.children[0];
+        assert_eq!(styled_html.value("font-size"), Some(Value::Length(20.0, Unit::Px)));
+        assert_eq!(styled_body.value("font-size"), Some(Value::Length(10.0, Unit::Px)));
+        assert_eq!(styled_div.value("font-size"), Some(Value::Length(30.0, Unit::Px)));
+        assert_eq!(styled_div.value("margin-top"), Some(Value::Length(15.0, Unit::Px)));
+        assert_eq!(styled_div.value("padding-left"), Some(Value::Length(20.0, Unit::Px)));
+        assert_eq!(styled_div.value("border-bottom-width"), Some(Value::Length(30.0, Unit::Px)));
+    }
+
+    #[test]
+    fn test_resolve_viewport_and_absolute_units() {
+        let target_element = create_element_node("div".to_string(), AttrMap::new(), vec![]);
+        let html = create_element_node("html".to_string(), AttrMap::new(), vec![target_element.clone()]);
+
+        let target_stylesheet = create_stylesheet(vec![
+            create_simple_selector_rule(vec![(Some("div"), None, vec![])], vec![
+                ("width", Value::Length(50.0, Unit::Vw)), ("height", Value::Length(10.0, Unit::Vh)),
+                ("margin-left", Value::Length(10.0, Unit::Vmin)), ("margin-right", Value::Length(10.0, Unit::Vmax)),
+                ("padding", Value::Length(1.0, Unit::In))
+            ])
+        ]);
+        let styled_html = style_tree(&html, &[(Origin::Author, &target_stylesheet)], &create_viewport());
+
+        let styled_div = &styled_html.children[0];
+        assert_eq!(styled_div.value("width"), Some(Value::Length(400.0, Unit::Px)));
+        assert_eq!(styled_div.value("height"), Some(Value::Length(60.0, Unit::Px)));
+        assert_eq!(styled_div.value("margin-left"), Some(Value::Length(60.0, Unit::Px)));
+        assert_eq!(styled_div.value("margin-right"), Some(Value::Length(80.0, Unit::Px)));
+        assert_eq!(styled_div.value("padding-right"), Some(Value::Length(96.0, Unit::Px)));
+    }
+
+    #[test]
+    fn test_resolve_current_color() {
+        let target_element = create_element_node("div".to_string(), AttrMap::new(), vec![]);
+        let body = create_element_node("body".to_string(), AttrMap::new(), vec![target_element.clone()]);
+        let html = create_element_node("html".to_string(), AttrMap::new(), vec![body.clone()]);
+
+        let red = Value::ColorValue(Color {r: 255, g: 0, b: 0, a: 255});
+        let target_stylesheet = create_stylesheet(vec![
+            create_simple_selector_rule(vec![(Some("body"), None, vec![])], vec![
+                ("color", red.clone()), ("border-color", Value::Keyword("currentColor".to_string()))
+            ]),
+            create_simple_selector_rule(vec![(Some("div"), None, vec![])], vec![
+                ("color", Value::Keyword("currentcolor".to_string())), ("background", Value::Keyword("currentColor".to_string()))
+            ]),
+            create_simple_selector_rule(vec![(Some("html"), None, vec![])], vec![
+                ("background", Value::Keyword("currentColor".to_string()))
+            ]),
+        ]);
+        let styled_html = style_tree(&html, &[(Origin::Author, &target_stylesheet)], &create_viewport());
+
+        let styled_body = &styled_html.children[0];
+        let styled_div = &styled_body.children[0];
+        assert_eq!(styled_html.value("background-color"), Some(Value::ColorValue(Color {r: 0, g: 0, b: 0, a: 255})));
+        assert_eq!(styled_body.value("border-top-color"), Some(red.clone()));
+        assert_eq!(styled_div.value("color"), Some(red.clone()));
+        assert_eq!(styled_div.value("background-color"), Some(red));
+    }
+
+    #[test]
+    fn test_expand_shorthands_in_declaration_order() {
+        let target_element = create_element_node("div".to_string(), AttrMap::new(), vec![]);
+        let html = create_element_node("html".to_string(), AttrMap::new(), vec![target_element.clone()]);
+
+        let target_stylesheet = create_stylesheet(vec![
+            create_simple_selector_rule(vec![(Some("div"), None, vec![])], vec![
+                ("margin-left", Value::Length(1.0, Unit::Px)),
+                ("margin", Value::List(vec![Value::Length(4.0, Unit::Px), Value::Length(8.0, Unit::Px)], Separator::Space)),
+                ("margin-bottom", Value::Length(2.0, Unit::Px)),
+                ("border", Value::List(vec![Value::Keyword("thin".to_string()), Value::Keyword("solid".to_string())], Separator::Space))
+            ])
+        ]);
+        let styled_html = style_tree(&html, &[(Origin::Author, &target_stylesheet)], &create_viewport());
+
+        let styled_div = &styled_html.children[0];
+        assert_eq!(styled_div.value("margin"), None);
+        assert_eq!(styled_div.value("margin-top"), Some(Value::Length(4.0, Unit::Px)));
+        assert_eq!(styled_div.value("margin-right"), Some(Value::Length(8.0, Unit::Px)));
+        assert_eq!(styled_div.value("margin-bottom"), Some(Value::Length(2.0, Unit::Px)));
+        assert_eq!(styled_div.value("margin-left"), Some(Value::Length(8.0, Unit::Px)));
+        assert_eq!(styled_div.value("border-left-width"), Some(Value::Length(1.0, Unit::Px)));
+        assert_eq!(styled_div.value("border-left-style"), Some(Value::Keyword("solid".to_string())));
+        assert_eq!(styled_div.value("border-left-color"), Some(Value::ColorValue(Color {r: 0, g: 0, b: 0, a: 255})));
+    }
+
+    #[test]
+    fn test_inherit_computed_font_weight_and_line_height() {
+        let span = create_element_node("span".to_string(), AttrMap::new(), vec![]);
+        let p = create_element_node("p".to_string(), AttrMap::new(), vec![span]);
+        let div = create_element_node("div".to_string(), AttrMap::new(), vec![p]);
+        let html = create_element_node("html".to_string(), AttrMap::new(), vec![div]);
+        let stylesheet = crate::css::parse("div {font-weight: bolder; font-size: 10px; line-height: 200%;} p {font-size: 30px;}".to_string());
+        let styled_html = style_tree(&html, &[(Origin::Author, &stylesheet)], &create_viewport());
+
+        // NOTE: 子孫は親が計算した後の値を継承し、bolderや200%を自身の値で計算し直さない
+        let styled_div = &styled_html.children[0];
+        let styled_p = &styled_div.children[0];
+        let styled_span = &styled_p.children[0];
+        for styled_node in [styled_div, styled_p, styled_span] {
+            assert_eq!(styled_node.style.inherited.font_weight, 700.0);
+            assert_eq!(styled_node.style.inherited.line_height, super::computed::LineHeight::Px(20.0));
+        }
+        assert_eq!(styled_span.style.inherited.font_size, 30.0);
+    }
+
+    #[test]
+    fn test_apply_property_names_in_any_case() {
+        let target_element = create_element_node("div".to_string(), AttrMap::new(), vec![]);
+        let html = create_element_node("html".to_string(), AttrMap::new(), vec![target_element.clone()]);
+        let stylesheet = crate::css::parse("div {COLOR: red; Margin-Top: 3px; MARGIN: 1px 2px auto;}".to_string());
+        let styled_html = style_tree(&html, &[(Origin::Author, &stylesheet)], &create_viewport());
+
+        let styled_div = &styled_html.children[0];
+        assert_eq!(styled_div.value("color"), Some(Value::ColorValue(Color {r: 255, g: 0, b: 0, a: 255})));
+        assert_eq!(styled_div.value("margin-top"), Some(Value::Length(1.0, Unit::Px)));
+        assert_eq!(styled_div.value("margin-bottom"), Some(Value::Keyword("auto".to_string())));
+    }
+
+    #[test]
+    fn test_compute_unitless_zero() {
+        let target_element = create_element_node("div".to_string(), AttrMap::new(), vec![]);
+        let html = create_element_node("html".to_string(), AttrMap::new(), vec![target_element.clone()]);
+        let stylesheet = crate::css::parse("div {width: 0; margin: 0 auto; opacity: 0; line-height: 0;}".to_string());
+        let styled_html = style_tree(&html, &[(Origin::Author, &stylesheet)], &create_viewport());
+
+        let styled_div = &styled_html.children[0];
+        assert_eq!(styled_div.value("width"), Some(Value::Length(0.0, Unit::Px)));
+        assert_eq!(styled_div.value("margin-top"), Some(Value::Length(0.0, Unit::Px)));
+        assert_eq!(styled_div.value("opacity"), Some(Value::Number(0.0)));
+        assert_eq!(styled_div.value("line-height"), Some(Value::Number(0.0)));
+    }
+
+    #[test]
+    fn test_expand_math_functions_in_box_shorthands() {
+        let target_element = create_element_node("div".to_string(), AttrMap::new(), vec![]);
+        let html = create_element_node("html".to_string(), AttrMap::new(), vec![target_element.clone()]);
+        let stylesheet = crate::css::parse("div {margin: calc(10px + 5px) max(1em, 4px); padding: min(3px, 1em); border-width: calc(1px * 2) thin;}".to_string());
+        let styled_html = style_tree(&html, &[(Origin::Author, &stylesheet)], &create_viewport());
+
+        let styled_div = &styled_html.children[0];
+        assert_eq!(styled_div.value("margin-top"), Some(Value::Length(15.0, Unit::Px)));
+        assert_eq!(styled_div.value("margin-left"), Some(Value::Length(16.0, Unit::Px)));
+        assert_eq!(styled_div.value("padding-bottom"), Some(Value::Length(3.0, Unit::Px)));
+        assert_eq!(styled_div.value("border-top-width"), Some(Value::Length(2.0, Unit::Px)));
+        assert_eq!(styled_div.value("border-right-width"), Some(Value::Length(1.0, Unit::Px)));
+    }
+
+    #[test]
+    fn test_cascade_by_origin_and_importance() {
+        let target_element = create_element_node("div".to_string(), AttrMap::new(), vec![]);
+        let html = create_element_node("html".to_string(), AttrMap::new(), vec![target_element.clone()]);
+
+        let px = |f: f32| Value::Length(f, Unit::Px);
+        let important = |mut rule: Rule| {
+            rule.declarations.iter_mut().for_each(|declaration| declaration.important = true);
+            rule
+        };
+        let user_agent_stylesheet = create_stylesheet(vec![
+            create_simple_selector_rule(vec![(Some("div"), None, vec![])], vec![("width", px(1.0)), ("height", px(1.0))]),
+            important(create_simple_selector_rule(vec![(Some("div"), None, vec![])], vec![("padding-top", px(1.0))]))
+        ]);
+        let user_stylesheet = create_stylesheet(vec![
+            create_simple_selector_rule(vec![(None, None, vec![])], vec![("width", px(2.0)), ("height", px(2.0)), ("margin-top", px(2.0))]),
+            important(create_simple_selector_rule(vec![(None, None, vec![])], vec![("font-size", px(24.0)), ("padding-top", px(2.0))]))
+        ]);
+        let author_stylesheet = create_stylesheet(vec![
+            important(create_simple_selector_rule(vec![(Some("div"), None, vec![])], vec![("margin-top", px(3.0))])),
+            create_simple_selector_rule(vec![(Some("div"), None, vec![])], vec![("width", px(3.0)), ("font-size", px(12.0)), ("padding-top", px(3.0))])
+        ]);
+        let stylesheets = [(Origin::Author, &author_stylesheet), (Origin::UserAgent, &user_agent_stylesheet), (Origin::User, &user_stylesheet)];
+        let styled_html = style_tree(&html, &stylesheets, &create_viewport());
+
+        let styled_div = &styled_html.children[0];
+        // NOTE: 通常の宣言は author > user > user agent
+        assert_eq!(styled_div.value("width"), Some(px(3.0)));
+        assert_eq!(styled_div.value("height"), Some(px(2.0)));
+        // NOTE: !importantの宣言は user agent > user > author で、全て通常の宣言より強い
+        assert_eq!(styled_div.value("margin-top"), Some(px(3.0)));
+        assert_eq!(styled_div.value("font-size"), Some(px(24.0)));
+        assert_eq!(styled_div.value("padding-top"), Some(px(1.0)));
+    }
+
+    #[test]
+    fn test_important_beats_specificity() {
+        let mut attr = AttrMap::new();
+        attr.insert("id".to_string(), "id1".to_string());
+        let target_element = create_element_node("div".to_string(), attr, vec![]);
+        let html = create_element_node("html".to_string(), AttrMap::new(), vec![target_element.clone()]);
+
+        let mut tag_rule = create_simple_selector_rule(vec![(Some("div"), None, vec![])], vec![("width", Value::Length(1.0, Unit::Px))]);
+        tag_rule.declarations[0].important = true;
+        let target_stylesheet = create_stylesheet(vec![
+            tag_rule,
+            create_simple_selector_rule(vec![(None, Some("id1"), vec![])], vec![("width", Value::Length(2.0, Unit::Px))])
+        ]);
+        let styled_html = style_tree(&html, &[(Origin::Author, &target_stylesheet)], &create_viewport());
+
+        assert_eq!(styled_html.children[0].value("width"), Some(Value::Length(1.0, Unit::Px)));
+    }
+
+    #[test]
+    fn test_evaluate_media_rules_against_viewport() {
+        let target_element = create_element_node("div".to_string(), AttrMap::new(), vec![]);
+        let html = create_element_node("html".to_string(), AttrMap::new(), vec![target_element.clone()]);
+
+        let target_stylesheet = crate::css::parse("
+            div {width: 1px; height: 1px;}
+            @media (max-width: 600px) { div {width: 2px;} }
+            @media screen and (min-width: 601px) and (orientation: landscape) { div {height: 3px;} }
+            @media print { div {height: 4px;} }
+        ".to_string());
+        let styled_html = style_tree(&html, &[(Origin::Author, &target_stylesheet)], &create_viewport());
+        assert_eq!(styled_html.children[0].value("width"), Some(Value::Length(1.0, Unit::Px)));
+        assert_eq!(styled_html.children[0].value("height"), Some(Value::Length(3.0, Unit::Px)));
+
+        let mut narrow_viewport = create_viewport();
+        narrow_viewport.content.width = 400.0;
+        let styled_html = style_tree(&html, &[(Origin::Author, &target_stylesheet)], &narrow_viewport);
+        assert_eq!(styled_html.children[0].value("width"), Some(Value::Length(2.0, Unit::Px)));
+        assert_eq!(styled_html.children[0].value("height"), Some(Value::Length(1.0, Unit::Px)));
+    }
+
+    #[test]
+    fn test_splice_imported_rules_at_import_position() {
+        let target_element = create_element_node("div".to_string(), AttrMap::new(), vec![]);
+        let html = create_element_node("html".to_string(), AttrMap::new(), vec![target_element.clone()]);
+
+        let mut target_stylesheet = crate::css::parse("
+            @import 'base.css';
+            @import 'print.css' print;
+            div {height: 2px;}
+        ".to_string());
+        let imported = [
+            crate::css::parse("div {width: 1px; height: 1px;}".to_string()),
+            crate::css::parse("div {width: 3px;}".to_string())
+        ];
+        for (rule, stylesheet) in target_stylesheet.rules.iter_mut().zip(imported.iter()) {
+            if let CssRule::Import(import_rule) = rule {
+                import_rule.stylesheet = Some(Box::new(stylesheet.clone()));
+            }
+        }
+        let styled_html = style_tree(&html, &[(Origin::Author, &target_stylesheet)], &create_viewport());
+        assert_eq!(styled_html.children[0].value("width"), Some(Value::Length(1.0, Unit::Px)));
+        assert_eq!(styled_html.children[0].value("height"), Some(Value::Length(2.0, Unit::Px)));
+    }
+
+    #[test]
+    fn test_inherit_custom_properties_and_substitute_var() {
+        let mut attr = AttrMap::new();
+        attr.insert("class".to_string(), "child".to_string());
+        let target_element = create_element_node("div".to_string(), attr, vec![]);
+        let html = create_element_node("html".to_string(), AttrMap::new(), vec![target_element.clone()]);
+
+        let target_stylesheet = crate::css::parse("
+            html {--brand-color: #336699; --space: 4px; --loop: var(--loop);}
+            .child {--space: calc-free; margin: var(--gap, 2px) var(--unknown, 8px); color: var(--brand-color);}
+            .child {width: var(--space); height: 1px; height: var(--loop, 3px); padding-top: var(--loop);}
+        ".to_string());
+        let styled_html = style_tree(&html, &[(Origin::Author, &target_stylesheet)], &create_viewport());
+        let styled_div = &styled_html.children[0];
+
+        assert_eq!(styled_div.value("color"), Some(Value::ColorValue(Color {r: 0x33, g: 0x66, b: 0x99, a: 255})));
+        assert_eq!(styled_div.value("margin-top"), Some(Value::Length(2.0, Unit::Px)));
+        assert_eq!(styled_div.value("margin-right"), Some(Value::Length(8.0, Unit::Px)));
+        // NOTE: 置換した値をプロパティが受け付けない場合はunsetになり、初期値が使われる
+        assert_eq!(styled_div.value("width"), Some(Value::Keyword("auto".to_string())));
+        // NOTE: 循環しているカスタムプロパティは無効なのでフォールバックが使われる
+        assert_eq!(styled_div.value("height"), Some(Value::Length(3.0, Unit::Px)));
+        assert_eq!(styled_div.value("padding-top"), Some(Value::Length(0.0, Unit::Px)));
+        assert_eq!(styled_html.value("--loop"), None);
+        assert!(styled_div.value("--brand-color").is_some());
+    }
+
+    #[test]
+    fn test_simplify_math_functions_at_computed_time() {
+        let target_element = create_element_node("div".to_string(), AttrMap::new(), vec![]);
+        let html = create_element_node("html".to_string(), AttrMap::new(), vec![target_element.clone()]);
+
+        let target_stylesheet = crate::css::parse("
+            html {font-size: calc(10px + 50%);}
+            div {font-size: clamp(14px, 2vw, 20px); height: calc(2em + 1in / 2); width: calc(100% - 2 * 1em); margin-left: max(10%, 4px);}
+        ".to_string());
+        let styled_html = style_tree(&html, &[(Origin::Author, &target_stylesheet)], &create_viewport());
+        let styled_div = &styled_html.children[0];
+
+        assert_eq!(styled_html.value("font-size"), Some(Value::Length(18.0, Unit::Px)));
+        assert_eq!(styled_div.value("font-size"), Some(Value::Length(16.0, Unit::Px)));
+        assert_eq!(styled_div.value("height"), Some(Value::Length(80.0, Unit::Px)));
+        assert_eq!(styled_div.value("width"), Some(Value::Calc(CalcNode::Sum(
+            Box::new(CalcNode::Length(-32.0, Unit::Px)), Box::new(CalcNode::Percentage(100.0))
+        ))));
+        assert_eq!(styled_div.value("margin-left"), Some(Value::Calc(CalcNode::Max(vec![
+            CalcNode::Percentage(10.0), CalcNode::Length(4.0, Unit::Px)
+        ]))));
+    }
+
+    #[test]
+    fn test_cascade_by_specificity_then_source_order() {
+        let mut attributes = AttrMap::new();
+        attributes.insert("id".to_string(), "a".to_string());
+        attributes.insert("class".to_string(), "b".to_string());
+        let target_element = create_element_node("div".to_string(), attributes, vec![]);
+        let html = create_element_node("html".to_string(), AttrMap::new(), vec![target_element.clone()]);
+
+        let author_stylesheet = crate::css::parse("
+            div, #a {width: 1px;}
+            .b {width: 2px; height: 2px;}
+            div {height: 3px; margin-top: 1px; margin-top: 4px; padding-top: 1px;}
+        ".to_string());
+        let second_author_stylesheet = crate::css::parse("div {padding-top: 2px;}".to_string());
+        let stylesheets = [(Origin::Author, &author_stylesheet), (Origin::Author, &second_author_stylesheet)];
+        let styled_html = style_tree(&html, &stylesheets, &create_viewport());
+
+        let styled_div = &styled_html.children[0];
+        let px = |f: f32| Value::Length(f, Unit::Px);
+        // NOTE: 一致したセレクタの中で一番高い詳細度がそのルールの詳細度になる
+        assert_eq!(styled_div.value("width"), Some(px(1.0)));
+        // NOTE: 詳細度が高い方が後に書かれたものより優先される
+        assert_eq!(styled_div.value("height"), Some(px(2.0)));
+        // NOTE: 詳細度が同じなら後に書かれたものが優先される。ルールの中でもスタイルシートをまたいでも同じ
+        assert_eq!(styled_div.value("margin-top"), Some(px(4.0)));
+        assert_eq!(styled_div.value("padding-top"), Some(px(2.0)));
+    }
+
+    #[test]
+    fn test_cascade_by_layer() {
+        let mut attributes = AttrMap::new();
+        attributes.insert("id".to_string(), "a".to_string());
+        let target_element = create_element_node("div".to_string(), attributes, vec![]);
+        let html = create_element_node("html".to_string(), AttrMap::new(), vec![target_element.clone()]);
+
+        let mut target_stylesheet = crate::css::parse("
+            @import 'base.css' layer(base);
+            @import 'theme.css' layer(theme);
+            @import 'reset.css' layer(base.reset);
+            div {margin-bottom: 5px; padding-bottom: 5px !important;}
+        ".to_string());
+        let imported = [
+            crate::css::parse("div {width: 1px; height: 1px; margin-top: 1px !important; padding-top: 1px !important;}".to_string()),
+            crate::css::parse("div {height: 2px; margin-top: 2px !important; margin-bottom: 2px; padding-bottom: 2px !important;}".to_string()),
+            crate::css::parse("#a {width: 3px; padding-top: 3px !important;}".to_string())
+        ];
+        for (rule, stylesheet) in target_stylesheet.rules.iter_mut().zip(imported.iter()) {
+            if let CssRule::Import(import_rule) = rule {
+                import_rule.stylesheet = Some(Box::new(stylesheet.clone()));
+            }
+        }
+        let styled_html = style_tree(&html, &[(Origin::Author, &target_stylesheet)], &create_viewport());
+
+        let styled_div = &styled_html.children[0];
+        let px = |f: f32| Value::Length(f, Unit::Px);
+        // NOTE: 通常の宣言は base.reset < base < theme < レイヤーなし の順に強く、詳細度より先に比べる
+        assert_eq!(styled_div.value("width"), Some(px(1.0)));
+        assert_eq!(styled_div.value("height"), Some(px(2.0)));
+        assert_eq!(styled_div.value("margin-bottom"), Some(px(5.0)));
+        // NOTE: !importantの宣言ではレイヤーの順番が逆になる
+        assert_eq!(styled_div.value("margin-top"), Some(px(1.0)));
+        assert_eq!(styled_div.value("padding-top"), Some(px(3.0)));
+        assert_eq!(styled_div.value("padding-bottom"), Some(px(2.0)));
+    }
+
+    #[test]
+    fn test_cascade_by_layer_rules() {
+        let mut attributes = AttrMap::new();
+        attributes.insert("id".to_string(), "a".to_string());
+        let target_element = create_element_node("div".to_string(), attributes, vec![]);
+        let html = create_element_node("html".to_string(), AttrMap::new(), vec![target_element.clone()]);
+        let stylesheet = crate::css::parse("
+            @layer reset, components;
+            @layer components {
+                div {width: 2px; margin-top: 2px !important;}
+                @layer buttons {div {height: 3px; width: 3px;}}
+                div {height: 2px;}
+            }
+            @layer reset {#a {width: 1px; height: 1px; margin-top: 1px !important; margin-bottom: 1px;}}
+            @layer {div {margin-bottom: 4px; padding-top: 4px !important;}}
+            @layer {div {padding-top: 5px !important;}}
+            div {margin-top: 6px !important; padding-top: 6px !important;}
+        ".to_string());
+        let styled_html = style_tree(&html, &[(Origin::Author, &stylesheet)], &create_viewport());
+
+        let styled_div = &styled_html.children[0];
+        let px = |f: f32| Value::Length(f, Unit::Px);
+        // NOTE: 最初の文でresetがcomponentsより前に宣言されているので、後に書かれていても弱い
+        assert_eq!(styled_div.value("width"), Some(px(2.0)));
+        // NOTE: components直属のルールは入れ子のbuttonsより強い
+        assert_eq!(styled_div.value("height"), Some(px(2.0)));
+        // NOTE: 無名のレイヤーは書かれるたびに別のレイヤーになる
+        assert_eq!(styled_div.value("margin-bottom"), Some(px(4.0)));
+        // NOTE: !importantの宣言では先に宣言されたレイヤーほど強く、レイヤーなしが一番弱い
+        assert_eq!(styled_div.value("margin-top"), Some(px(1.0)));
+        assert_eq!(styled_div.value("padding-top"), Some(px(4.0)));
+    }
+
+    #[test]
+    fn test_restyle_after_editing_stylesheet() {
+        let target_element = create_element_node("div".to_string(), AttrMap::new(), vec![]);
+        let html = create_element_node("html".to_string(), AttrMap::new(), vec![target_element.clone()]);
+        let mut stylesheet = crate::css::parse("div {width: 1px;}".to_string());
+        let px = |f: f32| Value::Length(f, Unit::Px);
+
+        let styled_html = style_tree(&html, &[(Origin::Author, &stylesheet)], &create_viewport());
+        assert_eq!(styled_html.children[0].value("width"), Some(px(1.0)));
+
+        stylesheet.insert_rule("div {height: 2px;}", 1).unwrap();
+        if let CssRule::Style(ref mut rule) = stylesheet.rules[0] {
+            rule.set_property("width", "3px", "").unwrap();
+        }
+        let styled_html = style_tree(&html, &[(Origin::Author, &stylesheet)], &create_viewport());
+        assert_eq!(styled_html.children[0].value("width"), Some(px(3.0)));
+        assert_eq!(styled_html.children[0].value("height"), Some(px(2.0)));
+    }
+
+    #[test]
+    fn test_animate_keyframes_at_time() {
+        let target_element = create_element_node("div".to_string(), AttrMap::new(), vec![]);
+        let html = create_element_node("html".to_string(), AttrMap::new(), vec![target_element.clone()]);
+        let stylesheet = crate::css::parse("
+            @keyframes grow {
+                from {width: 0;}
+                50% {height: 10px;}
+                to {width: 100px; color: #ffffff; height: 20px !important;}
+            }
+            div {
+                width: 40px; height: 0; color: #000000; margin-left: 5px !important;
+                animation-name: grow, shift; animation-duration: 2s, 1s; animation-delay: 0s, 1s;
+                animation-timing-function: linear; animation-fill-mode: none, backwards;
+            }
+            @keyframes shift {from {margin-left: 0;} to {margin-left: 10px;}}
+        ".to_string());
+        let px = |f: f32| Value::Length(f, Unit::Px);
+        let stylesheets = [(Origin::Author, &stylesheet)];
+
+        let styled_html = animated_style_tree(&html, &stylesheets, &create_viewport(), 0.5, None);
+        let div = &styled_html.children[0];
+        assert_eq!(div.value("width"), Some(px(25.0)));
+        assert_eq!(div.value("height"), Some(px(5.0)));
+        assert_eq!(div.value("color"), Some(Value::ColorValue(Color {r: 64, g: 64, b: 64, a: 255})));
+        // NOTE: !importantの宣言はアニメーションより強い
+        assert_eq!(div.value("margin-left"), Some(px(5.0)));
+
+        // NOTE: 終わった後はfill-modeがnoneなので元の値に戻る
+        let styled_html = animated_style_tree(&html, &stylesheets, &create_viewport(), 2.5, None);
+        assert_eq!(styled_html.children[0].value("width"), Some(px(40.0)));
+    }
+
+    #[test]
+    fn test_transition_from_before_change_style() {
+        let target_element = create_element_node("div".to_string(), AttrMap::new(), vec![]);
+        let html = create_element_node("html".to_string(), AttrMap::new(), vec![target_element.clone()]);
+        let before = crate::css::parse("div {width: 10px; height: 10px; transition-property: width; transition-duration: 1s; transition-timing-function: linear;}".to_string());
+        let after = crate::css::parse("div {width: 30px; height: 30px;}".to_string());
+        let px = |f: f32| Value::Length(f, Unit::Px);
+        let before_change = style_tree(&html, &[(Origin::Author, &before)], &create_viewport());
+        let stylesheets = [(Origin::Author, &before), (Origin::Author, &after)];
+
+        let styled_html = animated_style_tree(&html, &stylesheets, &create_viewport(), 0.25, Some(&before_change));
+        assert_eq!(styled_html.children[0].value("width"), Some(px(15.0)));
+        assert_eq!(styled_html.children[0].value("height"), Some(px(30.0)));
+        let styled_html = animated_style_tree(&html, &stylesheets, &create_viewport(), 1.0, Some(&before_change));
+        assert_eq!(styled_html.children[0].value("width"), Some(px(30.0)));
+    }
+
+    #[test]
+    fn test_apply_rules_in_supported_supports_blocks() {
+        let target_element = create_element_node("div".to_string(), AttrMap::new(), vec![]);
+        let html = create_element_node("html".to_string(), AttrMap::new(), vec![target_element.clone()]);
+        let stylesheet = crate::css::parse("
+            div {width: 1px; height: 1px;}
+            @supports (display: block) and (not (display: grid)) {div {width: 2px;}}
+            @supports (display: grid) or selector(a::before) {div {height: 2px;}}
+        ".to_string());
+        let px = |f: f32| Value::Length(f, Unit::Px);
+
+        let styled_html = style_tree(&html, &[(Origin::Author, &stylesheet)], &create_viewport());
+        assert_eq!(styled_html.children[0].value("width"), Some(px(2.0)));
+        assert_eq!(styled_html.children[0].value("height"), Some(px(1.0)));
+    }
+
+    #[test]
+    fn test_match_combinators_and_nested_rules() {
+        let element = |tag_name: &str, class: &str, children: Vec<Node>| {
+            let mut attributes = AttrMap::new();
+            attributes.insert("class".to_string(), class.to_string());
+            create_element_node(tag_name.to_string(), attributes, children)
+        };
+        let html = element("html", "", vec![
+            element("div", "card", vec![
+                element("p", "title", vec![]),
+                create_text_node("text"),
+                element("span", "", vec![element("p", "", vec![])]),
+                element("p", "", vec![])
+            ]),
+            element("p", "", vec![])
+        ]);
+        let stylesheet = crate::css::parse("
+            .card {
+                > p {width: 1px;}
+                p {height: 1px;}
+                .title + span {width: 2px;}
+                .title ~ p {height: 2px;}
+                &:hover p {width: 9px;}
+            }
+            div p:not(.title) {margin-top: 1px;}
+            :is(#missing, span) > p {margin-top: 2px;}
+            .card > :where(p) {margin-top: 3px;}
+        ".to_string());
+        let styled_html = style_tree(&html, &[(Origin::Author, &stylesheet)], &create_viewport());
+
+        let px = |f: f32| Value::Length(f, Unit::Px);
+        let card = &styled_html.children[0];
+        let (title, span, last) = (&card.children[0], &card.children[2], &card.children[3]);
+        assert_eq!(title.value("width"), Some(px(1.0)));
+        assert_eq!(title.value("height"), Some(px(1.0)));
+        // NOTE: :where()は詳細度が0なので、:not(.title)に一致しない要素にだけ効く
+        assert_eq!(title.value("margin-top"), Some(px(3.0)));
+        // NOTE: テキストノードを挟んでいても隣の兄弟の要素として扱う
+        assert_eq!(span.value("width"), Some(px(2.0)));
+        assert_eq!(span.children[0].value("width"), None);
+        assert_eq!(span.children[0].value("height"), Some(px(1.0)));
+        // NOTE: :is()は引数の中で一番高い詳細度になるので、#missingに一致しなくても(1, 0, 1)になる
+        assert_eq!(span.children[0].value("margin-top"), Some(px(2.0)));
+        assert_eq!(last.value("height"), Some(px(2.0)));
+        assert_eq!(last.value("margin-top"), Some(px(1.0)));
+        assert_eq!(styled_html.children[1].value("height"), None);
+    }
+
+    #[test]
+    fn test_evaluate_container_queries_against_laid_out_size() {
+        let element = |tag_name: &str, class: &str, children: Vec<Node>| {
+            let mut attributes = AttrMap::new();
+            attributes.insert("class".to_string(), class.to_string());
+            create_element_node(tag_name.to_string(), attributes, children)
+        };
+        let html = element("html", "", vec![
+            element("div", "wide", vec![
+                element("p", "", vec![]),
+                element("section", "inner", vec![element("span", "", vec![])])
+            ]),
+            element("div", "narrow", vec![element("p", "", vec![])]),
+            element("p", "", vec![])
+        ]);
+        let stylesheet = crate::css::parse("
+            html, div, p, section, span {display: block;}
+            .wide {container: card / inline-size; width: 500px;}
+            .narrow {container-type: inline-size; width: 300px;}
+            .inner {container-type: size; width: 50%; height: 40px;}
+            @container (min-width: 400px) {p {height: 10px;}}
+            @container card (max-width: 400px) {p {height: 20px;}}
+            @container card (min-width: 400px) {.inner {width: 300px;}}
+            @container (width >= 300px) and (height = 40px) {span {height: 30px;}}
+        ".to_string());
+        let styled_html = style_tree(&html, &[(Origin::Author, &stylesheet)], &create_viewport());
+
+        let px = |f: f32| Value::Length(f, Unit::Px);
+        let (wide, narrow) = (&styled_html.children[0], &styled_html.children[1]);
+        assert_eq!(wide.children[0].value("height"), Some(px(10.0)));
+        // NOTE: .narrowはcardという名前を持たないので、名前付きの@containerはその外のコンテナに問い合わせる
+        assert_eq!(narrow.children[0].value("height"), None);
+        assert_eq!(styled_html.children[2].value("height"), None);
+        // NOTE: .innerの幅は.wideの大きさで決まり、spanのスタイルは.innerの大きさで決まるので、レイアウトを繰り返して決める
+        assert_eq!(wide.children[1].value("width"), Some(px(300.0)));
+        assert_eq!(wide.children[1].children[0].value("height"), Some(px(30.0)));
+        // NOTE: container-type: inline-sizeの要素は高さを問い合わせられない
+        let stylesheet = crate::css::parse("
+            html, div, p {display: block;}
+            .wide {container-type: inline-size;}
+            @container (min-height: 0) {p {height: 10px;}}
+        ".to_string());
+        let styled_html = style_tree(&html, &[(Origin::Author, &stylesheet)], &create_viewport());
+        assert_eq!(styled_html.children[0].children[0].value("height"), None);
+    }
+
+    #[test]
+    fn test_apply_user_agent_stylesheet() {
+        let (_, diagnostics) = crate::css::parse_with_diagnostics(USER_AGENT_CSS.to_string());
+        assert_eq!(diagnostics, vec![]);
+
+        let element = |tag_name: &str, children: Vec<Node>| create_element_node(tag_name.to_string(), AttrMap::new(), children);
+        let mut hidden = AttrMap::new();
+        hidden.insert("hidden".to_string(), "".to_string());
+        let html = element("html", vec![
+            element("head", vec![element("style", vec![])]),
+            element("body", vec![
+                element("h1", vec![]),
+                create_element_node("p".to_string(), hidden, vec![]),
+                element("ul", vec![element("li", vec![element("ol", vec![])])]),
+                element("span", vec![])
+            ])
+        ]);
+        let user_agent_stylesheet = user_agent_stylesheet();
+        let author_stylesheet = crate::css::parse("h1 {margin-top: 0;}".to_string());
+        let stylesheets = [(Origin::UserAgent, &user_agent_stylesheet), (Origin::Author, &author_stylesheet)];
+        let styled_html = style_tree(&html, &stylesheets, &create_viewport());
+
+        let px = |f: f32| Some(Value::Length(f, Unit::Px));
+        let keyword = |name: &str| Some(Value::Keyword(name.to_string()));
+        let (head, body) = (&styled_html.children[0], &styled_html.children[1]);
+        assert_eq!(styled_html.value("display"), keyword("block"));
+        assert_eq!(head.value("display"), keyword("none"));
+        assert_eq!(body.value("margin-left"), px(8.0));
+        let (h1, p, ul, span) = (&body.children[0], &body.children[1], &body.children[2], &body.children[3]);
+        assert_eq!(h1.value("font-size"), px(32.0));
+        assert_eq!(h1.value("margin-bottom"), px(0.67 * 32.0));
+        assert_eq!(h1.value("margin-top"), px(0.0));
+        assert_eq!(p.value("display"), keyword("none"));
+        assert_eq!(ul.value("margin-top"), px(16.0));
+        assert_eq!(ul.value("padding-left"), px(40.0));
+        // NOTE: リストの中のリストには上下のマージンをつけない
+        assert_eq!(ul.children[0].children[0].value("margin-top"), px(0.0));
+        assert_eq!(span.value("display"), None);
+    }
+
+    #[test]
+    fn test_apply_inline_style_above_selectors() {
+        let mut attributes = AttrMap::new();
+        attributes.insert("id".to_string(), "main".to_string());
+        attributes.insert("style".to_string(), "color: red; margin: 4px; width: 1px; height: 1px !important; padding-top: 1px !important; --x: 2px; padding-left: var(--x); display: ;".to_string());
+        let div = create_element_node("div".to_string(), attributes, vec![]);
+        let stylesheet = crate::css::parse("
+            @layer base {div {padding-top: 9px !important;}}
+            #main#main {color: blue; display: block;}
+            div {width: 9px !important; height: 9px !important;}
+        ".to_string());
+        let styled_div = style_tree(&div, &[(Origin::Author, &stylesheet)], &create_viewport());
+
+        let px = |f: f32| Some(Value::Length(f, Unit::Px));
+        // NOTE: style属性はどんなに詳細度の高いセレクタより優先される
+        assert_eq!(styled_div.value("color"), Some(Value::ColorValue(Color {r: 255, g: 0, b: 0, a: 255})));
+        assert_eq!(styled_div.value("margin-left"), px(4.0));
+        assert_eq!(styled_div.value("padding-left"), px(2.0));
+        // NOTE: ただしスタイルシートの!importantには負ける。両方!importantならstyle属性が優先される
+        assert_eq!(styled_div.value("width"), px(9.0));
+        assert_eq!(styled_div.value("height"), px(1.0));
+        assert_eq!(styled_div.value("padding-top"), px(1.0));
+        // NOTE: 不正な宣言は読み飛ばす
+        assert_eq!(styled_div.value("display"), Some(Value::Keyword("block".to_string())));
+    }
+
+    #[test]
+    fn test_resolve_css_wide_keywords() {
+        let div = create_element_node("div".to_string(), AttrMap::new(), vec![]);
+        let p = create_element_node("p".to_string(), AttrMap::new(), vec![]);
+        let html = create_element_node("html".to_string(), AttrMap::new(), vec![div, p]);
+        let user_agent = crate::css::parse("div {margin-top: 1px; color: green;}".to_string());
+        let user = crate::css::parse("div {margin-left: 2px; padding-top: 4px; padding-top: revert !important; padding-bottom: 5px;}".to_string());
+        let author = crate::css::parse("
+            html {color: red; font-weight: bold; width: 10px;}
+            @layer base {div {padding-left: 3px;}}
+            div {
+                margin-left: revert; margin-top: revert; margin-bottom: revert; color: revert; padding-top: 6px; padding-bottom: 7px; padding-bottom: revert !important;
+                padding-left: revert-layer; padding-right: revert-layer;
+                width: inherit; height: 1px; height: initial; font-weight: unset; margin-right: unset; border-top-width: initial;
+            }
+            p {display: block; --x: 1px; all: initial;}
+        ".to_string());
+        let styled_html = style_tree(&html, &[(Origin::UserAgent, &user_agent), (Origin::User, &user), (Origin::Author, &author)], &create_viewport());
+        let styled_div = &styled_html.children[0];
+        let styled_p = &styled_html.children[1];
+
+        let px = |f: f32| Some(Value::Length(f, Unit::Px));
+        let keyword = |k: &str| Some(Value::Keyword(k.to_string()));
+        // NOTE: revertは作者の宣言がなかったものとして、ユーザーやユーザーエージェントの値に戻す。戻す先がなければunset
+        assert_eq!(styled_div.value("margin-left"), px(2.0));
+        assert_eq!(styled_div.value("margin-top"), px(1.0));
+        assert_eq!(styled_div.value("margin-bottom"), px(0.0));
+        assert_eq!(styled_div.value("color"), Some(Value::ColorValue(Color {r: 0, g: 128, b: 0, a: 255})));
+        // NOTE: !importantのrevertは同じ出どころの通常の宣言に戻す
+        assert_eq!(styled_div.value("padding-top"), px(4.0));
+        assert_eq!(styled_div.value("padding-bottom"), px(7.0));
+        // NOTE: revert-layerは一つ前のレイヤーの値に戻す
+        assert_eq!(styled_div.value("padding-left"), px(3.0));
+        assert_eq!(styled_div.value("padding-right"), px(0.0));
+        // NOTE: inheritは継承しないプロパティでも親の値を使う。initialとunsetはプロパティの定義の初期値になる
+        assert_eq!(styled_div.value("width"), px(10.0));
+        assert_eq!(styled_div.value("height"), keyword("auto"));
+        assert_eq!(styled_div.value("font-weight"), keyword("bold"));
+        assert_eq!(styled_div.value("margin-right"), px(0.0));
+        assert_eq!(styled_div.value("border-top-width"), px(3.0));
+        // NOTE: allはカスタムプロパティ以外の全てのロングハンドに展開する
+        assert_eq!(styled_p.value("display"), keyword("inline"));
+        assert_eq!(styled_p.value("color"), Some(Value::ColorValue(Color {r: 0, g: 0, b: 0, a: 255})));
+        assert_eq!(styled_p.value("font-weight"), keyword("normal"));
+        assert!(styled_p.value("--x").is_some());
+    }
+
+}
@@ -0,0 +1,430 @@
+// NOTE: @keyframesによるアニメーションとトランジションを、ある時刻の値として評価する
+// https://www.w3.org/TR/css-animations-1/
+// https://www.w3.org/TR/css-transitions-1/
+
+use std::collections::HashMap;
+use crate::css::{Value, Unit, Color, CalcNode, KeyframesRule, named_color, parse_value_tokens};
+use super::{PropertyMap, shorthand, variables};
+
+// NOTE: timeは文書を読み込んでからの秒数。アニメーションもトランジションも時刻0に始まったものとして扱う
+pub struct Timeline<'a> {
+    pub keyframes: HashMap<String, &'a KeyframesRule>,
+    pub time: f32
+}
+
+// NOTE: paramsはanimation-*の値を読むためのカスケードし終わった値。
+// アニメーションの値はvaluesに書き込み、!importantの宣言より弱くなるようにその後でカスケードする
+pub fn apply_animations(values: &mut PropertyMap, params: &PropertyMap, timeline: &Timeline) {
+    let names = list_items(params.get("animation-name"));
+    for (i, name) in names.iter().enumerate() {
+        let name = match name {
+            Value::Keyword(name) if !name.eq_ignore_ascii_case("none") => name,
+            Value::String(name) => name,
+            _ => continue
+        };
+        let keyframes_rule = match timeline.keyframes.get(name) {
+            Some(keyframes_rule) => keyframes_rule,
+            None => continue
+        };
+        let param = |name: &str| nth_item(params.get(name), i);
+        let timing_function = param("animation-timing-function").as_ref().and_then(TimingFunction::from_value).unwrap_or(TimingFunction::EASE);
+        // NOTE: 一時停止しているアニメーションは時刻0から進まない
+        let time = if param("animation-play-state").is_some_and(|state| is_keyword(&state, "paused")) {0.0} else {timeline.time};
+        let iteration_count = match param("animation-iteration-count") {
+            Some(Value::Keyword(ref keyword)) if keyword.eq_ignore_ascii_case("infinite") => f32::INFINITY,
+            Some(value) => as_number(&value).unwrap_or(1.0),
+            None => 1.0
+        };
+        let progress = directed_progress(
+            time - seconds(param("animation-delay")),
+            seconds(param("animation-duration")),
+            iteration_count,
+            &keyword_or(param("animation-direction"), "normal"),
+            &keyword_or(param("animation-fill-mode"), "none")
+        );
+        if let Some(progress) = progress {
+            let animated = keyframe_values(keyframes_rule, values, progress, &timing_function);
+            values.extend(animated);
+        }
+    }
+}
+
+// NOTE: before_changeは変化する前のcomputed value。変化したプロパティのうちtransition-propertyに含まれていて補間できるものを
+// 変化する前の値から今の値へ向けて補間する。他の全ての宣言より強い
+pub fn apply_transitions(values: &mut PropertyMap, before_change: &PropertyMap, time: f32) {
+    let properties = list_items(values.get("transition-property"));
+    let mut transitions = Vec::new();
+    for (i, property) in properties.iter().enumerate() {
+        let names: Vec<String> = match property {
+            Value::Keyword(keyword) if keyword.eq_ignore_ascii_case("none") => continue,
+            Value::Keyword(keyword) if keyword.eq_ignore_ascii_case("all") => values.keys()
+                .filter(|name| is_animatable(name))
+                .cloned().collect(),
+            Value::Keyword(keyword) => shorthand::longhand_names(keyword).unwrap_or_else(|| vec![keyword.clone()]),
+            _ => continue
+        };
+        let param = |name: &str| nth_item(values.get(name), i);
+        let timing_function = param("transition-timing-function").as_ref().and_then(TimingFunction::from_value).unwrap_or(TimingFunction::EASE);
+        let duration = seconds(param("transition-duration"));
+        let elapsed = time - seconds(param("transition-delay"));
+        for name in names {
+            if let (Some(from), Some(to)) = (before_change.get(&name), values.get(&name)) {
+                if from == to || duration <= 0.0 {
+                    continue;
+                }
+                // NOTE: 遅延している間は変化する前の値のまま
+                let progress = timing_function.apply((elapsed / duration).clamp(0.0, 1.0));
+                if let Some(value) = interpolate(from, to, progress) {
+                    transitions.push((name, value));
+                }
+            }
+        }
+    }
+    values.extend(transitions);
+}
+
+// NOTE: アニメーション自体やカスタムプロパティはアニメーションしない
+fn is_animatable(name: &str) -> bool {
+    !name.starts_with("animation") && !name.starts_with("transition") && !variables::is_custom_property(name)
+}
+
+// NOTE: 現在の繰り返しの中での進み具合(0~1)を、animation-directionを反映して返す
+// アニメーションが効いていない時(遅延中や終わった後でfill-modeで延長されていない時)はNone
+fn directed_progress(elapsed: f32, duration: f32, iteration_count: f32, direction: &str, fill_mode: &str) -> Option<f32> {
+    let active_duration = if duration > 0.0 {duration * iteration_count} else {0.0};
+    let fills_backwards = fill_mode == "backwards" || fill_mode == "both";
+    let fills_forwards = fill_mode == "forwards" || fill_mode == "both";
+    let (iteration, progress) = if elapsed < 0.0 {
+        if !fills_backwards {
+            return None;
+        }
+        (0.0, 0.0)
+    } else if elapsed >= active_duration {
+        if !fills_forwards {
+            return None;
+        }
+        // NOTE: 終わった時点の値。繰り返し回数が整数なら最後の繰り返しの終わりになる
+        let iteration = iteration_count.floor();
+        if iteration_count == iteration && iteration > 0.0 {(iteration - 1.0, 1.0)} else {(iteration, iteration_count - iteration)}
+    } else {
+        let overall = elapsed / duration;
+        (overall.floor(), overall - overall.floor())
+    };
+    let reversed = match direction {
+        "reverse" => true,
+        "alternate" => iteration % 2.0 == 1.0,
+        "alternate-reverse" => iteration % 2.0 == 0.0,
+        _ => false
+    };
+    Some(if reversed {1.0 - progress} else {progress})
+}
+
+// NOTE: キーフレームの値をプロパティごとに並べ、progressを挟む2つのキーフレームの間を補間する
+// 0%や100%のキーフレームがない場合はアニメーションしていない時の値を使う
+fn keyframe_values(keyframes_rule: &KeyframesRule, values: &PropertyMap, progress: f32, default_timing_function: &TimingFunction) -> PropertyMap {
+    let mut properties: HashMap<String, Vec<(f32, Value, Option<TimingFunction>)>> = HashMap::new();
+    for keyframe in &keyframes_rule.keyframes {
+        let timing_function = keyframe.declarations.iter().rev()
+            .find(|declaration| declaration.name == "animation-timing-function")
+            .and_then(|declaration| TimingFunction::from_value(&declaration.value));
+        for declaration in keyframe.declarations.iter().filter(|declaration| is_animatable(&declaration.name)) {
+            // NOTE: var()は要素のカスタムプロパティで置換する
+            let value = match declaration.value {
+                Value::Tokens(ref tokens) => match variables::substitute(tokens, values).and_then(parse_value_tokens) {
+                    Some(value) => value,
+                    None => continue
+                },
+                ref value => value.clone()
+            };
+            for (name, value) in shorthand::expand(&declaration.name, &value) {
+                for offset in &keyframe.offsets {
+                    let keyframes = properties.entry(name.clone()).or_default();
+                    // NOTE: 同じ位置のキーフレームは後に書かれたものが優先される
+                    keyframes.retain(|(o, _, _)| o != &(offset / 100.0));
+                    keyframes.push((offset / 100.0, value.clone(), timing_function.clone()));
+                }
+            }
+        }
+    }
+
+    let mut animated = PropertyMap::new();
+    for (name, mut keyframes) in properties {
+        keyframes.sort_by(|(a, _, _), (b, _, _)| a.total_cmp(b));
+        if let Some(underlying) = values.get(&name) {
+            if keyframes[0].0 > 0.0 {
+                keyframes.insert(0, (0.0, underlying.clone(), None));
+            }
+            if keyframes[keyframes.len() - 1].0 < 1.0 {
+                keyframes.push((1.0, underlying.clone(), None));
+            }
+        }
+        let next = keyframes.iter().position(|(offset, _, _)| *offset > progress).unwrap_or(keyframes.len());
+        let value = match (next.checked_sub(1).map(|i| &keyframes[i]), keyframes.get(next)) {
+            (Some((from_offset, from, timing_function)), Some((to_offset, to, _))) => {
+                let local_progress = (progress - from_offset) / (to_offset - from_offset);
+                let local_progress = timing_function.as_ref().unwrap_or(default_timing_function).apply(local_progress);
+                // NOTE: 補間できない値は途中で切り替わる
+                interpolate(from, to, local_progress)
+                    .unwrap_or_else(|| if local_progress < 0.5 {from.clone()} else {to.clone()})
+            },
+            (Some((_, value, _)), None) | (None, Some((_, value, _))) => value.clone(),
+            (None, None) => continue
+        };
+        animated.insert(name, value);
+    }
+    animated
+}
+
+// NOTE: 長さ、パーセンテージ、数値、色と、それらを同じ形で並べたリストや関数を補間する。補間できない組み合わせはNone
+// 単位の違う長さやパーセンテージとの組み合わせはcalc()にしてcomputed valueの段階で計算する
+pub fn interpolate(from: &Value, to: &Value, progress: f32) -> Option<Value> {
+    let lerp = |a: f32, b: f32| a + (b - a) * progress;
+    let value = match (from, to) {
+        (from, to) if from == to => from.clone(),
+        (Value::Length(a, a_unit), Value::Length(b, b_unit)) if a_unit == b_unit => Value::Length(lerp(*a, *b), *a_unit),
+        (Value::Percentage(a), Value::Percentage(b)) => Value::Percentage(lerp(*a, *b)),
+        (Value::Number(a), Value::Number(b)) => Value::Number(lerp(*a, *b)),
+        (Value::Time(a), Value::Time(b)) => Value::Time(lerp(*a, *b)),
+        (Value::Angle(a), Value::Angle(b)) => Value::Angle(lerp(*a, *b)),
+        (from, to) if as_color(from).is_some() && as_color(to).is_some() => {
+            let (a, b) = (as_color(from)?, as_color(to)?);
+            let channel = |a: u8, b: u8| lerp(a as f32, b as f32).round().clamp(0.0, 255.0) as u8;
+            Value::ColorValue(Color {r: channel(a.r, b.r), g: channel(a.g, b.g), b: channel(a.b, b.b), a: channel(a.a, b.a)})
+        },
+        (Value::List(a, a_separator), Value::List(b, b_separator)) if a_separator == b_separator && a.len() == b.len() => {
+            let items = a.iter().zip(b).map(|(a, b)| interpolate(a, b, progress)).collect::<Option<Vec<Value>>>()?;
+            Value::List(items, *a_separator)
+        },
+        (Value::Function {name: a_name, args: a}, Value::Function {name: b_name, args: b}) if a_name.eq_ignore_ascii_case(b_name) && a.len() == b.len() => {
+            let args = a.iter().zip(b).map(|(a, b)| interpolate(a, b, progress)).collect::<Option<Vec<Value>>>()?;
+            Value::Function {name: a_name.clone(), args}
+        },
+        (from, to) => {
+            let (from, to) = (length_percentage_node(from)?, length_percentage_node(to)?);
+            let scale = |node: CalcNode, factor: f32| CalcNode::Product(Box::new(node), Box::new(CalcNode::Number(factor)));
+            Value::Calc(CalcNode::Sum(Box::new(scale(from, 1.0 - progress)), Box::new(scale(to, progress))))
+        }
+    };
+    Some(value)
+}
+
+// NOTE: キーフレームの値はcomputed valueになる前なので、redのような色の名前もここで色にする
+fn as_color(value: &Value) -> Option<Color> {
+    match value {
+        Value::ColorValue(color) => Some(color.clone()),
+        Value::Keyword(keyword) => named_color(keyword),
+        _ => None
+    }
+}
+
+fn length_percentage_node(value: &Value) -> Option<CalcNode> {
+    match value {
+        Value::Length(f, unit) => Some(CalcNode::Length(*f, *unit)),
+        // NOTE: width: 0 のような単位のない0は長さの0として補間する
+        Value::Number(n) if *n == 0.0 => Some(CalcNode::Length(0.0, Unit::Px)),
+        Value::Percentage(p) => Some(CalcNode::Percentage(*p)),
+        Value::Calc(node) if !node.is_number() => Some(node.clone()),
+        _ => None
+    }
+}
+
+// NOTE: https://www.w3.org/TR/css-easing-1/
+#[derive(Clone, Debug, PartialEq)]
+enum TimingFunction {
+    CubicBezier(f32, f32, f32, f32),
+    // NOTE: (段数, 最初に跳ぶか, 最後に跳ぶか)
+    Steps(u32, bool, bool)
+}
+
+impl TimingFunction {
+    const EASE: TimingFunction = TimingFunction::CubicBezier(0.25, 0.1, 0.25, 1.0);
+
+    fn from_value(value: &Value) -> Option<TimingFunction> {
+        let timing_function = match value {
+            Value::Keyword(keyword) => match &*keyword.to_ascii_lowercase() {
+                "linear" => TimingFunction::CubicBezier(0.0, 0.0, 1.0, 1.0),
+                "ease" => TimingFunction::EASE,
+                "ease-in" => TimingFunction::CubicBezier(0.42, 0.0, 1.0, 1.0),
+                "ease-out" => TimingFunction::CubicBezier(0.0, 0.0, 0.58, 1.0),
+                "ease-in-out" => TimingFunction::CubicBezier(0.42, 0.0, 0.58, 1.0),
+                "step-start" => TimingFunction::Steps(1, true, false),
+                "step-end" => TimingFunction::Steps(1, false, true),
+                _ => return None
+            },
+            Value::Function {name, args} if name.eq_ignore_ascii_case("cubic-bezier") && args.len() == 4 => {
+                let args = args.iter().map(as_number).collect::<Option<Vec<f32>>>()?;
+                if !(0.0..=1.0).contains(&args[0]) || !(0.0..=1.0).contains(&args[2]) {
+                    return None;
+                }
+                TimingFunction::CubicBezier(args[0], args[1], args[2], args[3])
+            },
+            Value::Function {name, args} if name.eq_ignore_ascii_case("steps") && (1..=2).contains(&args.len()) => {
+                let steps = as_number(&args[0])?;
+                let (jump_start, jump_end) = match args.get(1) {
+                    None => (false, true),
+                    Some(Value::Keyword(position)) => match &*position.to_ascii_lowercase() {
+                        "jump-start" | "start" => (true, false),
+                        "jump-end" | "end" => (false, true),
+                        "jump-none" => (false, false),
+                        "jump-both" => (true, true),
+                        _ => return None
+                    },
+                    Some(_) => return None
+                };
+                let minimum = if jump_start || jump_end {1.0} else {2.0};
+                if steps < minimum || steps.fract() != 0.0 {
+                    return None;
+                }
+                TimingFunction::Steps(steps as u32, jump_start, jump_end)
+            },
+            _ => return None
+        };
+        Some(timing_function)
+    }
+
+    fn apply(&self, progress: f32) -> f32 {
+        match *self {
+            TimingFunction::CubicBezier(x1, y1, x2, y2) => {
+                if progress <= 0.0 || progress >= 1.0 {
+                    return progress;
+                }
+                // NOTE: xがprogressになる媒介変数tを二分法で探してからyを求める
+                let bezier = |t: f32, p1: f32, p2: f32| 3.0 * (1.0 - t) * (1.0 - t) * t * p1 + 3.0 * (1.0 - t) * t * t * p2 + t * t * t;
+                let (mut low, mut high) = (0.0, 1.0);
+                for _ in 0..32 {
+                    let middle = (low + high) / 2.0;
+                    if bezier(middle, x1, x2) < progress {
+                        low = middle;
+                    } else {
+                        high = middle;
+                    }
+                }
+                bezier((low + high) / 2.0, y1, y2)
+            },
+            TimingFunction::Steps(steps, jump_start, jump_end) => {
+                let steps = steps as f32;
+                let mut step = (progress * steps).floor();
+                if jump_start {
+                    step += 1.0;
+                }
+                if progress >= 0.0 && step < 0.0 {
+                    step = 0.0;
+                }
+                let jumps = steps + if jump_start {0.0} else {-1.0} + if jump_end {1.0} else {0.0};
+                if progress <= 1.0 && step > jumps {
+                    step = jumps;
+                }
+                step / jumps
+            }
+        }
+    }
+}
+
+// NOTE: animation-name: a, b; のようなカンマ区切りのリストを要素の配列にする
+fn list_items(value: Option<&Value>) -> Vec<Value> {
+    match value {
+        Some(Value::List(items, crate::css::Separator::Comma)) => items.clone(),
+        Some(value) => vec![value.clone()],
+        None => vec![]
+    }
+}
+
+// NOTE: リストの長さがanimation-nameより短い場合は繰り返して使う
+fn nth_item(value: Option<&Value>, i: usize) -> Option<Value> {
+    let items = list_items(value);
+    if items.is_empty() {None} else {Some(items[i % items.len()].clone())}
+}
+
+fn seconds(value: Option<Value>) -> f32 {
+    match value {
+        Some(Value::Time(seconds)) => seconds,
+        _ => 0.0
+    }
+}
+
+fn as_number(value: &Value) -> Option<f32> {
+    match *value {
+        Value::Number(n) => Some(n),
+        _ => None
+    }
+}
+
+fn is_keyword(value: &Value, keyword: &str) -> bool {
+    matches!(value, Value::Keyword(k) if k.eq_ignore_ascii_case(keyword))
+}
+
+fn keyword_or(value: Option<Value>, default: &str) -> String {
+    match value {
+        Some(Value::Keyword(keyword)) => keyword.to_ascii_lowercase(),
+        _ => default.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{interpolate, directed_progress, TimingFunction};
+    use crate::css::{Value, Unit, Color, CalcNode, Separator};
+
+    #[test]
+    fn interpolate_values() {
+        let length = |px: f32| Value::Length(px, Unit::Px);
+        assert_eq!(interpolate(&length(0.0), &length(10.0), 0.25), Some(length(2.5)));
+        assert_eq!(interpolate(
+            &Value::ColorValue(Color {r: 0, g: 0, b: 0, a: 255}),
+            &Value::ColorValue(Color {r: 255, g: 100, b: 0, a: 255}),
+            0.5
+        ), Some(Value::ColorValue(Color {r: 128, g: 50, b: 0, a: 255})));
+        assert_eq!(interpolate(
+            &Value::Keyword("black".to_string()),
+            &Value::Keyword("White".to_string()),
+            0.5
+        ), Some(Value::ColorValue(Color {r: 128, g: 128, b: 128, a: 255})));
+        assert_eq!(interpolate(
+            &Value::List(vec![length(0.0), Value::Keyword("auto".to_string())], Separator::Space),
+            &Value::List(vec![length(4.0), Value::Keyword("auto".to_string())], Separator::Space),
+            0.5
+        ), Some(Value::List(vec![length(2.0), Value::Keyword("auto".to_string())], Separator::Space)));
+        // NOTE: 単位の違う長さはcalc()になる
+        assert_eq!(interpolate(&length(10.0), &Value::Percentage(50.0), 0.25), Some(Value::Calc(CalcNode::Sum(
+            Box::new(CalcNode::Product(Box::new(CalcNode::Length(10.0, Unit::Px)), Box::new(CalcNode::Number(0.75)))),
+            Box::new(CalcNode::Product(Box::new(CalcNode::Percentage(50.0)), Box::new(CalcNode::Number(0.25))))
+        ))));
+        assert_eq!(interpolate(&Value::Keyword("block".to_string()), &Value::Keyword("none".to_string()), 0.5), None);
+        assert_eq!(interpolate(&length(0.0), &Value::ColorValue(Color {r: 0, g: 0, b: 0, a: 255}), 0.5), None);
+    }
+
+    #[test]
+    fn timing_functions() {
+        let function = |name: &str, args: Vec<Value>| TimingFunction::from_value(&Value::Function {name: name.to_string(), args}).unwrap();
+        let linear = TimingFunction::from_value(&Value::Keyword("linear".to_string())).unwrap();
+        assert!((linear.apply(0.3) - 0.3).abs() < 1e-4);
+        let ease = TimingFunction::EASE;
+        assert!((ease.apply(0.5) - 0.8024).abs() < 1e-3);
+        assert_eq!(ease.apply(0.0), 0.0);
+        assert_eq!(ease.apply(1.0), 1.0);
+
+        let steps = function("steps", vec![Value::Number(4.0)]);
+        assert_eq!(steps.apply(0.3), 0.25);
+        assert_eq!(steps.apply(1.0), 1.0);
+        let jump_start = function("steps", vec![Value::Number(2.0), Value::Keyword("start".to_string())]);
+        assert_eq!(jump_start.apply(0.0), 0.5);
+        let jump_none = function("steps", vec![Value::Number(3.0), Value::Keyword("jump-none".to_string())]);
+        assert_eq!(jump_none.apply(0.5), 0.5);
+
+        assert_eq!(TimingFunction::from_value(&Value::Function {name: "steps".to_string(), args: vec![Value::Number(1.0), Value::Keyword("jump-none".to_string())]}), None);
+        assert_eq!(TimingFunction::from_value(&Value::Function {name: "cubic-bezier".to_string(), args: vec![Value::Number(2.0), Value::Number(0.0), Value::Number(1.0), Value::Number(1.0)]}), None);
+    }
+
+    #[test]
+    fn progress_with_direction_and_fill_mode() {
+        assert_eq!(directed_progress(0.5, 2.0, 1.0, "normal", "none"), Some(0.25));
+        assert_eq!(directed_progress(-1.0, 2.0, 1.0, "normal", "none"), None);
+        assert_eq!(directed_progress(-1.0, 2.0, 1.0, "reverse", "backwards"), Some(1.0));
+        assert_eq!(directed_progress(3.0, 2.0, 1.0, "normal", "none"), None);
+        assert_eq!(directed_progress(3.0, 2.0, 1.0, "normal", "forwards"), Some(1.0));
+        // NOTE: alternateは奇数回目の繰り返しで逆向きに進む
+        assert_eq!(directed_progress(2.5, 2.0, f32::INFINITY, "alternate", "none"), Some(0.75));
+        assert_eq!(directed_progress(10.0, 2.0, 2.0, "alternate", "both"), Some(0.0));
+        assert_eq!(directed_progress(10.0, 2.0, 1.5, "normal", "forwards"), Some(0.5));
+    }
+}
@@ -0,0 +1,346 @@
+// NOTE: レイアウトと描画が読む、型の付いたcomputed value
+// カスケードした結果のPropertyMapから作り、継承するプロパティは親と同じならRcで共有する
+// https://www.w3.org/TR/css-cascade-4/#computed
+
+use std::rc::Rc;
+use crate::css::{Value, Color, CalcNode};
+use super::PropertyMap;
+use super::container::{ContainerType, container_type};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Display {
+    Inline,
+    Block,
+    None
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Position {
+    Static,
+    Relative,
+    Absolute,
+    Fixed,
+    Sticky
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Float {
+    None,
+    Left,
+    Right
+}
+
+// NOTE: pxに直した長さか、containing blockの大きさが分かるまで決まらないパーセンテージ
+#[derive(Clone, Debug, PartialEq)]
+pub enum LengthPercentage {
+    Px(f32),
+    Percentage(f32),
+    Calc(CalcNode)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum LengthPercentageOrAuto {
+    Auto,
+    LengthPercentage(LengthPercentage)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LineHeight {
+    Normal,
+    Number(f32),
+    Px(f32)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sides<T> {
+    pub top: T,
+    pub right: T,
+    pub bottom: T,
+    pub left: T
+}
+
+// NOTE: 継承するプロパティ。親と値が同じ場合は親のものを共有する
+#[allow(dead_code)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct InheritedStyle {
+    pub color: Color,
+    pub font_size: f32,
+    pub font_weight: f32,
+    pub line_height: LineHeight
+}
+
+// NOTE: position, float, font-weight, line-heightはまだレイアウトで使っていない
+#[allow(dead_code)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct ComputedStyle {
+    pub display: Display,
+    pub position: Position,
+    pub float: Float,
+    pub width: LengthPercentageOrAuto,
+    pub height: LengthPercentageOrAuto,
+    pub margin: Sides<LengthPercentageOrAuto>,
+    pub padding: Sides<LengthPercentage>,
+    // NOTE: border-styleがnoneかhiddenの辺は0になる
+    pub border_width: Sides<f32>,
+    // NOTE: 指定されていない辺は初期値のcurrentcolor、つまりcolorの値になる
+    pub border_color: Sides<Color>,
+    pub background_color: Color,
+    pub container_type: Option<ContainerType>,
+    pub inherited: Rc<InheritedStyle>
+}
+
+const TRANSPARENT: Color = Color {r: 0, g: 0, b: 0, a: 0};
+
+impl LengthPercentage {
+    // NOTE: パーセンテージをpercentage_basis(containing blockの幅等)に対する割合としてpxに直す
+    pub fn resolve(&self, percentage_basis: f32) -> f32 {
+        match *self {
+            LengthPercentage::Px(px) => px,
+            LengthPercentage::Percentage(p) => percentage_basis * p / 100.0,
+            LengthPercentage::Calc(ref node) => node.evaluate(percentage_basis)
+        }
+    }
+}
+
+impl LengthPercentageOrAuto {
+    // NOTE: autoの場合はNone
+    pub fn resolve(&self, percentage_basis: f32) -> Option<f32> {
+        match *self {
+            LengthPercentageOrAuto::Auto => None,
+            LengthPercentageOrAuto::LengthPercentage(ref length) => Some(length.resolve(percentage_basis))
+        }
+    }
+}
+
+impl ComputedStyle {
+    // NOTE: valuesは単位をpxに直した後の値。テキストノードのように値を持たない場合は空のvaluesで親から継承する
+    pub fn new(values: &PropertyMap, parent: Option<&ComputedStyle>) -> ComputedStyle {
+        let inherited = compute_inherited(values, parent.map(|parent| &*parent.inherited));
+        let keyword = |name: &str| match values.get(name) {
+            Some(Value::Keyword(keyword)) => Some(keyword.to_ascii_lowercase()),
+            _ => None
+        };
+        ComputedStyle {
+            display: match keyword("display").as_deref() {
+                Some("block") => Display::Block,
+                Some("none") => Display::None,
+                _ => Display::Inline
+            },
+            position: match keyword("position").as_deref() {
+                Some("relative") => Position::Relative,
+                Some("absolute") => Position::Absolute,
+                Some("fixed") => Position::Fixed,
+                Some("sticky") => Position::Sticky,
+                _ => Position::Static
+            },
+            float: match keyword("float").as_deref() {
+                Some("left") => Float::Left,
+                Some("right") => Float::Right,
+                _ => Float::None
+            },
+            width: length_percentage_or_auto(values.get("width"), LengthPercentageOrAuto::Auto),
+            height: length_percentage_or_auto(values.get("height"), LengthPercentageOrAuto::Auto),
+            margin: sides(values, "margin", "", |value| length_percentage_or_auto(value, zero())),
+            padding: sides(values, "padding", "", |value| length_percentage(value).unwrap_or(LengthPercentage::Px(0.0))),
+            border_width: border_widths(values),
+            border_color: sides(values, "border", "-color", |value| color(value).unwrap_or_else(|| inherited.color.clone())),
+            background_color: color(values.get("background-color")).unwrap_or(TRANSPARENT),
+            container_type: container_type(values),
+            inherited: match parent {
+                Some(parent) if *parent.inherited == inherited => Rc::clone(&parent.inherited),
+                _ => Rc::new(inherited)
+            }
+        }
+    }
+}
+
+// NOTE: 値が指定されていなければ親の値を、ルート要素なら初期値を使う
+fn compute_inherited(values: &PropertyMap, parent: Option<&InheritedStyle>) -> InheritedStyle {
+    let parent_font_size = parent.map_or(super::INITIAL_FONT_SIZE, |parent| parent.font_size);
+    let parent_font_weight = parent.map_or(400.0, |parent| parent.font_weight);
+    let font_size = match values.get("font-size") {
+        Some(value @ Value::Length(..)) => value.to_px(),
+        _ => parent_font_size
+    };
+    InheritedStyle {
+        color: color(values.get("color")).or_else(|| parent.map(|parent| parent.color.clone())).unwrap_or(Color {r: 0, g: 0, b: 0, a: 255}),
+        font_size,
+        font_weight: values.get("font-weight").map_or(parent_font_weight, |weight| font_weight(weight, parent_font_weight)),
+        line_height: match values.get("line-height") {
+            Some(Value::Number(n)) => LineHeight::Number(*n),
+            Some(value @ Value::Length(..)) => LineHeight::Px(value.to_px()),
+            Some(Value::Percentage(p)) => LineHeight::Px(font_size * p / 100.0),
+            Some(_) => LineHeight::Normal,
+            None => parent.map_or(LineHeight::Normal, |parent| parent.line_height)
+        }
+    }
+}
+
+// NOTE: bolderとlighterは親の太さが基準になる
+pub fn font_weight(value: &Value, parent_font_weight: f32) -> f32 {
+    match value {
+        Value::Number(weight) => *weight,
+        Value::Keyword(keyword) => match &*keyword.to_ascii_lowercase() {
+            "bold" => 700.0,
+            "bolder" => bolder(parent_font_weight),
+            "lighter" => lighter(parent_font_weight),
+            _ => 400.0
+        },
+        _ => parent_font_weight
+    }
+}
+
+// NOTE: https://www.w3.org/TR/css-fonts-4/#relative-weights
+fn bolder(parent_weight: f32) -> f32 {
+    if parent_weight < 350.0 {
+        400.0
+    } else if parent_weight < 550.0 {
+        700.0
+    } else {
+        parent_weight.max(900.0)
+    }
+}
+
+fn lighter(parent_weight: f32) -> f32 {
+    if parent_weight < 550.0 {
+        parent_weight.min(100.0)
+    } else if parent_weight < 750.0 {
+        400.0
+    } else {
+        700.0
+    }
+}
+
+fn zero() -> LengthPercentageOrAuto {
+    LengthPercentageOrAuto::LengthPercentage(LengthPercentage::Px(0.0))
+}
+
+fn length_percentage(value: Option<&Value>) -> Option<LengthPercentage> {
+    match value? {
+        value @ Value::Length(..) => Some(LengthPercentage::Px(value.to_px())),
+        Value::Percentage(p) => Some(LengthPercentage::Percentage(*p)),
+        Value::Calc(node) => Some(LengthPercentage::Calc(node.clone())),
+        _ => None
+    }
+}
+
+fn length_percentage_or_auto(value: Option<&Value>, initial: LengthPercentageOrAuto) -> LengthPercentageOrAuto {
+    match value {
+        Some(Value::Keyword(keyword)) if keyword.eq_ignore_ascii_case("auto") => LengthPercentageOrAuto::Auto,
+        value => length_percentage(value).map_or(initial, LengthPercentageOrAuto::LengthPercentage)
+    }
+}
+
+// NOTE: border-styleの初期値はnoneなので、styleを指定していない辺にも幅はない
+fn border_widths(values: &PropertyMap) -> Sides<f32> {
+    let has_border = sides(values, "border", "-style", |style| match style {
+        Some(Value::Keyword(keyword)) => !keyword.eq_ignore_ascii_case("none") && !keyword.eq_ignore_ascii_case("hidden"),
+        _ => false
+    });
+    let widths = sides(values, "border", "-width", |value| value.map_or(0.0, Value::to_px));
+    let width = |has_border: bool, width: f32| if has_border {width} else {0.0};
+    Sides {
+        top: width(has_border.top, widths.top),
+        right: width(has_border.right, widths.right),
+        bottom: width(has_border.bottom, widths.bottom),
+        left: width(has_border.left, widths.left)
+    }
+}
+
+// NOTE: margin-top, border-top-width等の4辺のプロパティをまとめて読む
+fn sides<T>(values: &PropertyMap, prefix: &str, suffix: &str, compute: impl Fn(Option<&Value>) -> T) -> Sides<T> {
+    let value = |side: &str| values.get(&format!("{}-{}{}", prefix, side, suffix));
+    Sides {
+        top: compute(value("top")),
+        right: compute(value("right")),
+        bottom: compute(value("bottom")),
+        left: compute(value("left"))
+    }
+}
+
+fn color(value: Option<&Value>) -> Option<Color> {
+    match value {
+        Some(Value::ColorValue(color)) => Some(color.clone()),
+        _ => None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use crate::css::{Value, Unit, Color};
+    use crate::style::PropertyMap;
+    use crate::style::container::ContainerType;
+    use super::{ComputedStyle, Display, Position, Float, LengthPercentage, LengthPercentageOrAuto, LineHeight};
+
+    fn create_values(values: Vec<(&str, Value)>) -> PropertyMap {
+        values.into_iter().map(|(name, value)| (name.to_string(), value)).collect()
+    }
+
+    #[test]
+    fn compute_typed_style() {
+        let red = Color {r: 255, g: 0, b: 0, a: 255};
+        let values = create_values(vec![
+            ("display", Value::Keyword("block".to_string())),
+            ("position", Value::Keyword("relative".to_string())),
+            ("float", Value::Keyword("left".to_string())),
+            ("width", Value::Percentage(50.0)),
+            ("margin-left", Value::Keyword("auto".to_string())),
+            ("margin-top", Value::Length(10.0, Unit::Px)),
+            ("padding-top", Value::Length(1.0, Unit::In)),
+            ("border-top-width", Value::Length(2.0, Unit::Px)),
+            ("border-top-style", Value::Keyword("solid".to_string())),
+            ("border-top-color", Value::ColorValue(red.clone())),
+            ("border-left-width", Value::Length(3.0, Unit::Px)),
+            ("border-left-style", Value::Keyword("none".to_string())),
+            ("border-right-width", Value::Length(4.0, Unit::Px)),
+            ("container-type", Value::Keyword("inline-size".to_string())),
+            ("line-height", Value::Number(1.5)),
+        ]);
+        let style = ComputedStyle::new(&values, None);
+        assert_eq!(style.display, Display::Block);
+        assert_eq!(style.position, Position::Relative);
+        assert_eq!(style.float, Float::Left);
+        assert_eq!(style.width.resolve(200.0), Some(100.0));
+        assert_eq!(style.height, LengthPercentageOrAuto::Auto);
+        assert_eq!(style.margin.left, LengthPercentageOrAuto::Auto);
+        assert_eq!(style.margin.top.resolve(0.0), Some(10.0));
+        assert_eq!(style.margin.right.resolve(0.0), Some(0.0));
+        assert_eq!(style.padding.top, LengthPercentage::Px(96.0));
+        assert_eq!(style.border_width.top, 2.0);
+        assert_eq!(style.border_width.left, 0.0);
+        assert_eq!(style.border_width.right, 0.0);
+        assert_eq!(style.border_color.top, red);
+        assert_eq!(style.border_color.left, Color {r: 0, g: 0, b: 0, a: 255});
+        assert_eq!(style.background_color.a, 0);
+        assert_eq!(style.container_type, Some(ContainerType::InlineSize));
+        assert_eq!(style.inherited.color, Color {r: 0, g: 0, b: 0, a: 255});
+        assert_eq!(style.inherited.font_size, 16.0);
+        assert_eq!(style.inherited.line_height, LineHeight::Number(1.5));
+    }
+
+    #[test]
+    fn share_inherited_style_with_parent() {
+        let parent = ComputedStyle::new(&create_values(vec![
+            ("color", Value::ColorValue(Color {r: 0, g: 0, b: 255, a: 255})),
+            ("font-weight", Value::Keyword("bold".to_string())),
+        ]), None);
+
+        // NOTE: 継承する値が親と同じなら親のものを共有する
+        let child = ComputedStyle::new(&create_values(vec![
+            ("display", Value::Keyword("block".to_string())),
+        ]), Some(&parent));
+        assert!(Rc::ptr_eq(&parent.inherited, &child.inherited));
+        assert_eq!(child.display, Display::Block);
+
+        let bolder = ComputedStyle::new(&create_values(vec![
+            ("font-weight", Value::Keyword("bolder".to_string())),
+            ("font-size", Value::Length(20.0, Unit::Px)),
+        ]), Some(&parent));
+        assert!(!Rc::ptr_eq(&parent.inherited, &bolder.inherited));
+        assert_eq!(bolder.inherited.font_weight, 900.0);
+        assert_eq!(bolder.inherited.font_size, 20.0);
+        assert_eq!(bolder.inherited.color, Color {r: 0, g: 0, b: 255, a: 255});
+    }
+
+}
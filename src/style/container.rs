@@ -0,0 +1,91 @@
+// NOTE: @containerの問い合わせ先になるコンテナと、レイアウトしたコンテナの大きさを集める処理
+// https://www.w3.org/TR/css-contain-3/#container-queries
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use crate::css::{Value, Separator, ContainerRule};
+use crate::css::media::{Device, evaluate_container_condition};
+use crate::dom::{ElementData, NodeType};
+use crate::layout::{Dimensions, LayoutBox, BoxType, layout_tree};
+use super::{PropertyMap, StyledNode, Display};
+
+// NOTE: 前回レイアウトした時のコンテナのコンテンツボックスの幅と高さ。要素のアドレスで引く
+pub type ContainerSizes = HashMap<*const ElementData, (f32, f32)>;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ContainerType {
+    // NOTE: 幅と高さの両方を問い合わせられる
+    Size,
+    // NOTE: 幅だけを問い合わせられる
+    InlineSize
+}
+
+// NOTE: container-typeがnormal以外の要素。まだレイアウトしていない場合は大きさが分からないのでNone
+#[derive(Clone, Debug, PartialEq)]
+pub struct QueryContainer {
+    names: Vec<String>,
+    width: Option<f32>,
+    height: Option<f32>
+}
+
+pub fn container_type(values: &PropertyMap) -> Option<ContainerType> {
+    match values.get("container-type") {
+        Some(Value::Keyword(keyword)) => match &*keyword.to_ascii_lowercase() {
+            "size" => Some(ContainerType::Size),
+            "inline-size" => Some(ContainerType::InlineSize),
+            _ => None
+        },
+        _ => None
+    }
+}
+
+pub fn query_container(data: &ElementData, values: &PropertyMap, sizes: &ContainerSizes) -> Option<QueryContainer> {
+    let container_type = container_type(values)?;
+    // NOTE: container-nameは大文字と小文字を区別する
+    let names = match values.get("container-name") {
+        Some(Value::Keyword(name)) if !name.eq_ignore_ascii_case("none") => vec![name.clone()],
+        Some(Value::List(names, Separator::Space)) => names.iter().filter_map(|name| match name {
+            Value::Keyword(name) => Some(name.clone()),
+            _ => None
+        }).collect(),
+        _ => vec![]
+    };
+    let size = sizes.get(&(data as *const ElementData));
+    Some(QueryContainer {
+        names,
+        width: size.map(|&(width, _)| width),
+        height: size.filter(|_| container_type == ContainerType::Size).map(|&(_, height)| height)
+    })
+}
+
+// NOTE: containersは近い方から並んだ祖先のコンテナ。名前が一致する一番近いコンテナに対して評価し、見つからなければ一致しない
+pub fn matches_container_rule<'a>(rule: &ContainerRule, mut containers: impl Iterator<Item = &'a QueryContainer>, device: &Device) -> bool {
+    let container = containers.find(|container| rule.name.as_ref().is_none_or(|name| container.names.contains(name)));
+    container.is_some_and(|container| evaluate_container_condition(&rule.condition, container.width, container.height, device))
+}
+
+// NOTE: スタイルを決めたツリーをレイアウトして、コンテナの大きさを集める。viewportは書き換えないように複製して使う
+pub fn layout_container_sizes(style_root: &StyledNode, viewport: &Dimensions) -> ContainerSizes {
+    let mut sizes = ContainerSizes::new();
+    if let Display::None = style_root.style.display {
+        return sizes;
+    }
+    let layout_root = layout_tree(style_root, Rc::new(RefCell::new(viewport.clone())));
+    collect_container_sizes(&layout_root, &mut sizes);
+    sizes
+}
+
+fn collect_container_sizes(layout_box: &LayoutBox, sizes: &mut ContainerSizes) {
+    if let BoxType::BlockNode(style_node) = layout_box.box_type {
+        if let NodeType::Element(ref data) = style_node.node.node_type {
+            if style_node.style.container_type.is_some() {
+                let content = &layout_box.dimensions.borrow().content;
+                sizes.insert(data, (content.width, content.height));
+            }
+        }
+    }
+    for child in &layout_box.children {
+        collect_container_sizes(child, sizes);
+    }
+}
@@ -0,0 +1,317 @@
+// NOTE: 要素ごとに全てのルールを照合しなくて済むように、セレクタを一番右の複合セレクタのid, class, タグ名で振り分けておく
+// 祖先の要素のid, class, タグ名を数えるBloom filterで、子孫結合子等で必要な祖先がいないセレクタを照合する前に弾く
+// 参考: https://github.com/servo/servo/blob/main/components/style/selector_map.rs
+
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use crate::css::{Selector, SimpleSelector, Combinator};
+use crate::dom::ElementData;
+use super::CascadeRule;
+
+// NOTE: Bloom filterの大きさ。ハッシュ値の下位12bitとその次の12bitの2箇所を使う
+const FILTER_KEY_BITS: u32 = 12;
+const FILTER_SIZE: usize = 1 << FILTER_KEY_BITS;
+const FILTER_KEY_MASK: u32 = (1 << FILTER_KEY_BITS) - 1;
+
+// NOTE: (ルールの順番, ルールの中でのセレクタの順番)と、一致するのに祖先にいなければならないid, class, タグ名のハッシュ値
+#[derive(Debug)]
+struct SelectorEntry {
+    rule_index: usize,
+    selector_index: usize,
+    ancestor_hashes: Vec<u32>
+}
+
+#[derive(Debug, Default)]
+pub struct SelectorMap {
+    ids: HashMap<String, Vec<SelectorEntry>>,
+    classes: HashMap<String, Vec<SelectorEntry>>,
+    tags: HashMap<String, Vec<SelectorEntry>>,
+    // NOTE: id, class, タグ名のどれも持たない *, [hidden], :is(...) のようなセレクタ
+    universal: Vec<SelectorEntry>
+}
+
+impl SelectorMap {
+    pub fn new(rules: &[CascadeRule]) -> SelectorMap {
+        let mut map = SelectorMap::default();
+        for (rule_index, rule) in rules.iter().enumerate() {
+            for (selector_index, selector) in rule.rule.selectors.iter().enumerate() {
+                let (subject, ancestor_hashes) = match selector {
+                    Selector::Simple(subject) => (subject, Vec::new()),
+                    Selector::Complex(compounds, subject) => (subject, ancestor_hashes(compounds))
+                };
+                let entry = SelectorEntry {rule_index, selector_index, ancestor_hashes};
+                // NOTE: 一番絞り込めるidを優先し、次にclass、タグ名の順で一つのバケツにだけ入れる
+                let bucket = if let Some(ref id) = subject.id {
+                    map.ids.entry(id.clone()).or_default()
+                } else if let Some(class) = subject.class.first() {
+                    map.classes.entry(class.clone()).or_default()
+                } else if let Some(ref tag_name) = subject.tag_name {
+                    map.tags.entry(tag_name.clone()).or_default()
+                } else {
+                    &mut map.universal
+                };
+                bucket.push(entry);
+            }
+        }
+        map
+    }
+
+    // NOTE: 要素に一致するかもしれないセレクタを(ルールの順番, ルールの中でのセレクタの順番)の昇順で返す
+    // filterには要素の祖先が全て入っている必要がある
+    pub fn candidates(&self, data: &ElementData, filter: &AncestorFilter) -> Vec<(usize, usize)> {
+        let mut buckets = vec![&self.universal];
+        buckets.extend(data.id().and_then(|id| self.ids.get(id)));
+        buckets.extend(data.classes().into_iter().filter_map(|class| self.classes.get(class)));
+        buckets.extend(self.tags.get(&data.tag_name));
+        let mut candidates: Vec<(usize, usize)> = buckets.into_iter()
+            .flatten()
+            .filter(|entry| entry.ancestor_hashes.iter().all(|&hash| filter.might_contain(hash)))
+            .map(|entry| (entry.rule_index, entry.selector_index))
+            .collect();
+        candidates.sort_unstable();
+        candidates
+    }
+}
+
+// NOTE: 一番右から見て子孫結合子と子結合子で繋がっている複合セレクタは祖先と一致しなければならない
+// 兄弟結合子が出てきたらそれより左の複合セレクタは祖先の兄弟と照合するので含めない
+fn ancestor_hashes(compounds: &[(SimpleSelector, Combinator)]) -> Vec<u32> {
+    let mut hashes = Vec::new();
+    for (compound, combinator) in compounds.iter().rev() {
+        match combinator {
+            Combinator::Descendant | Combinator::Child => {},
+            Combinator::NextSibling | Combinator::SubsequentSibling => break
+        }
+        hashes.extend(compound.id.iter().map(|id| hash(id)));
+        hashes.extend(compound.class.iter().map(|class| hash(class)));
+        hashes.extend(compound.tag_name.iter().map(|tag_name| hash(tag_name)));
+    }
+    hashes
+}
+
+fn hash(name: &str) -> u32 {
+    let mut hasher = DefaultHasher::new();
+    name.hash(&mut hasher);
+    hasher.finish() as u32
+}
+
+// NOTE: 祖先の要素を出し入れできるように、ビットではなく数を数えるBloom filter
+// 数が上限に達した場所は以降減らさないので、誤って「いない」と答えることはない
+pub struct AncestorFilter {
+    counters: Box<[u8; FILTER_SIZE]>
+}
+
+impl AncestorFilter {
+    pub fn new() -> AncestorFilter {
+        AncestorFilter {counters: Box::new([0; FILTER_SIZE])}
+    }
+
+    // NOTE: 子の要素のスタイルを決める前にpushし、決め終わったらpopする
+    pub fn push(&mut self, data: &ElementData) {
+        for hash in element_hashes(data) {
+            for key in keys(hash) {
+                let counter = &mut self.counters[key];
+                *counter = counter.saturating_add(1);
+            }
+        }
+    }
+
+    pub fn pop(&mut self, data: &ElementData) {
+        for hash in element_hashes(data) {
+            for key in keys(hash) {
+                let counter = &mut self.counters[key];
+                if *counter != u8::MAX {
+                    *counter -= 1;
+                }
+            }
+        }
+    }
+
+    fn might_contain(&self, hash: u32) -> bool {
+        keys(hash).iter().all(|&key| self.counters[key] != 0)
+    }
+}
+
+fn element_hashes(data: &ElementData) -> Vec<u32> {
+    let mut hashes = vec![hash(&data.tag_name)];
+    hashes.extend(data.id().map(|id| hash(id)));
+    hashes.extend(data.classes().into_iter().map(hash));
+    hashes
+}
+
+fn keys(hash: u32) -> [usize; 2] {
+    [(hash & FILTER_KEY_MASK) as usize, ((hash >> FILTER_KEY_BITS) & FILTER_KEY_MASK) as usize]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::time::Instant;
+    use super::{SelectorMap, AncestorFilter, hash};
+    use crate::css::media::Device;
+    use crate::dom::{Node, NodeType, ElementData, AttrMap, elem, text};
+    use crate::style::{CascadeRule, Element, Origin, effective_rules, matches};
+
+    const TAGS: [&str; 6] = ["div", "span", "p", "ul", "li", "section"];
+
+    // NOTE: 同じ文書とスタイルシートを何度でも作れるように、乱数は種を固定した線形合同法で作る
+    struct Random(u64);
+
+    impl Random {
+        fn below(&mut self, n: usize) -> usize {
+            self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (self.0 >> 33) as usize % n
+        }
+    }
+
+    fn generate_stylesheet(random: &mut Random, rule_count: usize, class_count: usize) -> String {
+        let mut css = String::new();
+        for _ in 0..rule_count {
+            let a = random.below(class_count);
+            let b = random.below(class_count);
+            let tag = TAGS[random.below(TAGS.len())];
+            let selector = match random.below(8) {
+                0 => format!(".c{}", a),
+                1 => format!("#e{}", a),
+                2 => format!("{} .c{}", tag, a),
+                3 => format!(".c{} > {}", a, tag),
+                4 => format!(".c{} .c{} {}.c{}", a, b, tag, random.below(class_count)),
+                5 => format!(".c{} + .c{}", a, b),
+                6 => format!("{}[data-k], .c{} ~ {}", tag, a, tag),
+                _ => format!("#e{} .c{}", a, b)
+            };
+            css.push_str(&format!("{} {{ width: {}px; }}\n", selector, a));
+        }
+        css
+    }
+
+    fn generate_document(random: &mut Random, depth: usize, class_count: usize) -> Node {
+        let mut attributes = AttrMap::new();
+        let classes: Vec<String> = (0..1 + random.below(2)).map(|_| format!("c{}", random.below(class_count))).collect();
+        attributes.insert("class".to_string(), classes.join(" "));
+        if random.below(10) == 0 {
+            attributes.insert("id".to_string(), format!("e{}", random.below(class_count)));
+        }
+        if random.below(4) == 0 {
+            attributes.insert("data-k".to_string(), String::new());
+        }
+        let mut children = Vec::new();
+        if depth > 0 {
+            for _ in 0..2 + random.below(5) {
+                if random.below(4) == 0 {
+                    children.push(text("text".to_string()));
+                }
+                children.push(generate_document(random, depth - 1, class_count));
+            }
+        }
+        elem(TAGS[random.below(TAGS.len())].to_string(), attributes, children)
+    }
+
+    // NOTE: style_tree_recと同じように、要素を訪れている間はその祖先だけがfilterに入っている
+    fn visit<'a>(node: &'a Node, parent: Option<&Element<'a, '_>>, preceding_siblings: &'a [Node], filter: &mut AncestorFilter, f: &mut dyn FnMut(&Element, &AncestorFilter)) {
+        if let NodeType::Element(ref data) = node.node_type {
+            let element = Element {data, parent, preceding_siblings, container: None, style: None};
+            f(&element, filter);
+            filter.push(data);
+            for (i, child) in node.children.iter().enumerate() {
+                visit(child, Some(&element), &node.children[..i], filter, f);
+            }
+            filter.pop(data);
+        }
+    }
+
+    fn linear_matches(element: &Element, rules: &[CascadeRule]) -> Vec<(usize, usize)> {
+        rules.iter().enumerate()
+            .flat_map(|(rule_index, rule)| rule.rule.selectors.iter().enumerate()
+                .filter(|(_, selector)| matches(element, selector))
+                .map(move |(selector_index, _)| (rule_index, selector_index)))
+            .collect()
+    }
+
+    fn indexed_matches(element: &Element, filter: &AncestorFilter, rules: &[CascadeRule], map: &SelectorMap) -> Vec<(usize, usize)> {
+        map.candidates(element.data, filter).into_iter()
+            .filter(|&(rule_index, selector_index)| matches(element, &rules[rule_index].rule.selectors[selector_index]))
+            .collect()
+    }
+
+    fn element_data(tag_name: &str, class: &str) -> ElementData {
+        let mut attributes = AttrMap::new();
+        attributes.insert("class".to_string(), class.to_string());
+        ElementData {tag_name: tag_name.to_string(), attributes}
+    }
+
+    #[test]
+    fn ancestor_filter_forgets_popped_elements() {
+        let mut filter = AncestorFilter::new();
+        let outer = element_data("section", "outer");
+        let inner = element_data("div", "inner");
+        filter.push(&outer);
+        filter.push(&inner);
+        assert!(filter.might_contain(hash("section")) && filter.might_contain(hash("inner")));
+        filter.pop(&inner);
+        assert!(filter.might_contain(hash("outer")));
+        assert!(!filter.might_contain(hash("inner")));
+        filter.pop(&outer);
+        assert!(!filter.might_contain(hash("section")));
+    }
+
+    #[test]
+    fn candidates_are_bucketed_and_filtered_by_ancestors() {
+        let stylesheet = crate::css::parse("#a {} .b {} div {} * {} .x .b {} span {} .y + .b {}".to_string());
+        let rules = effective_rules(&[(Origin::Author, &stylesheet)], &Device::new(800.0, 600.0), &mut HashMap::new());
+        let map = SelectorMap::new(&rules);
+        let mut data = element_data("div", "b");
+        data.attributes.insert("id".to_string(), "a".to_string());
+        let mut filter = AncestorFilter::new();
+        assert_eq!(map.candidates(&data, &filter), vec![(0, 0), (1, 0), (2, 0), (3, 0), (6, 0)]);
+        filter.push(&element_data("p", "x"));
+        assert_eq!(map.candidates(&data, &filter), vec![(0, 0), (1, 0), (2, 0), (3, 0), (4, 0), (6, 0)]);
+    }
+
+    #[test]
+    fn indexed_matching_agrees_with_linear_matching() {
+        let mut random = Random(1);
+        let stylesheet = crate::css::parse(generate_stylesheet(&mut random, 400, 20));
+        let document = generate_document(&mut random, 4, 20);
+        let rules = effective_rules(&[(Origin::Author, &stylesheet)], &Device::new(800.0, 600.0), &mut HashMap::new());
+        let map = SelectorMap::new(&rules);
+        let mut matched = 0;
+        visit(&document, None, &[], &mut AncestorFilter::new(), &mut |element, filter| {
+            let linear = linear_matches(element, &rules);
+            assert_eq!(indexed_matches(element, filter, &rules, &map), linear);
+            matched += linear.len();
+        });
+        assert!(matched > 0);
+    }
+
+    // NOTE: cargo test --release bench_selector_map -- --ignored --nocapture で実行する
+    #[test]
+    #[ignore]
+    fn bench_selector_map_on_large_document() {
+        let mut random = Random(7);
+        let stylesheet = crate::css::parse(generate_stylesheet(&mut random, 5000, 500));
+        let document = generate_document(&mut random, 6, 500);
+        let rules = effective_rules(&[(Origin::Author, &stylesheet)], &Device::new(800.0, 600.0), &mut HashMap::new());
+
+        let (mut elements, mut linear_count, mut indexed_count) = (0, 0, 0);
+        let start = Instant::now();
+        visit(&document, None, &[], &mut AncestorFilter::new(), &mut |element, _| {
+            elements += 1;
+            linear_count += linear_matches(element, &rules).len();
+        });
+        let linear_time = start.elapsed();
+
+        let start = Instant::now();
+        let map = SelectorMap::new(&rules);
+        visit(&document, None, &[], &mut AncestorFilter::new(), &mut |element, filter| {
+            indexed_count += indexed_matches(element, filter, &rules, &map).len();
+        });
+        let indexed_time = start.elapsed();
+
+        println!("{} rules, {} elements, {} matches", rules.len(), elements, linear_count);
+        println!("linear:  {:?}", linear_time);
+        println!("indexed: {:?}", indexed_time);
+        assert_eq!(indexed_count, linear_count);
+    }
+}
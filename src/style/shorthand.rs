@@ -0,0 +1,395 @@
+// NOTE: margin: 4px 8px; のようなショートハンドをmargin-top等のロングハンドに展開する
+// 各値を受け付けるかどうかと省略した値の初期値は、展開先のロングハンドの定義から読む
+// https://www.w3.org/TR/css-cascade-4/#shorthand
+
+use crate::css::{Value, Separator};
+use crate::css::properties::{property_definition, is_css_wide_keyword};
+
+// NOTE: ショートハンドなら展開したロングハンドの配列を、そうでなければ宣言をそのまま返す
+// ショートハンドの値が不正な場合は空の配列を返して宣言ごと無視する
+pub fn expand(name: &str, value: &Value) -> Vec<(String, Value)> {
+    let longhands = match longhand_names(name) {
+        Some(longhands) => longhands,
+        None => return vec![(name.to_string(), value.clone())]
+    };
+
+    // NOTE: margin: inherit; のような場合は全てのロングハンドに同じキーワードを入れる
+    if is_css_wide_keyword(value) {
+        return longhands.into_iter().map(|longhand| (longhand, value.clone())).collect();
+    }
+
+    let values = match &*name.to_ascii_lowercase() {
+        "margin" | "padding" | "border-width" | "border-style" | "border-color" => expand_box(value, &longhands[0]),
+        "border" | "border-top" | "border-right" | "border-bottom" | "border-left" => expand_border(value, longhands.len() / 3),
+        "background" => expand_background(value),
+        "font" => expand_font(value),
+        "container" => expand_container(value),
+        "gap" => expand_gap(value),
+        _ => None
+    };
+    match values {
+        Some(values) => longhands.into_iter().zip(values).collect(),
+        None => vec![]
+    }
+}
+
+// NOTE: 展開先のロングハンドはプロパティの定義に並べた順番で返す
+pub fn longhand_names(name: &str) -> Option<Vec<String>> {
+    match property_definition(name) {
+        Some(property) if property.is_shorthand() => Some(property.longhands().into_iter().map(|longhand| longhand.to_string()).collect()),
+        _ => None
+    }
+}
+
+// NOTE: 値がロングハンドの文法で受け付けられるか
+fn accepts(longhand: &str, value: &Value) -> bool {
+    property_definition(longhand).is_some_and(|property| property.accepts(value))
+}
+
+fn initial_value(longhand: &str) -> Value {
+    property_definition(longhand).expect("longhand of a shorthand must be defined").initial_value()
+}
+
+// NOTE: 1~4個の値を top, right, bottom, left に割り当てる。どの辺のロングハンドも同じ文法なのでtopの文法で確かめる
+fn expand_box(value: &Value, top_longhand: &str) -> Option<Vec<Value>> {
+    let components = components(value);
+    if !components.iter().all(|component| accepts(top_longhand, component)) {
+        return None;
+    }
+    let (top, right, bottom, left) = match components.len() {
+        1 => (0, 0, 0, 0),
+        2 => (0, 1, 0, 1),
+        3 => (0, 1, 2, 1),
+        4 => (0, 1, 2, 3),
+        _ => return None
+    };
+    Some(vec![components[top].clone(), components[right].clone(), components[bottom].clone(), components[left].clone()])
+}
+
+// NOTE: width, style, colorを順不同で受け取る。省略されたものは初期値になる
+fn expand_border(value: &Value, side_count: usize) -> Option<Vec<Value>> {
+    let kinds = ["border-top-width", "border-top-style", "border-top-color"];
+    let mut side: [Option<Value>; 3] = [None, None, None];
+    for component in components(value) {
+        let kind = kinds.iter().position(|longhand| accepts(longhand, &component))?;
+        if side[kind].replace(component).is_some() {
+            return None;
+        }
+    }
+    let side: Vec<Value> = side.iter().zip(kinds).map(|(value, longhand)| value.clone().unwrap_or_else(|| initial_value(longhand))).collect();
+    Some(side.iter().cycle().take(side.len() * side_count).cloned().collect())
+}
+
+// NOTE: カンマ区切りの各レイヤーを展開し、レイヤーが複数ある場合はロングハンドもカンマ区切りのリストにする
+// background-colorは最後のレイヤーにしか書けない
+fn expand_background(value: &Value) -> Option<Vec<Value>> {
+    let layers = match value {
+        Value::List(layers, Separator::Comma) => layers.clone(),
+        value => vec![value.clone()]
+    };
+    let mut color = None;
+    let mut longhands: Vec<Vec<Value>> = vec![Vec::new(); 7];
+    for (i, layer) in layers.iter().enumerate() {
+        let is_final_layer = i == layers.len() - 1;
+        let mut image = None;
+        let mut repeat = Vec::new();
+        let mut attachment = None;
+        let mut position = Vec::new();
+        let mut size = None;
+        let mut boxes = Vec::new();
+        for component in components(layer) {
+            match component {
+                ref c if accepts("background-color", c) && is_final_layer && color.is_none() => color = Some(c.clone()),
+                ref c if accepts("background-image", c) && image.is_none() => image = Some(c.clone()),
+                ref c if accepts("background-repeat", c) => repeat.push(c.clone()),
+                ref c if accepts("background-attachment", c) && attachment.is_none() => attachment = Some(c.clone()),
+                ref c if accepts("background-origin", c) => boxes.push(c.clone()),
+                ref c if accepts("background-position", c) => position.push(c.clone()),
+                // NOTE: center / cover のように / の後ろはbackground-size
+                Value::List(ref items, Separator::Slash) if items.len() == 2 && size.is_none() => {
+                    let position_part = components(&items[0]);
+                    if !position_part.iter().all(|c| accepts("background-position", c)) {
+                        return None;
+                    }
+                    position.extend(position_part);
+                    size = Some(items[1].clone());
+                },
+                _ => return None
+            }
+        }
+        if repeat.len() > 2 || position.len() > 4 || boxes.len() > 2 {
+            return None;
+        }
+        let origin = boxes.first().cloned().unwrap_or_else(|| initial_value("background-origin"));
+        let clip = boxes.last().cloned().unwrap_or_else(|| initial_value("background-clip"));
+        longhands[0].push(image.unwrap_or_else(|| initial_value("background-image")));
+        longhands[1].push(space_list(repeat).unwrap_or_else(|| initial_value("background-repeat")));
+        longhands[2].push(attachment.unwrap_or_else(|| initial_value("background-attachment")));
+        longhands[3].push(space_list(position).unwrap_or_else(|| initial_value("background-position")));
+        longhands[4].push(size.unwrap_or_else(|| initial_value("background-size")));
+        longhands[5].push(origin);
+        longhands[6].push(clip);
+    }
+    let mut values = vec![color.unwrap_or_else(|| initial_value("background-color"))];
+    values.extend(longhands.into_iter().map(|mut layer_values| {
+        if layer_values.len() == 1 {
+            layer_values.remove(0)
+        } else {
+            Value::List(layer_values, Separator::Comma)
+        }
+    }));
+    Some(values)
+}
+
+// NOTE: [style || variant || weight || stretch]? size [/ line-height]? family の順
+fn expand_font(value: &Value) -> Option<Vec<Value>> {
+    // NOTE: font-familyのカンマ区切りがあるので、最初の要素に他のロングハンドが全部入っている
+    let (head, other_families) = match value {
+        Value::List(items, Separator::Comma) => (components(&items[0]), &items[1..]),
+        value => (components(value), &[][..])
+    };
+
+    let prefix_longhands = ["font-style", "font-variant", "font-weight", "font-stretch"];
+    let mut prefix: [Option<Value>; 4] = [None, None, None, None];
+    let mut i = 0;
+    while i < head.len() {
+        let component = &head[i];
+        // NOTE: normalはどのロングハンドも受け付けるので、まだ指定されていない最初のロングハンドの値になる
+        let accepting: Vec<usize> = (0..prefix_longhands.len()).filter(|&k| accepts(prefix_longhands[k], component)).collect();
+        if accepting.is_empty() {
+            break;
+        }
+        let slot = accepting.into_iter().find(|&k| prefix[k].is_none())?;
+        prefix[slot] = Some(component.clone());
+        i += 1;
+    }
+
+    let (size, line_height) = match head.get(i) {
+        Some(Value::List(items, Separator::Slash)) if items.len() == 2 => (items[0].clone(), Some(items[1].clone())),
+        Some(size) => (size.clone(), None),
+        None => return None
+    };
+    if !accepts("font-size", &size) || !line_height.as_ref().is_none_or(|line_height| accepts("line-height", line_height)) {
+        return None;
+    }
+
+    // NOTE: Times New Roman のようにクォートされていないファミリー名はスペース区切りの識別子になる
+    let first_family = &head[i + 1..];
+    if first_family.is_empty() {
+        return None;
+    }
+    let mut families = vec![font_family_name(&space_list(first_family.to_vec()).unwrap())?];
+    for family in other_families {
+        families.push(font_family_name(family)?);
+    }
+    let family = if families.len() == 1 {families.remove(0)} else {Value::List(families, Separator::Comma)};
+
+    let mut values: Vec<Value> = prefix.iter().zip(prefix_longhands)
+        .map(|(value, longhand)| value.clone().unwrap_or_else(|| initial_value(longhand)))
+        .collect();
+    values.push(size);
+    values.push(line_height.unwrap_or_else(|| initial_value("line-height")));
+    values.push(family);
+    Some(values)
+}
+
+fn font_family_name(value: &Value) -> Option<Value> {
+    match value {
+        Value::Keyword(_) | Value::String(_) => Some(value.clone()),
+        Value::List(items, Separator::Space) => {
+            let mut names = Vec::new();
+            for item in items {
+                match item {
+                    Value::Keyword(name) => names.push(name.clone()),
+                    _ => return None
+                }
+            }
+            Some(Value::Keyword(names.join(" ")))
+        },
+        _ => None
+    }
+}
+
+// NOTE: <container-name> [/ <container-type>]?。typeを省略すると初期値のnormalになる
+fn expand_container(value: &Value) -> Option<Vec<Value>> {
+    let (name, container_type) = match value {
+        Value::List(items, Separator::Slash) if items.len() == 2 => (items[0].clone(), items[1].clone()),
+        Value::List(_, Separator::Slash) => return None,
+        value => (value.clone(), initial_value("container-type"))
+    };
+    if !accepts("container-name", &name) || !accepts("container-type", &container_type) {
+        return None;
+    }
+    Some(vec![name, container_type])
+}
+
+// NOTE: row-gap column-gapの順。一つだけなら両方に同じ値を入れる
+fn expand_gap(value: &Value) -> Option<Vec<Value>> {
+    let components = components(value);
+    if !components.iter().all(|component| accepts("row-gap", component)) {
+        return None;
+    }
+    match components.as_slice() {
+        [gap] => Some(vec![gap.clone(), gap.clone()]),
+        [row, column] => Some(vec![row.clone(), column.clone()]),
+        _ => None
+    }
+}
+
+// NOTE: スペース区切りのリストなら各要素を、そうでなければ値一つだけを返す
+fn components(value: &Value) -> Vec<Value> {
+    match value {
+        Value::List(items, Separator::Space) => items.clone(),
+        value => vec![value.clone()]
+    }
+}
+
+fn space_list(mut values: Vec<Value>) -> Option<Value> {
+    match values.len() {
+        0 => None,
+        1 => Some(values.remove(0)),
+        _ => Some(Value::List(values, Separator::Space))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::expand;
+    use crate::css::{Value, Unit, Color, Separator};
+
+    fn px(f: f32) -> Value {
+        Value::Length(f, Unit::Px)
+    }
+
+    fn keyword(name: &str) -> Value {
+        Value::Keyword(name.to_string())
+    }
+
+    fn space_list(values: Vec<Value>) -> Value {
+        Value::List(values, Separator::Space)
+    }
+
+    fn longhand_values(name: &str, value: Value) -> Vec<(String, Value)> {
+        expand(name, &value)
+    }
+
+    fn pairs(expected: Vec<(&str, Value)>) -> Vec<(String, Value)> {
+        expected.into_iter().map(|(name, value)| (name.to_string(), value)).collect()
+    }
+
+    #[test]
+    fn expand_margin_and_padding_box_values() {
+        assert_eq!(longhand_values("margin", space_list(vec![px(4.0), px(8.0)])), pairs(vec![
+            ("margin-top", px(4.0)), ("margin-right", px(8.0)), ("margin-bottom", px(4.0)), ("margin-left", px(8.0))
+        ]));
+        assert_eq!(longhand_values("padding", space_list(vec![px(1.0), px(2.0), px(3.0)])), pairs(vec![
+            ("padding-top", px(1.0)), ("padding-right", px(2.0)), ("padding-bottom", px(3.0)), ("padding-left", px(2.0))
+        ]));
+        assert_eq!(longhand_values("margin", space_list(vec![px(1.0), keyword("auto"), px(3.0), Value::Percentage(4.0)])), pairs(vec![
+            ("margin-top", px(1.0)), ("margin-right", keyword("auto")), ("margin-bottom", px(3.0)), ("margin-left", Value::Percentage(4.0))
+        ]));
+        assert_eq!(longhand_values("padding", keyword("auto")), vec![]);
+        assert_eq!(longhand_values("margin", space_list(vec![px(1.0); 5])), vec![]);
+    }
+
+    #[test]
+    fn expand_css_wide_keyword_to_every_longhand() {
+        assert_eq!(longhand_values("border-color", keyword("inherit")), pairs(vec![
+            ("border-top-color", keyword("inherit")), ("border-right-color", keyword("inherit")),
+            ("border-bottom-color", keyword("inherit")), ("border-left-color", keyword("inherit"))
+        ]));
+    }
+
+    #[test]
+    fn expand_border_in_any_order() {
+        let red = Value::ColorValue(Color {r: 255, g: 0, b: 0, a: 255});
+        assert_eq!(longhand_values("border-top", space_list(vec![red.clone(), keyword("solid"), px(2.0)])), pairs(vec![
+            ("border-top-width", px(2.0)), ("border-top-style", keyword("solid")), ("border-top-color", red.clone())
+        ]));
+        let expanded = longhand_values("border", keyword("dashed"));
+        assert_eq!(expanded.len(), 12);
+        assert_eq!(expanded[9], ("border-left-width".to_string(), keyword("medium")));
+        assert_eq!(expanded[10], ("border-left-style".to_string(), keyword("dashed")));
+        assert_eq!(expanded[11], ("border-left-color".to_string(), keyword("currentcolor")));
+        assert_eq!(longhand_values("border", space_list(vec![px(1.0), px(2.0)])), vec![]);
+    }
+
+    #[test]
+    fn expand_background_layers() {
+        let red = Value::ColorValue(Color {r: 255, g: 0, b: 0, a: 255});
+        let expanded = longhand_values("background", space_list(vec![red.clone(), keyword("no-repeat"), keyword("center"),
+            Value::List(vec![keyword("top"), keyword("contain")], Separator::Slash)]));
+        assert_eq!(expanded, pairs(vec![
+            ("background-color", red.clone()),
+            ("background-image", keyword("none")),
+            ("background-repeat", keyword("no-repeat")),
+            ("background-attachment", keyword("scroll")),
+            ("background-position", space_list(vec![keyword("center"), keyword("top")])),
+            ("background-size", keyword("contain")),
+            ("background-origin", keyword("padding-box")),
+            ("background-clip", keyword("border-box"))
+        ]));
+
+        let layers = Value::List(vec![keyword("repeat-x"), space_list(vec![keyword("none"), red.clone()])], Separator::Comma);
+        let expanded = longhand_values("background", layers);
+        assert_eq!(expanded[0], ("background-color".to_string(), red.clone()));
+        assert_eq!(expanded[2], ("background-repeat".to_string(), Value::List(vec![keyword("repeat-x"), keyword("repeat")], Separator::Comma)));
+
+        let expanded = longhand_values("background", space_list(vec![Value::Url("a.png".to_string()), keyword("no-repeat"), red.clone()]));
+        assert_eq!(expanded[0], ("background-color".to_string(), red.clone()));
+        assert_eq!(expanded[1], ("background-image".to_string(), Value::Url("a.png".to_string())));
+
+        let color_in_first_layer = Value::List(vec![red, keyword("none")], Separator::Comma);
+        assert_eq!(longhand_values("background", color_in_first_layer), vec![]);
+    }
+
+    #[test]
+    fn expand_font() {
+        let value = Value::List(vec![
+            space_list(vec![keyword("italic"), keyword("bold"), Value::List(vec![px(12.0), px(30.0)], Separator::Slash),
+                keyword("Times"), keyword("New"), keyword("Roman")]),
+            keyword("serif")
+        ], Separator::Comma);
+        assert_eq!(longhand_values("font", value), pairs(vec![
+            ("font-style", keyword("italic")),
+            ("font-variant", keyword("normal")),
+            ("font-weight", keyword("bold")),
+            ("font-stretch", keyword("normal")),
+            ("font-size", px(12.0)),
+            ("line-height", px(30.0)),
+            ("font-family", Value::List(vec![keyword("Times New Roman"), keyword("serif")], Separator::Comma))
+        ]));
+        assert_eq!(longhand_values("font", space_list(vec![Value::Percentage(80.0), keyword("sans-serif")]))[4],
+            ("font-size".to_string(), Value::Percentage(80.0)));
+        let quoted_family = Value::List(vec![
+            space_list(vec![Value::List(vec![px(12.0), Value::Number(1.5)], Separator::Slash), Value::String("Noto Sans JP".to_string())]),
+            keyword("sans-serif")
+        ], Separator::Comma);
+        assert_eq!(longhand_values("font", quoted_family)[5..], pairs(vec![
+            ("line-height", Value::Number(1.5)),
+            ("font-family", Value::List(vec![Value::String("Noto Sans JP".to_string()), keyword("sans-serif")], Separator::Comma))
+        ])[..]);
+        assert_eq!(longhand_values("font", space_list(vec![Value::Number(700.0), px(12.0), keyword("serif")]))[2], ("font-weight".to_string(), Value::Number(700.0)));
+        assert_eq!(longhand_values("font", space_list(vec![keyword("bold"), px(12.0)])), vec![]);
+        assert_eq!(longhand_values("font", space_list(vec![keyword("bold"), keyword("italic"), keyword("bold"), px(12.0), keyword("serif")])), vec![]);
+    }
+
+    #[test]
+    fn expand_container() {
+        assert_eq!(longhand_values("container", Value::List(vec![space_list(vec![keyword("a"), keyword("b")]), keyword("inline-size")], Separator::Slash)), pairs(vec![
+            ("container-name", space_list(vec![keyword("a"), keyword("b")])),
+            ("container-type", keyword("inline-size"))
+        ]));
+        assert_eq!(longhand_values("container", keyword("card"))[1], ("container-type".to_string(), keyword("normal")));
+        assert_eq!(longhand_values("container", Value::List(vec![keyword("card"), px(1.0)], Separator::Slash)), vec![]);
+    }
+
+    #[test]
+    fn expand_gap() {
+        assert_eq!(longhand_values("gap", px(4.0)), pairs(vec![("row-gap", px(4.0)), ("column-gap", px(4.0))]));
+        assert_eq!(longhand_values("gap", space_list(vec![keyword("normal"), Value::Percentage(10.0)])), pairs(vec![
+            ("row-gap", keyword("normal")),
+            ("column-gap", Value::Percentage(10.0))
+        ]));
+    }
+}
@@ -0,0 +1,177 @@
+// NOTE: カスタムプロパティとvar()の置換
+// https://www.w3.org/TR/css-variables-1/
+
+use crate::css::{Value, Token, trim_whitespace};
+use super::PropertyMap;
+
+pub fn is_custom_property(name: &str) -> bool {
+    name.starts_with("--")
+}
+
+// NOTE: カスタムプロパティの値の中のvar()を置換する
+// 循環している、または参照先がなくフォールバックもないものはcomputed valueの段階で無効なので取り除く
+pub fn resolve_custom_properties(values: &mut PropertyMap) {
+    let names: Vec<String> = values.keys().filter(|name| is_custom_property(name)).cloned().collect();
+    let in_cycle: Vec<&String> = names.iter().filter(|name| reaches(values, name, name, &mut vec![])).collect();
+    for name in in_cycle {
+        values.remove(name);
+    }
+
+    let mut resolved = PropertyMap::new();
+    for name in names {
+        resolve(values, &name, &mut resolved);
+    }
+    values.retain(|name, _| !is_custom_property(name));
+    values.extend(resolved);
+}
+
+// NOTE: 依存先を先に解決してから自身を解決する。循環は取り除いてあるので必ず終わる
+fn resolve(values: &PropertyMap, name: &str, resolved: &mut PropertyMap) -> Option<Vec<Token>> {
+    if let Some(Value::Tokens(tokens)) = resolved.get(name) {
+        return Some(tokens.clone());
+    }
+    let tokens = match values.get(name) {
+        Some(Value::Tokens(tokens)) => tokens.clone(),
+        _ => return None
+    };
+    for reference in references(&tokens) {
+        resolve(values, &reference, resolved);
+    }
+    let substituted = substitute(&tokens, resolved)?;
+    resolved.insert(name.to_string(), Value::Tokens(substituted.clone()));
+    Some(substituted)
+}
+
+// NOTE: fromからvar()の参照を辿ってtargetに辿り着けるか
+fn reaches(values: &PropertyMap, from: &str, target: &str, visited: &mut Vec<String>) -> bool {
+    let tokens = match values.get(from) {
+        Some(Value::Tokens(tokens)) => tokens,
+        _ => return false
+    };
+    for reference in references(tokens) {
+        if reference == target {
+            return true;
+        }
+        if !visited.contains(&reference) {
+            visited.push(reference.clone());
+            if reaches(values, &reference, target, visited) {
+                return true;
+            }
+        }
+    }
+    false
+}
+
+// NOTE: フォールバックの中も含めてvar()で参照しているカスタムプロパティの名前を返す
+fn references(tokens: &[Token]) -> Vec<String> {
+    tokens.windows(2).enumerate()
+        .filter(|(_, pair)| matches!(pair[0], Token::Function(ref name) if name.eq_ignore_ascii_case("var")))
+        .filter_map(|(i, _)| tokens[i + 1..].iter().find(|t| **t != Token::Whitespace))
+        .filter_map(|token| match token {
+            Token::Ident(name) if is_custom_property(name) => Some(name.clone()),
+            _ => None
+        })
+        .collect()
+}
+
+// NOTE: var(--name, fallback) を参照先の値に置き換える。参照先もフォールバックもない場合はNone
+pub fn substitute(tokens: &[Token], custom_properties: &PropertyMap) -> Option<Vec<Token>> {
+    let mut result = Vec::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        match tokens[i] {
+            Token::Function(ref name) if name.eq_ignore_ascii_case("var") => {
+                let end = closing_paren(tokens, i + 1);
+                result.extend(substitute_var(&tokens[i + 1..end], custom_properties)?);
+                i = end + 1;
+            },
+            ref token => {
+                result.push(token.clone());
+                i += 1;
+            }
+        }
+    }
+    Some(result)
+}
+
+fn substitute_var(args: &[Token], custom_properties: &PropertyMap) -> Option<Vec<Token>> {
+    let mut args = args.iter().skip_while(|t| **t == Token::Whitespace);
+    let name = match args.next()? {
+        Token::Ident(name) if is_custom_property(name) => name,
+        _ => return None
+    };
+    if let Some(Value::Tokens(value)) = custom_properties.get(name) {
+        return Some(value.clone());
+    }
+    let mut rest = args.skip_while(|t| **t == Token::Whitespace);
+    match rest.next() {
+        Some(Token::Comma) => {
+            let fallback: Vec<Token> = rest.cloned().collect();
+            substitute(trim_whitespace(&fallback), custom_properties)
+        },
+        _ => None
+    }
+}
+
+// NOTE: 関数トークンの直後から数えて対応する ) の位置を返す。閉じられていなければ末尾
+fn closing_paren(tokens: &[Token], start: usize) -> usize {
+    let mut depth = 0;
+    for (i, token) in tokens.iter().enumerate().skip(start) {
+        match token {
+            Token::Function(_) | Token::OpenParen => depth += 1,
+            Token::CloseParen if depth == 0 => return i,
+            Token::CloseParen => depth -= 1,
+            _ => {}
+        }
+    }
+    tokens.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{resolve_custom_properties, substitute};
+    use crate::css::{Value, Token};
+    use crate::style::PropertyMap;
+
+    fn tokens(source: &str) -> Value {
+        match crate::css::parse(format!("a {{--x: {};}}", source)).rules[0] {
+            crate::css::CssRule::Style(ref rule) => rule.declarations[0].value.clone(),
+            _ => unreachable!()
+        }
+    }
+
+    fn to_tokens(value: Value) -> Vec<Token> {
+        match value {
+            Value::Tokens(tokens) => tokens,
+            value => panic!("Expected tokens but found {:?}", value)
+        }
+    }
+
+    #[test]
+    fn substitute_var_with_fallback() {
+        let mut custom_properties = PropertyMap::new();
+        custom_properties.insert("--a".to_string(), tokens("4px"));
+        assert_eq!(substitute(&to_tokens(tokens("var(--a) var(--b, 8px)")), &custom_properties), Some(to_tokens(tokens("4px 8px"))));
+        assert_eq!(substitute(&to_tokens(tokens("var(--b, var(--a))")), &custom_properties), Some(to_tokens(tokens("4px"))));
+        assert_eq!(substitute(&to_tokens(tokens("rgb(var(--b))")), &custom_properties), None);
+    }
+
+    #[test]
+    fn resolve_dependencies_and_cycles() {
+        let mut values = PropertyMap::new();
+        values.insert("--a".to_string(), tokens("var(--b) 1px"));
+        values.insert("--b".to_string(), tokens("2px"));
+        values.insert("--c".to_string(), tokens("var(--d)"));
+        values.insert("--d".to_string(), tokens("var(--c, 3px)"));
+        values.insert("--e".to_string(), tokens("var(--c, 4px)"));
+        values.insert("--f".to_string(), tokens("var(--missing)"));
+        resolve_custom_properties(&mut values);
+
+        assert_eq!(values.get("--a"), Some(&tokens("2px 1px")));
+        assert_eq!(values.get("--b"), Some(&tokens("2px")));
+        assert_eq!(values.get("--c"), None);
+        assert_eq!(values.get("--d"), None);
+        assert_eq!(values.get("--e"), Some(&tokens("4px")));
+        assert_eq!(values.get("--f"), None);
+    }
+}